@@ -3,8 +3,9 @@ use std::num::NonZeroU32;
 use serde::{Deserialize, Serialize};
 
 pub use libtruinlag::{
-    Challenge, CompletedChallenge, DetailedLocation, Event, MinimalLocation, PastGame,
-    PastGameInfo, Picture, Player, Team, TeamRole,
+    Announcement, CatchProposal, CatchStatus, Challenge, CompletedChallenge, DetailedLocation,
+    Event, InputChallenge, Message, MessageScope, MinimalLocation, PastGame, PastGameInfo, Picture,
+    Player, PlayerStats, Team, TeamRole,
 };
 
 pub mod api;
@@ -36,9 +37,30 @@ pub enum ToServer {
         team_id: usize,
     },
     SetPhoneNumber(Option<String>),
+    /// The language to get challenges in, e.g. "fr_ch", or `None` for the default
+    SetLanguage(Option<String>),
     SetTeamName(String),
     ListPastGames,
     GetPastGame(u64),
+    GetMyStats,
+    SendMessage {
+        scope: MessageScope,
+        text: String,
+        pictures: Vec<Vec<u8>>,
+    },
+    RequestMessages,
+    RequestAnnouncements,
+    AcknowledgeAnnouncement(usize),
+    ConfirmCatch(usize),
+    DisputeCatch {
+        proposal_id: usize,
+        reason: String,
+    },
+    /// Suggests a change to a challenge, which has to be reviewed unless the player is a reviewer
+    EditChallenge {
+        challenge: Box<InputChallenge>,
+        comment: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -83,6 +105,14 @@ pub enum ToApp {
     YouLeftGracePeriod(Everything),
     PastGame(PastGame),
     PastGameList(Vec<PastGameInfo>),
+    MyStats(PlayerStats),
+    Message(Message),
+    Messages(Vec<Message>),
+    Announcement(Announcement),
+    Announcements(Vec<Announcement>),
+    CatchPending(usize),
+    CatchProposed(CatchProposal),
+    CatchProposalUpdated(CatchProposal),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -97,12 +127,19 @@ pub enum ClientError {
     NotImplemented,       // Feature is not yet implemented
     TeamIsRunner(usize),  // A relevant team is runner, but has to be catcher
     TeamIsCatcher(usize), // A relevant team is catcher, but has to be runner
-    TeamsTooFar,          // Two relevant teams are too far away from each other
+    TeamsTooFar {
+        // Two relevant teams are too far away from each other, or their distance can't be checked
+        distance: Option<u32>,
+        allowed: u32,
+        reason: String,
+    },
     BadData(String),
-    TextError(String), // Some other kind of error with a custom text
-    PictureProblem,    // An Image-related error
-    TooRapid,          // When requests are sent too rapidly
-    TooFewChallenges,  // When there are too few challenges to start a game
+    TextError(String),       // Some other kind of error with a custom text
+    PictureProblem,          // An Image-related error
+    TooRapid,                // When requests are sent too rapidly
+    TooFewChallenges,        // When there are too few challenges to start a game
+    NotAtChallenge(String),  // The team isn't where the challenge has to be completed
+    Referenced(Vec<String>), // Something can't be removed because the listed entries refer to it
 }
 
 impl std::fmt::Display for ClientError {
@@ -118,7 +155,20 @@ impl std::fmt::Display for ClientError {
             Self::NotImplemented => write!(f, "Not yet implemented"),
             Self::TeamIsRunner(team) => write!(f, "team {} is runner", team),
             Self::TeamIsCatcher(team) => write!(f, "team {} is catcher", team),
-            Self::TeamsTooFar => write!(f, "the teams are too far away from each other"),
+            Self::TeamsTooFar {
+                distance: Some(_),
+                reason,
+                ..
+            } => write!(f, "the teams are too far away from each other: {}", reason),
+            Self::TeamsTooFar {
+                distance: None,
+                reason,
+                ..
+            } => write!(
+                f,
+                "the distance between the teams can't be checked: {}",
+                reason
+            ),
             Self::BadData(text) => write!(f, "bad data: {}", text),
             Self::TextError(text) => write!(f, "{}", text),
             Self::PictureProblem => write!(f, "there was a problem processing an image"),
@@ -127,6 +177,12 @@ impl std::fmt::Display for ClientError {
                 f,
                 "there are not enough challenges to start a game in the challenge db"
             ),
+            Self::NotAtChallenge(reason) => {
+                write!(f, "the challenge can't be completed here: {}", reason)
+            }
+            Self::Referenced(references) => {
+                write!(f, "still referred to by: {}", references.join(", "))
+            }
         }
     }
 }
@@ -148,12 +204,22 @@ impl TryFrom<libtruinlag::commands::Error> for ClientError {
             NotImplemented => Ok(Self::NotImplemented),
             TeamIsRunner(team) => Ok(Self::TeamIsRunner(team)),
             TeamIsCatcher(team) => Ok(Self::TeamIsCatcher(team)),
-            TeamsTooFar => Ok(Self::TeamsTooFar),
+            TeamsTooFar {
+                distance,
+                allowed,
+                reason,
+            } => Ok(Self::TeamsTooFar {
+                distance,
+                allowed,
+                reason,
+            }),
             BadData(text) => Ok(Self::BadData(text)),
             TextError(text) => Ok(Self::TextError(text)),
             PictureProblem => Ok(Self::PictureProblem),
             TooRapid => Ok(Self::TooRapid),
             TooFewChallenges => Ok(Self::TooFewChallenges),
+            NotAtChallenge(reason) => Ok(Self::NotAtChallenge(reason)),
+            Referenced(references) => Ok(Self::Referenced(references)),
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::codec::FrameCodec;
 use crate::commands::{
     BroadcastAction, ClientCommand, EngineAction, EngineAction::*, EngineCommandPackage,
     ResponseAction, ResponsePackage,
@@ -10,7 +11,7 @@ use futures::prelude::*;
 use std::sync::Arc;
 use tokio::net::UnixStream;
 use tokio::sync::{Mutex, mpsc, oneshot};
-use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+use tokio_util::codec::{FramedRead, FramedWrite};
 
 pub mod error;
 
@@ -65,7 +66,7 @@ where
         let mut id = 0;
         // The socket is wrapped in a `FramedWrite`, which handles packaging serialised commands
         // for sending them through a socket.
-        let mut transport = FramedWrite::new(socket_write, LengthDelimitedCodec::new());
+        let mut transport = FramedWrite::new(socket_write, FrameCodec::commands());
         // Awaiting send requests. Either waits or returns `Some` until all `SendConnection`s are
         // dropped. When that happens, the task exits silently, so that broadcasts can still be
         // received.
@@ -103,7 +104,7 @@ where
     // The `receive_manager` receives messages from truinlag, then deserialises and forwards them
     // to the `distributor`.
     let receive_manager = tokio::spawn(async move {
-        let mut transport = FramedRead::new(socket_read, LengthDelimitedCodec::new());
+        let mut transport = FramedRead::new(socket_read, FrameCodec::client_commands());
         while let Some(message) = transport.next().await {
             match message {
                 Ok(message) => {
@@ -155,7 +156,7 @@ where
                 }
                 DistributorMessage::Command(command) => match *command {
                     ClientCommand::Broadcast(msg) => {
-                        broadcast_send.send(msg).await.expect(
+                        broadcast_send.send(*msg).await.expect(
                             "Receiver handle can only be dropped if JoinHandle is dropped too",
                         );
                     }
@@ -168,7 +169,7 @@ where
                                 .send(msg.action.clone())
                                 .ok();
                         } else {
-                            msg_cache.push(msg);
+                            msg_cache.push(*msg);
                         }
                     }
                 },
@@ -260,6 +261,32 @@ impl SendConnection {
         }
     }
 
+    pub async fn get_stations(&mut self) -> Result<Vec<Station>> {
+        match self.send(EngineAction::GetStations).await? {
+            ResponseAction::SendStations(stations) => Ok(stations),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    /// Fetches a JSON archive of the whole database
+    pub async fn export_database(&mut self) -> Result<String> {
+        match self.send(EngineAction::ExportDatabase).await? {
+            ResponseAction::SendDatabase(archive) => Ok(archive),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    /// Replaces the whole database with a JSON archive made by `export_database`
+    pub async fn import_database(&mut self, archive: String) -> Result<()> {
+        match self.send(EngineAction::ImportDatabase(archive)).await? {
+            ResponseAction::Success => Ok(()),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
     pub async fn get_sectors(&mut self) -> Result<Vec<Sector>> {
         match self.send(EngineAction::GetSectors).await? {
             ResponseAction::SendSectors(sectors) => Ok(sectors),
@@ -276,6 +303,118 @@ impl SendConnection {
         }
     }
 
+    pub async fn delete_challenge(&mut self, id: u64) -> Result<()> {
+        match self.send(EngineAction::DeleteChallenge(id)).await? {
+            ResponseAction::Success => Ok(()),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    /// Patches all challenges matching the filter and returns them as they are after the patch,
+    /// see `EngineAction::PatchChallenges`.
+    pub async fn patch_challenges(
+        &mut self,
+        filter: ChallengeFilter,
+        patch: PartialInputChallenge,
+        add_sets: Vec<u64>,
+        remove_sets: Vec<u64>,
+        comment: Option<String>,
+        dry_run: bool,
+    ) -> Result<Vec<RawChallenge>> {
+        match self
+            .send(EngineAction::PatchChallenges {
+                filter: Box::new(filter),
+                patch: Box::new(patch),
+                add_sets,
+                remove_sets,
+                comment,
+                dry_run,
+            })
+            .await?
+        {
+            ResponseAction::SendRawChallenges(challenges) => Ok(challenges),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    /// Searches the challenges, returns the requested page and the total number of matches
+    pub async fn query_challenges(
+        &mut self,
+        filter: ChallengeFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<InputChallenge>, usize)> {
+        match self
+            .send(EngineAction::QueryChallenges {
+                filter,
+                offset,
+                limit,
+            })
+            .await?
+        {
+            ResponseAction::SendChallengePage { challenges, total } => Ok((challenges, total)),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_challenge_history(
+        &mut self,
+        challenge_id: u64,
+    ) -> Result<Vec<ChallengeRevision>> {
+        match self
+            .send(EngineAction::GetChallengeHistory(challenge_id))
+            .await?
+        {
+            ResponseAction::SendChallengeHistory(revisions) => Ok(revisions),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    /// Restores a challenge to one of its revisions, `author` is `None` for the organisers
+    pub async fn revert_challenge(
+        &mut self,
+        challenge_id: u64,
+        revision: usize,
+        author: Option<u64>,
+    ) -> Result<()> {
+        match self
+            .send(EngineAction::RevertChallenge {
+                challenge_id,
+                revision,
+                author,
+            })
+            .await?
+        {
+            ResponseAction::Success => Ok(()),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn edit_challenge(
+        &mut self,
+        challenge: InputChallenge,
+        author: u64,
+        comment: Option<String>,
+    ) -> Result<()> {
+        match self
+            .send(EngineAction::EditChallenge {
+                challenge,
+                author,
+                comment,
+            })
+            .await?
+        {
+            ResponseAction::Success => Ok(()),
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
     pub async fn get_global_state(&mut self) -> Result<(Vec<GameSession>, Vec<Player>)> {
         match self.send(EngineAction::GetState(None)).await? {
             ResponseAction::SendGlobalState { sessions, players } => Ok((sessions, players)),
@@ -352,6 +491,213 @@ impl SendConnection {
             other => Err(Error::InvalidSignal(format!("{:?}", other))),
         }
     }
+
+    pub async fn rename_challenge_set(&mut self, set_id: u64, name: String) -> Result<()> {
+        match self
+            .send(EngineAction::RenameChallengeSet { set_id, name })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::Success => Ok(()),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    /// Removes a challenge set, the strategy decides what happens to the challenges in it
+    pub async fn remove_challenge_set(
+        &mut self,
+        set_id: u64,
+        strategy: ReferenceStrategy,
+    ) -> Result<()> {
+        match self
+            .send(EngineAction::RemoveChallengeSet { set_id, strategy })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::Success => Ok(()),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn export_geojson(
+        &mut self,
+        session_id: u64,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> Result<String> {
+        match self
+            .send(ExportGeoJson {
+                session_id,
+                since,
+                until,
+            })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendGeoJson(geojson) => Ok(geojson),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn export_past_game(
+        &mut self,
+        past_game_id: u64,
+        format: ExportFormat,
+    ) -> Result<String> {
+        match self
+            .send(ExportPastGame {
+                past_game_id,
+                format,
+            })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendPastGameExport(export) => Ok(export),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_past_game_stats(&mut self, past_game_id: u64) -> Result<PastGameStats> {
+        match self.send(GetPastGameStats(past_game_id)).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendPastGameStats(stats) => Ok(stats),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_challenge_analytics(&mut self) -> Result<Vec<ChallengeAnalytics>> {
+        match self.send(GetChallengeAnalytics).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendChallengeAnalytics(analytics) => Ok(analytics),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_player_stats(&mut self, player_id: u64) -> Result<PlayerStats> {
+        match self.send(GetPlayerStats(player_id)).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendPlayerStats(stats) => Ok(stats),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_leaderboard(&mut self) -> Result<Vec<PlayerStats>> {
+        match self.send(GetLeaderboard).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendLeaderboard(leaderboard) => Ok(leaderboard),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn send_message(
+        &mut self,
+        session_id: u64,
+        scope: MessageScope,
+        text: String,
+    ) -> Result<()> {
+        match self
+            .send(SendMessage {
+                session_id,
+                author: None,
+                scope,
+                text,
+                pictures: Vec::new(),
+            })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::Success => Ok(()),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_messages(&mut self, session_id: u64) -> Result<Vec<Message>> {
+        match self
+            .send(GetMessages {
+                session_id,
+                team: None,
+            })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendMessages(messages) => Ok(messages),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn schedule_announcement(
+        &mut self,
+        session_id: u64,
+        target: MessageScope,
+        text: String,
+        time: Option<i64>,
+    ) -> Result<()> {
+        match self
+            .send(ScheduleAnnouncement {
+                session_id,
+                target,
+                text,
+                time,
+            })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::Success => Ok(()),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_announcements(&mut self, session_id: u64) -> Result<Vec<Announcement>> {
+        match self
+            .send(GetAnnouncements {
+                session_id,
+                team: None,
+            })
+            .await?
+        {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendAnnouncements(announcements) => Ok(announcements),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_disputed_catches(&mut self, session_id: u64) -> Result<Vec<CatchProposal>> {
+        match self.send(GetDisputedCatches(session_id)).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendCatchProposals(proposals) => Ok(proposals),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_sector_map(&mut self) -> Result<String> {
+        match self.send(GetSectorMap).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendGeoJson(geojson) => Ok(geojson),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_suspicious_locations(
+        &mut self,
+        session_id: u64,
+    ) -> Result<Vec<SuspiciousLocation>> {
+        match self.send(GetSuspiciousLocations(session_id)).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendSuspiciousLocations(locations) => Ok(locations),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
+
+    pub async fn get_location_quality(
+        &mut self,
+        session_id: u64,
+    ) -> Result<Vec<PlayerLocationQuality>> {
+        match self.send(GetLocationQuality(session_id)).await? {
+            ResponseAction::Error(err) => Err(Error::Truinlag(err)),
+            ResponseAction::SendLocationQuality(quality) => Ok(quality),
+            other => Err(Error::InvalidSignal(format!("{:?}", other))),
+        }
+    }
 }
 
 pub struct RecvConnection {
@@ -0,0 +1,91 @@
+use crate::commands::{
+    ClientCommand, EngineAction, EngineCommandPackage, MAX_ARCHIVE_FRAME_LENGTH, MAX_FRAME_LENGTH,
+    ResponseAction, ResponsePackage,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Frames messages between truinlag and its clients with a 4 byte big endian length prefix, like
+/// the default `LengthDelimitedCodec`. Frames are limited to `MAX_FRAME_LENGTH`, except for the
+/// ones carrying database archives, which may be up to `MAX_ARCHIVE_FRAME_LENGTH` long.
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+    /// how frames that may be longer than `MAX_FRAME_LENGTH` start
+    archive_prefix: Vec<u8>,
+}
+
+impl FrameCodec {
+    /// For truinlag reading commands, where only archive imports may be large
+    pub fn commands() -> Self {
+        Self::new(&EngineCommandPackage {
+            command: EngineAction::ImportDatabase(String::new()),
+            id: 0,
+        })
+    }
+
+    /// For clients reading responses and broadcasts, where only archive exports may be large
+    pub fn client_commands() -> Self {
+        Self::new(&ClientCommand::Response(Box::new(ResponsePackage {
+            action: ResponseAction::SendDatabase(String::new()),
+            id: 0,
+        })))
+    }
+
+    /// Takes a message with an empty archive, which has to end with the archive and an id. The
+    /// bytes before them are the prefix of all messages with an archive.
+    fn new(message: &impl serde::Serialize) -> Self {
+        let mut archive_prefix =
+            bincode::serialize(message).expect("messages should always be serializable");
+        // the length of the archive and the id are both 8 bytes long
+        archive_prefix.truncate(archive_prefix.len() - 16);
+        Self { archive_prefix }
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(head) = src.get(..4) else {
+            return Ok(None);
+        };
+        let length =
+            u32::from_be_bytes(head.try_into().expect("the head is 4 bytes long")) as usize;
+        if length > MAX_FRAME_LENGTH {
+            let Some(prefix) = src.get(4..4 + self.archive_prefix.len()) else {
+                return Ok(None);
+            };
+            if prefix != self.archive_prefix || length > MAX_ARCHIVE_FRAME_LENGTH {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("frame of {} bytes is too long", length),
+                ));
+            }
+        }
+        if src.len() < 4 + length {
+            // the prefix could be faked, so archives only get more space once they actually arrive
+            src.reserve((4 + length - src.len()).min(MAX_FRAME_LENGTH));
+            return Ok(None);
+        }
+        src.advance(4);
+        Ok(Some(src.split_to(length)))
+    }
+}
+
+impl Encoder<Bytes> for FrameCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, data: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if data.len() > MAX_ARCHIVE_FRAME_LENGTH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("frame of {} bytes is too long", data.len()),
+            ));
+        }
+        dst.reserve(4 + data.len());
+        dst.put_u32(data.len() as u32);
+        dst.extend_from_slice(&data);
+        Ok(())
+    }
+}
@@ -3,6 +3,14 @@ use std::num::NonZeroU32;
 use super::*;
 use serde::{Deserialize, Serialize};
 
+/// The largest message that can be sent between truinlag and its clients, in bytes. This is the
+/// default of `LengthDelimitedCodec`.
+pub const MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// The largest database archive that can be sent between truinlag and its clients, in bytes. This
+/// is well above `MAX_FRAME_LENGTH`, since archives contain all pictures.
+pub const MAX_ARCHIVE_FRAME_LENGTH: usize = 1024 * 1024 * 1024;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EngineCommandPackage {
     pub command: EngineAction,
@@ -26,8 +34,8 @@ impl From<ResponseAction> for EngineResponse {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum ClientCommand {
-    Broadcast(BroadcastAction),
-    Response(ResponsePackage),
+    Broadcast(Box<BroadcastAction>),
+    Response(Box<ResponsePackage>),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -99,6 +107,11 @@ pub enum EngineAction {
     Stop(u64),
     Ping(Option<String>),
     GetState(Option<u64>),
+    /// Like `GetState` for a session, but with the challenges in the player's language
+    GetPlayerState {
+        session_id: u64,
+        player_id: u64,
+    },
     MakeTeamCatcher {
         session_id: u64,
         team_id: usize,
@@ -123,7 +136,49 @@ pub enum EngineAction {
     },
     AddChallengeSet(String),
     GetChallengeSets,
+    RenameChallengeSet {
+        set_id: u64,
+        name: String,
+    },
+    RemoveChallengeSet {
+        set_id: u64,
+        strategy: ReferenceStrategy,
+    },
     DeleteAllChallenges,
+    DeleteChallenge(u64),
+    /// Changes a challenge on behalf of a player, following the review rules
+    EditChallenge {
+        challenge: InputChallenge,
+        author: u64,
+        comment: Option<String>,
+    },
+    GetChallengeHistory(u64),
+    /// Searches the challenges and responds with at most `limit` of them, skipping the first
+    /// `offset` results
+    QueryChallenges {
+        filter: ChallengeFilter,
+        offset: usize,
+        limit: usize,
+    },
+    /// Restores a challenge to an earlier revision
+    RevertChallenge {
+        challenge_id: u64,
+        revision: usize,
+        author: Option<u64>,
+    },
+    /// Applies the patch to all challenges that match the filter. The sets are replaced if the
+    /// patch contains sets, but they can also be added to or removed from the challenges'
+    /// existing sets. Responds with the patched challenges, which aren't saved if `dry_run` is set.
+    /// Challenges that the patch doesn't change are left out. The filter can't search for text,
+    /// since a fuzzy search could catch challenges that weren't meant to be changed.
+    PatchChallenges {
+        filter: Box<ChallengeFilter>,
+        patch: Box<PartialInputChallenge>,
+        add_sets: Vec<u64>,
+        remove_sets: Vec<u64>,
+        comment: Option<String>,
+        dry_run: bool,
+    },
     GetAllZones,
     AddZone {
         zone: u64,
@@ -133,11 +188,32 @@ pub enum EngineAction {
         mongus: bool,
         s_bahn_zone: bool,
     },
+    UpdateZone {
+        zone_id: u64,
+        zone: Option<u64>,
+        num_conn_zones: Option<u64>,
+        num_connections: Option<u64>,
+        train_through: Option<bool>,
+        mongus: Option<bool>,
+        s_bahn_zone: Option<bool>,
+    },
+    RemoveZone {
+        zone_id: u64,
+        strategy: ReferenceStrategy,
+    },
     AddMinutesTo {
         from_zone: u64,
         to_zone: u64,
         minutes: u64,
     },
+    AddTimedMinutesTo {
+        from_zone: u64,
+        to_zone: u64,
+        from: chrono::NaiveTime,
+        until: chrono::NaiveTime,
+        minutes: u64,
+    },
+    ImportZoneBoundaries(String),
     GetEvents(u64),
     UploadPeriodPictures {
         session_id: u64,
@@ -176,11 +252,27 @@ pub enum EngineAction {
         team_id: usize,
     },
     SetPlayerPhoneNumber(u64, Option<String>),
+    SetPlayerReviewer(u64, bool),
+    /// Sets the language a player wants challenges in, `None` for the default
+    SetPlayerLanguage(u64, Option<String>),
     AddSector(char),
     AddNeighbourhood(u64, u64),
     RemoveNeighbourhood(u64, u64),
     GetSectors,
-    RemoveSector(u64),
+    RenameSector {
+        sector_id: u64,
+        name: char,
+    },
+    RemoveSector {
+        sector_id: u64,
+        strategy: ReferenceStrategy,
+    },
+    ImportSectorBoundaries(String),
+    GetSectorMap,
+    ImportStations(Vec<Station>),
+    GetStations,
+    ExportDatabase,
+    ImportDatabase(String),
     SetCloseSectors {
         zone_id: u64,
         sector_ids: Vec<u64>,
@@ -188,6 +280,64 @@ pub enum EngineAction {
     ListPastGames,
     ListPastGamesOfPlayer(u64),
     GetPastGame(u64),
+    ExportGeoJson {
+        session_id: u64,
+        since: Option<Timestamp>,
+        until: Option<Timestamp>,
+    },
+    ExportPastGame {
+        past_game_id: u64,
+        format: ExportFormat,
+    },
+    GetPastGameStats(u64),
+    GetChallengeAnalytics,
+    GetPlayerStats(u64),
+    GetLeaderboard,
+    SendMessage {
+        session_id: u64,
+        author: Option<u64>,
+        scope: MessageScope,
+        text: String,
+        pictures: Vec<RawPicture>,
+    },
+    GetMessages {
+        session_id: u64,
+        team: Option<usize>,
+    },
+    ScheduleAnnouncement {
+        session_id: u64,
+        target: MessageScope,
+        text: String,
+        time: Option<Timestamp>,
+    },
+    GetAnnouncements {
+        session_id: u64,
+        team: Option<usize>,
+    },
+    AcknowledgeAnnouncement {
+        session_id: u64,
+        player: u64,
+        announcement_id: usize,
+    },
+    ConfirmCatch {
+        session_id: u64,
+        player: u64,
+        proposal_id: usize,
+    },
+    DisputeCatch {
+        session_id: u64,
+        player: u64,
+        proposal_id: usize,
+        reason: String,
+    },
+    GetDisputedCatches(u64),
+    ResolveDisputedCatch {
+        session_id: u64,
+        proposal_id: usize,
+        uphold: bool,
+    },
+    GetSuspiciousLocations(u64),
+    GetLocationQuality(u64),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -221,6 +371,26 @@ pub enum ResponseAction {
     SendSectors(Vec<Sector>),
     SendPastGameList(Vec<PastGameInfo>),
     SendPastGame(PastGame),
+    SendGeoJson(String),
+    SendPastGameExport(String),
+    SendPastGameStats(PastGameStats),
+    SendChallengeAnalytics(Vec<ChallengeAnalytics>),
+    SendPlayerStats(PlayerStats),
+    SendLeaderboard(Vec<PlayerStats>),
+    SendMessages(Vec<Message>),
+    SendAnnouncements(Vec<Announcement>),
+    CatchPending(usize),
+    SendCatchProposals(Vec<CatchProposal>),
+    SendSuspiciousLocations(Vec<SuspiciousLocation>),
+    SendLocationQuality(Vec<PlayerLocationQuality>),
+    SendStations(Vec<Station>),
+    SendDatabase(String),
+    SendChallengeHistory(Vec<ChallengeRevision>),
+    SendChallengePage {
+        challenges: Vec<InputChallenge>,
+        /// the number of challenges matching the query, including the ones on other pages
+        total: usize,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -232,6 +402,8 @@ pub enum BroadcastAction {
     Completed {
         completer: Team,
         completed: Challenge,
+        /// the completed challenge in other languages, by language
+        translations: std::collections::HashMap<String, Challenge>,
     },
     Started {
         teams: Vec<Team>,
@@ -258,6 +430,30 @@ pub enum BroadcastAction {
     TeamMadeCatcher(Team),
     TeamMadeRunner(Team),
     TeamLeftGracePeriod(Team),
+    Message {
+        session: u64,
+        recipients: Vec<usize>,
+        message: Message,
+    },
+    Announcement {
+        session: u64,
+        recipients: Vec<usize>,
+        announcement: Announcement,
+    },
+    CatchProposed {
+        session: u64,
+        proposal: CatchProposal,
+    },
+    CatchProposalUpdated {
+        session: u64,
+        proposal: CatchProposal,
+    },
+    TeamSplit {
+        session: u64,
+        team: usize,
+        player: u64,
+        distance: u32,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -274,12 +470,19 @@ pub enum Error {
     NotImplemented,    // Feature is not yet implemented
     TeamIsRunner(usize), // A relevant team is runner, but has to be catcher
     TeamIsCatcher(usize), // A relevant team is catcher, but has to be runner
-    TeamsTooFar,       // Two relevant teams are too far away from each other
+    TeamsTooFar {
+        // Two relevant teams are too far away from each other, or their distance can't be checked
+        distance: Option<u32>,
+        allowed: u32,
+        reason: String,
+    },
     BadData(String),
-    TextError(String), // Some other kind of error with a custom text
-    PictureProblem,    // An Image-related error
-    TooRapid,          // When requests are sent too rapidly
-    TooFewChallenges,  // When there are too few challenges to start a game
+    TextError(String),       // Some other kind of error with a custom text
+    PictureProblem,          // An Image-related error
+    TooRapid,                // When requests are sent too rapidly
+    TooFewChallenges,        // When there are too few challenges to start a game
+    NotAtChallenge(String),  // The team isn't where the challenge has to be completed
+    Referenced(Vec<String>), // Something can't be removed because the listed entries refer to it
 }
 
 impl std::fmt::Display for Error {
@@ -303,7 +506,20 @@ impl std::fmt::Display for Error {
             Self::NotImplemented => write!(f, "Not yet implemented"),
             Self::TeamIsRunner(team) => write!(f, "team {} is runner", team),
             Self::TeamIsCatcher(team) => write!(f, "team {} is catcher", team),
-            Self::TeamsTooFar => write!(f, "the teams are too far away from each other"),
+            Self::TeamsTooFar {
+                distance: Some(_),
+                reason,
+                ..
+            } => write!(f, "the teams are too far away from each other: {}", reason),
+            Self::TeamsTooFar {
+                distance: None,
+                reason,
+                ..
+            } => write!(
+                f,
+                "the distance between the teams can't be checked: {}",
+                reason
+            ),
             Self::BadData(text) => write!(f, "bad data: {}", text),
             Self::TextError(text) => write!(f, "{}", text),
             Self::PictureProblem => write!(f, "there was a problem processing an image"),
@@ -315,6 +531,12 @@ impl std::fmt::Display for Error {
                 f,
                 "there are not enough challenges to start a game in the challenge db"
             ),
+            Self::NotAtChallenge(reason) => {
+                write!(f, "the challenge can't be completed here: {}", reason)
+            }
+            Self::Referenced(references) => {
+                write!(f, "still referred to by: {}", references.join(", "))
+            }
         }
     }
 }
@@ -6,6 +6,9 @@ use std::str::FromStr;
 #[cfg(feature = "api")]
 pub mod api;
 
+#[cfg(feature = "codec")]
+pub mod codec;
+
 pub mod commands;
 
 type Timestamp = i64; // seconds since epoch
@@ -19,6 +22,13 @@ pub struct GameConfig {
     pub start_time: chrono::NaiveTime,
     pub end_time: chrono::NaiveTime,
     pub challenge_sets: Vec<u64>,
+    pub catch_confirmation: bool,
+    pub catch_radius: f64,
+    pub catch_accuracy_margin: f64,
+    /// in seconds
+    pub max_location_age: u64,
+    pub location_policy: LocationPolicy,
+    pub completion_verification: VerificationMode,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +56,16 @@ impl MinimalLocation {
     pub fn as_point(&self) -> geo::Point<f64> {
         geo::Point::from((self.latitude as f64, self.longitude as f64))
     }
+
+    /// Returns the geodesic distance to another location in metres
+    pub fn distance_to(&self, other: &MinimalLocation) -> f64 {
+        use geo::Distance;
+        // geo wants points as (longitude, latitude)
+        geo::Geodesic.distance(
+            geo::Point::new(self.longitude as f64, self.latitude as f64),
+            geo::Point::new(other.longitude as f64, other.latitude as f64),
+        )
+    }
 }
 
 impl From<DetailedLocation> for MinimalLocation {
@@ -71,6 +91,13 @@ pub struct Colour {
     pub b: u8,
 }
 
+impl Colour {
+    /// Formats the colour as a hex string like `#ff8000`, as used by most mapping tools.
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
 /// A binary JPEG-encoded picture. It *should* always be a valid JPEG.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RawPicture {
@@ -137,7 +164,7 @@ pub struct GameSession {
     pub id: u64,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
 pub enum ChallengeType {
     Kaff,
     Ortsspezifisch,
@@ -166,13 +193,87 @@ impl FromStr for ChallengeType {
     }
 }
 
+/// The file formats that games can be exported to for viewing them in mapping tools
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ExportFormat {
+    GeoJson,
+    Gpx,
+    Kml,
+}
+
+impl FromStr for ExportFormat {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace("_", "").as_str() {
+            "geojson" | "json" => Ok(Self::GeoJson),
+            "gpx" => Ok(Self::Gpx),
+            "kml" => Ok(Self::Kml),
+            _ => Err(TextError(format!(
+                "failed parsing \"{}\" as ExportFormat",
+                s
+            ))),
+        }
+    }
+}
+
+/// A saved version of a challenge. A revision is added whenever a challenge is changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeRevision {
+    /// counts the revisions of a challenge, starting at 0
+    pub revision: usize,
+    /// the player that made the change, `None` if it was made by the organisers
+    pub author: Option<u64>,
+    pub time: Timestamp,
+    pub comment: Option<String>,
+    /// the challenge as it was after the change
+    pub challenge: InputChallenge,
+}
+
+/// Selects challenges, e.g. to edit many of them at once. A challenge has to meet all criteria and
+/// an empty criterion is met by every challenge.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChallengeFilter {
+    pub ids: Vec<u64>,
+    pub kinds: Vec<ChallengeType>,
+    pub statuses: Vec<ChallengeStatus>,
+    /// the ids of the challenge sets, the challenge has to be in at least one of them
+    pub sets: Vec<u64>,
+    /// the ids of the zones, the challenge has to be in at least one of them
+    pub zones: Vec<u64>,
+    /// the ids of the sectors, the challenge has to be in at least one of them
+    pub sectors: Vec<u64>,
+    /// challenges without a kaffskala don't match if this is set
+    #[serde(default)]
+    pub kaffskala: Option<std::ops::RangeInclusive<u8>>,
+    /// challenges without a grade don't match if this is set
+    #[serde(default)]
+    pub grade: Option<std::ops::RangeInclusive<u8>>,
+    /// words that have to appear in the title or description, typos are tolerated. Only for
+    /// queries, patches refuse it.
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// What happens to the entries that refer to something that is removed, e.g. the challenges in a
+/// challenge set that is removed
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ReferenceStrategy {
+    /// Don't remove anything if it is referred to and list everything that refers to it instead
+    Refuse,
+    /// Remove the references along with it
+    Cascade,
+    /// Refer to the entry with the given id instead
+    Reassign(u64),
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub enum TeamRole {
     Runner,
     Catcher,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 pub enum RandomPlaceType {
     Zone,
     SBahnZone,
@@ -224,7 +325,7 @@ impl FromStr for ChallengeStatus {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ChallengeActionEntry {
     UncompletableMinutes(Option<u64>), // None -> uses repetitions (%r)
 
@@ -242,7 +343,7 @@ pub struct ChallengeSet {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, partially::Partial)]
-#[partially(derive(Clone, Debug))]
+#[partially(derive(Clone, Debug, Serialize, Deserialize))]
 pub struct RawChallenge {
     pub kind: ChallengeType,
     pub sets: Vec<ChallengeSet>,
@@ -251,6 +352,9 @@ pub struct RawChallenge {
     pub description: Option<String>,
     pub random_place: Option<RandomPlaceType>,
     pub place: Option<String>,
+    /// the latitude and longitude of the place, used to check completions
+    #[serde(default)]
+    pub place_coordinates: Option<(f32, f32)>,
     pub comment: String,
     pub kaffskala: Option<u8>,
     pub grade: Option<u8>,
@@ -287,6 +391,7 @@ impl From<RawChallenge> for InputChallenge {
             description: challenge.description,
             random_place: challenge.random_place,
             place: challenge.place,
+            place_coordinates: challenge.place_coordinates,
             comment: challenge.comment,
             kaffskala: challenge.kaffskala,
             grade: challenge.grade,
@@ -315,7 +420,7 @@ impl From<RawChallenge> for InputChallenge {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, partially::Partial)]
-#[partially(derive(Clone, Debug))]
+#[partially(derive(Clone, Debug, Serialize, Deserialize, Default))]
 pub struct InputChallenge {
     pub kind: ChallengeType,
     pub sets: Vec<u64>,
@@ -324,6 +429,9 @@ pub struct InputChallenge {
     pub description: Option<String>,
     pub random_place: Option<RandomPlaceType>,
     pub place: Option<String>,
+    /// the latitude and longitude of the place, used to check completions
+    #[serde(default)]
+    pub place_coordinates: Option<(f32, f32)>,
     pub comment: String,
     pub kaffskala: Option<u8>,
     pub grade: Option<u8>,
@@ -350,10 +458,18 @@ pub struct InputChallenge {
 }
 
 impl InputChallenge {
-    pub fn check_validity(&self) -> Result<(), TextError> {
+    /// Checks whether the challenge has everything it needs for its type. If any stations are
+    /// given, the place also has to be one of them.
+    pub fn check_validity(&self, stations: &[Station]) -> Result<(), TextError> {
         if matches!(self.status, ChallengeStatus::ToSort) {
             return Ok(());
         }
+        if let Some(place) = &self.place
+            && !stations.is_empty()
+            && !stations.iter().any(|s| s.is_called(place))
+        {
+            return Err(TextError(format!("place {} is not a known station", place)));
+        }
         if self.sets.is_empty() {
             return Err("has no challenge sets".into());
         }
@@ -403,8 +519,8 @@ impl InputChallenge {
         Ok(())
     }
 
-    pub fn is_valid(&self) -> bool {
-        self.check_validity().is_ok()
+    pub fn is_valid(&self, stations: &[Station]) -> bool {
+        self.check_validity(stations).is_ok()
     }
 }
 
@@ -417,10 +533,33 @@ pub struct Zone {
     pub mongus: bool,
     pub s_bahn_zone: bool,
     pub minutes_to: std::collections::HashMap<u64, u64>,
+    pub travel_bands: Vec<TravelBand>,
     pub close_sectors: Vec<Sector>,
     pub id: u64,
 }
 
+/// Travel durations between zones that apply during a part of the day, i.e. in the evening when
+/// fewer trains run. `until` may be before `from` for bands that span midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TravelBand {
+    pub from: chrono::NaiveTime,
+    pub until: chrono::NaiveTime,
+    /// Key is the target zone id and value is the time in minutes required to travel there. Holds
+    /// every zone, not just the ones whose travel durations differ from the zone's `minutes_to`.
+    pub minutes_to: std::collections::HashMap<u64, u64>,
+}
+
+impl TravelBand {
+    /// Whether the band applies at the given time of day
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.from <= self.until {
+            self.from <= time && time < self.until
+        } else {
+            time >= self.from || time < self.until
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sector {
     pub name: char,
@@ -443,6 +582,8 @@ pub struct Team {
     pub location: Option<DetailedLocation>,
     pub grace_period_end: Option<Timestamp>,
     pub period_id: usize,
+    /// the number of the zone the team is in, as far as truinlag knows
+    pub zone: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -452,6 +593,12 @@ pub struct Player {
     pub session: Option<u64>,
     pub picture_id: Option<u64>,
     pub phone_number: Option<String>,
+    /// whether the player may approve challenges
+    #[serde(default)]
+    pub reviewer: bool,
+    /// the language the player wants challenges in, e.g. "fr_ch"
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -471,6 +618,8 @@ pub struct CompletedChallenge {
     pub picture_ids: Vec<u64>,
     pub not_completed: Vec<Challenge>,
     pub id: u64,
+    /// whether the team was at the challenge's location, if that was checked
+    pub verification: Option<CompletionVerification>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
@@ -626,3 +775,323 @@ pub struct PastGame {
     pub events: Vec<Event>,
     pub id: u64,
 }
+
+/// A challenge that was skipped, i.e. that was open while a team completed a different one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedChallenge {
+    pub title: String,
+    pub times: u32,
+    pub id: u64,
+}
+
+/// Statistics about a single team in a past game. Durations are in seconds and distances in
+/// metres.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamStats {
+    pub name: String,
+    pub points: u64,
+    pub distance_travelled: f64,
+    pub points_per_hour: f64,
+    pub catches: u32,
+    pub times_caught: u32,
+    pub completed_challenges: u32,
+    pub average_challenge_points: f64,
+    pub time_as_catcher: i64,
+    pub time_as_runner: i64,
+    pub most_skipped: Vec<SkippedChallenge>,
+    pub team_id: usize,
+}
+
+/// Statistics about a past game as a whole and about each of its teams. Durations are in seconds
+/// and distances in metres.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PastGameStats {
+    pub name: String,
+    pub duration: i64,
+    pub teams: Vec<TeamStats>,
+    pub total_points: u64,
+    pub total_distance: f64,
+    pub total_catches: u32,
+    pub total_completed_challenges: u32,
+    pub average_challenge_points: f64,
+    pub most_skipped: Vec<SkippedChallenge>,
+    pub id: u64,
+}
+
+/// How a challenge fared across all past games. A challenge is offered whenever it is one of a
+/// team's open challenges, chosen when the team completes it and skipped when the team completes a
+/// different one instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeAnalytics {
+    pub title: String,
+    pub offered: u32,
+    pub chosen: u32,
+    pub skipped: u32,
+    /// the average points the challenge was worth when it was completed
+    pub average_points: Option<f64>,
+    /// the average number of seconds it took from getting the challenge to completing it
+    pub average_completion_time: Option<i64>,
+    pub id: u64,
+}
+
+/// A player's all-time statistics across all past games
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub name: String,
+    pub games_played: u32,
+    /// the points of the player's teams, split evenly among the team members
+    pub points_contributed: u64,
+    pub catches: u32,
+    pub times_caught: u32,
+    pub challenges_completed: u32,
+    /// the zones the player completed the most challenges in as (zone number, challenges) pairs,
+    /// most frequent first
+    pub favourite_zones: Vec<(u64, u32)>,
+    pub id: u64,
+}
+
+/// Who a chat message is addressed to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum MessageScope {
+    /// only the members of the given team
+    Team(usize),
+    /// all teams that are catchers at the time of sending
+    Catchers,
+    /// everyone in the session
+    All,
+    /// only the organisers of the game, i.e. whoever is running it with tredit
+    Organisers,
+}
+
+impl FromStr for MessageScope {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "all" | "everyone" => Ok(Self::All),
+            "catchers" => Ok(Self::Catchers),
+            "organisers" | "organizers" => Ok(Self::Organisers),
+            _ => match s.parse() {
+                Ok(team) => Ok(Self::Team(team)),
+                Err(_) => Err(TextError(format!(
+                    "failed parsing \"{}\" as MessageScope",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+/// A chat message within a session. Messages sent by the organisers have no author and no team.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub scope: MessageScope,
+    pub text: String,
+    pub picture_ids: Vec<u64>,
+    pub author: Option<u64>,
+    pub team: Option<usize>,
+    pub time: Timestamp,
+    pub id: usize,
+}
+
+/// An announcement from the organisers. Announcements can be scheduled in advance and are kept
+/// after delivery, so that players who join later can still read them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Announcement {
+    pub target: MessageScope,
+    pub text: String,
+    /// when the announcement is or was delivered
+    pub time: Timestamp,
+    pub delivered: bool,
+    /// the players that have acknowledged the announcement
+    pub acknowledged_by: Vec<u64>,
+    pub id: usize,
+}
+
+/// What became of a catch proposal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum CatchStatus {
+    /// waiting for the caught team to confirm or dispute the catch
+    Pending,
+    /// confirmed by the caught team
+    Confirmed,
+    /// the caught team didn't react in time, so the catch went through
+    TimedOut,
+    /// disputed by the caught team and waiting for the organisers
+    Disputed,
+    /// disputed, but the organisers decided that the catch counts
+    Upheld,
+    /// disputed and the organisers decided that the catch doesn't count
+    Rejected,
+    /// the catch could no longer be carried out, e.g. because the game ended
+    Void,
+}
+
+/// A catch that has been proposed by the catcher team, but not yet carried out. Only used if
+/// catch confirmation is turned on in the game config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatchProposal {
+    pub catcher: usize,
+    pub caught: usize,
+    pub status: CatchStatus,
+    pub time: Timestamp,
+    /// when the catch goes through if the caught team doesn't react
+    pub deadline: Timestamp,
+    pub dispute_reason: Option<String>,
+    pub id: usize,
+}
+
+/// What happens to a catch if the location of one of the teams is missing or too old to check
+/// whether the teams are close enough to each other
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum LocationPolicy {
+    /// the catch goes through anyways
+    #[default]
+    Allow,
+    /// the catch is refused
+    Reject,
+    /// the caught team has to confirm the catch, even if catch confirmation is turned off
+    RequireConfirmation,
+}
+
+impl FromStr for LocationPolicy {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "allow" => Ok(Self::Allow),
+            "reject" => Ok(Self::Reject),
+            "confirm" | "require_confirmation" => Ok(Self::RequireConfirmation),
+            _ => Err(TextError(format!(
+                "failed parsing \"{}\" as LocationPolicy",
+                s
+            ))),
+        }
+    }
+}
+
+/// Why a location sent by a player was deemed implausible
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum LocationSuspicion {
+    /// the location's timestamp lies this many seconds in the future
+    FutureTimestamp(i64),
+    /// the location is older than the last location sent by the same player
+    OutOfOrder,
+    /// The player would have had to move this fast (in m/s) to get to the location, which is
+    /// faster than the speed reported by their phone or faster than public transport can go.
+    TooFast {
+        implied_speed: f32,
+        reported_speed: f32,
+    },
+}
+
+/// A location that was sent by a player, but not used as their team's location because it seemed
+/// implausible
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuspiciousLocation {
+    pub team: usize,
+    pub player: u64,
+    pub location: DetailedLocation,
+    pub suspicion: LocationSuspicion,
+    /// when the location was received by truinlag
+    pub received: Timestamp,
+}
+
+/// How well the location tracking of a single player works
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerLocationQuality {
+    pub team: usize,
+    pub player: u64,
+    /// the number of plausible locations the player sent
+    pub received: u32,
+    /// the number of locations that were used for the team's location
+    pub used: u32,
+    /// the number of locations that were ignored because they seemed implausible
+    pub suspicious: u32,
+    /// the average accuracy of the plausible locations in metres
+    pub average_accuracy: Option<f32>,
+    pub last_location: Option<Timestamp>,
+    /// whether the player is currently far away from the rest of their team
+    pub separated: bool,
+    pub times_separated: u32,
+}
+
+/// How strictly truinlag checks that a team is at a challenge's location when it completes it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum VerificationMode {
+    /// locations aren't checked at all
+    #[default]
+    Off,
+    /// locations are checked and the result is stored with the completion, but it always goes
+    /// through
+    Warn,
+    /// completions away from the challenge's location, or without a recent location, are refused
+    Enforce,
+}
+
+impl FromStr for VerificationMode {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "enforce" => Ok(Self::Enforce),
+            _ => Err(TextError(format!(
+                "failed parsing \"{}\" as VerificationMode",
+                s
+            ))),
+        }
+    }
+}
+
+/// The result of checking a team's location when it completed a challenge
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum CompletionVerification {
+    /// the challenge has no place coordinates or outlines that could be checked
+    Unchecked,
+    /// the team's location was missing or too old
+    NoLocation,
+    /// the team was at the challenge's location
+    Verified,
+    /// the team was somewhere else, for the given reason
+    Failed(String),
+}
+
+/// A public transport station, used to find the coordinates of the places of challenges
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Station {
+    pub name: String,
+    pub latitude: f32,
+    pub longitude: f32,
+    /// the number of the zone the station is in, if known
+    pub zone: Option<u64>,
+    /// other names of the station, e.g. without the name of the town
+    pub aliases: Vec<String>,
+    /// `None` for stations that aren't in the db yet
+    pub id: Option<u64>,
+}
+
+impl Station {
+    /// Whether the station goes by the given name or one of its aliases, see
+    /// `normalise_station_name`
+    pub fn is_called(&self, name: &str) -> bool {
+        let name = normalise_station_name(name);
+        normalise_station_name(&self.name) == name
+            || self
+                .aliases
+                .iter()
+                .any(|a| normalise_station_name(a) == name)
+    }
+}
+
+/// Brings a station name into a form that can be compared, ignoring case, surrounding whitespace
+/// and the different ways the town can be separated from the station, i.e. "Zürich, Hardbrücke"
+/// and "zürich hardbrücke" are the same.
+pub fn normalise_station_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -5,7 +5,6 @@ use libtruinlag::commands::{BroadcastAction, EngineAction, ResponseAction};
 use libtruinlag::{RawPicture, api};
 use std::error::Error;
 use tokio::io::{AsyncReadExt, ReadHalf, WriteHalf};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tokio_rustls::rustls::pki_types::pem::PemObject;
@@ -22,7 +21,10 @@ async fn get_everything(
 ) -> Everything {
     match response_to_to_app(
         truin_tx
-            .send(EngineAction::GetState(Some(session)))
+            .send(EngineAction::GetPlayerState {
+                session_id: session,
+                player_id,
+            })
             .await
             .unwrap(),
         player_id,
@@ -96,6 +98,22 @@ fn response_to_to_app(response: ResponseAction, player_id: u64, session_id: u64)
         }),
         SendGameConfig(_) => None,
         SendSectors(_) => None,
+        SendGeoJson(_) => None,
+        SendPastGameExport(_) => None,
+        SendPastGameStats(_) => None,
+        SendChallengeAnalytics(_) => None,
+        SendPlayerStats(stats) => Some(ToApp::MyStats(stats)),
+        SendLeaderboard(_) => None,
+        SendMessages(messages) => Some(ToApp::Messages(messages)),
+        SendAnnouncements(announcements) => Some(ToApp::Announcements(announcements)),
+        CatchPending(proposal_id) => Some(ToApp::CatchPending(proposal_id)),
+        SendCatchProposals(_) => None,
+        SendSuspiciousLocations(_) => None,
+        SendLocationQuality(_) => None,
+        SendStations(_) => None,
+        SendDatabase(_) => None,
+        SendChallengeHistory(_) => None,
+        SendChallengePage { .. } => None,
     }
 }
 
@@ -153,10 +171,19 @@ async fn broadcast_to_to_app(
         Completed {
             completer,
             completed,
+            mut translations,
         } => {
             let everything = get_everything(player_id, truin_tx, session).await;
+            let language = everything
+                .teams
+                .iter()
+                .flat_map(|t| &t.players)
+                .find(|p| p.id == player_id)
+                .and_then(|p| p.language.as_ref());
             let event = Event::Complete {
-                challenge: completed,
+                challenge: language
+                    .and_then(|l| translations.remove(l))
+                    .unwrap_or(completed),
                 completer_id: completer.id,
                 time: 0,
                 picture_ids: Vec::new(),
@@ -219,6 +246,54 @@ async fn broadcast_to_to_app(
                 None
             }
         }
+        Message {
+            session: message_session,
+            recipients,
+            message,
+        } => {
+            if message_session == session && recipients.contains(&team_id) {
+                Some(ToApp::Message(message))
+            } else {
+                None
+            }
+        }
+        Announcement {
+            session: announcement_session,
+            recipients,
+            announcement,
+        } => {
+            if announcement_session == session && recipients.contains(&team_id) {
+                Some(ToApp::Announcement(announcement))
+            } else {
+                None
+            }
+        }
+        CatchProposed {
+            session: proposal_session,
+            proposal,
+        } => {
+            if proposal_session == session
+                && (proposal.catcher == team_id || proposal.caught == team_id)
+            {
+                Some(ToApp::CatchProposed(proposal))
+            } else {
+                None
+            }
+        }
+        CatchProposalUpdated {
+            session: proposal_session,
+            proposal,
+        } => {
+            if proposal_session == session
+                && (proposal.catcher == team_id || proposal.caught == team_id)
+            {
+                Some(ToApp::CatchProposalUpdated(proposal))
+            } else {
+                None
+            }
+        }
+        // separated players are only the organisers' business
+        TeamSplit { .. } => None,
         PlayerDeleted(player) => {
             if player.id == player_id {
                 panic!(
@@ -252,6 +327,69 @@ fn to_server_to_engine_command(
 ) -> EngineCommandConversion {
     use ToServer::*;
     match to_server {
+        ConfirmCatch(proposal_id) => EngineAction::ConfirmCatch {
+            session_id: session,
+            player: player_id,
+            proposal_id,
+        }
+        .into(),
+        DisputeCatch {
+            proposal_id,
+            reason,
+        } => EngineAction::DisputeCatch {
+            session_id: session,
+            player: player_id,
+            proposal_id,
+            reason,
+        }
+        .into(),
+        EditChallenge { challenge, comment } => EngineAction::EditChallenge {
+            challenge: *challenge,
+            author: player_id,
+            comment,
+        }
+        .into(),
+        RequestAnnouncements => EngineAction::GetAnnouncements {
+            session_id: session,
+            team: Some(team_id),
+        }
+        .into(),
+        AcknowledgeAnnouncement(announcement_id) => EngineAction::AcknowledgeAnnouncement {
+            session_id: session,
+            player: player_id,
+            announcement_id,
+        }
+        .into(),
+        RequestMessages => EngineAction::GetMessages {
+            session_id: session,
+            team: Some(team_id),
+        }
+        .into(),
+        SendMessage {
+            scope,
+            text,
+            pictures,
+        } => EngineCommandConversion::Delayed(Box::pin(async move {
+            let pictures = tokio::task::block_in_place(|| {
+                pictures
+                    .into_iter()
+                    .filter_map(|p| RawPicture::from_bytes(p).ok())
+                    .collect()
+            });
+            // the app doesn't need to know its own team id to message its team
+            let scope = match scope {
+                MessageScope::Team(_) => MessageScope::Team(team_id),
+                other => other,
+            };
+            EngineAction::SendMessage {
+                session_id: session,
+                author: Some(player_id),
+                scope,
+                text,
+                pictures,
+            }
+        })),
+        GetMyStats => EngineAction::GetPlayerStats(player_id).into(),
         GetPastGame(past_game_id) => EngineAction::GetPastGame(past_game_id).into(),
         ListPastGames => EngineAction::ListPastGamesOfPlayer(player_id).into(),
         SetTeamName(name) => EngineAction::RenameTeam {
@@ -263,6 +401,7 @@ fn to_server_to_engine_command(
         SetPhoneNumber(phone_number) => {
             EngineAction::SetPlayerPhoneNumber(player_id, phone_number).into()
         }
+        SetLanguage(language) => EngineAction::SetPlayerLanguage(player_id, language).into(),
         Login(passphrase) => EngineAction::GetPlayerByPassphrase(passphrase).into(),
         Location(location) => EngineAction::SendLocation {
             session_id: session,
@@ -319,7 +458,11 @@ fn to_server_to_engine_command(
         }
         .into(),
         Ping(mayssage) => EngineAction::Ping(mayssage).into(),
-        RequestEverything => EngineAction::GetState(Some(session)).into(),
+        RequestEverything => EngineAction::GetPlayerState {
+            session_id: session,
+            player_id,
+        }
+        .into(),
         RequestPictures(pictures) => EngineAction::GetPictures(pictures).into(),
         RequestThumbnails(thumbnails) => EngineAction::GetThumbnails(thumbnails).into(),
         RequestPastLocations {
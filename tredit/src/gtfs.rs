@@ -0,0 +1,313 @@
+//! Reads a local GTFS feed and derives travel times from the timetable.
+//!
+//! Stops are merged into their parent stations, so all platforms of a station count as one
+//! place. Travel times are calculated with a simple connection scan over all trips running on
+//! a given day.
+
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use libtruinlag::normalise_station_name;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// The time that is needed to change trains at a station, in seconds
+const TRANSFER_SECONDS: u32 = 120;
+
+/// Journeys taking longer than this (in seconds) are not considered
+const MAX_TRAVEL_SECONDS: u32 = 4 * 60 * 60;
+
+/// A part of the day in which travel times are sampled, all times are in seconds after midnight
+#[derive(Debug, Clone, Copy)]
+pub struct TimeWindow {
+    pub from: u32,
+    pub until: u32,
+    /// the time between two sampled departures
+    pub interval: u32,
+}
+
+impl TimeWindow {
+    fn samples(&self) -> impl Iterator<Item = u32> + '_ {
+        (self.from..self.until).step_by(self.interval.max(60) as usize)
+    }
+
+    fn hours(&self) -> f32 {
+        (self.until.saturating_sub(self.from)) as f32 / 3600.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+    from: usize,
+    to: usize,
+    departure: u32,
+    arrival: u32,
+    trip: usize,
+}
+
+/// The timetable of one day, reduced to the connections between stations
+pub struct Feed {
+    /// the names of the stations, indexed by station
+    names: Vec<String>,
+    /// all connections, sorted by departure
+    connections: Vec<Connection>,
+    trips: usize,
+}
+
+/// Finds the index of a column in a csv header
+fn column(headers: &csv::StringRecord, name: &str) -> anyhow::Result<usize> {
+    headers
+        .iter()
+        .position(|h| h.trim() == name)
+        .context(format!("column \"{}\" not found", name))
+}
+
+/// Parses a GTFS time (HH:MM:SS, hours may exceed 24) into seconds after midnight
+fn parse_gtfs_time(time: &str) -> Option<u32> {
+    let mut parts = time.trim().split(':').map(|p| p.parse::<u32>().ok());
+    let hours = parts.next()??;
+    let minutes = parts.next()??;
+    let seconds = parts.next().flatten().unwrap_or(0);
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Collects the services that run on a date from `calendar.txt` and `calendar_dates.txt`. At
+/// least one of the two files has to exist.
+fn active_services(dir: &Path, date: NaiveDate) -> anyhow::Result<HashSet<String>> {
+    let day = date.format("%Y%m%d").to_string();
+    let weekday = [
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+        "sunday",
+    ][date.weekday().num_days_from_monday() as usize];
+    let mut services = HashSet::new();
+    let calendar = dir.join("calendar.txt");
+    let calendar_dates = dir.join("calendar_dates.txt");
+    if !calendar.exists() && !calendar_dates.exists() {
+        anyhow::bail!("the feed has neither a calendar.txt nor a calendar_dates.txt");
+    }
+    if calendar.exists() {
+        let mut rdr = csv::Reader::from_path(&calendar)?;
+        let headers = rdr.headers()?.clone();
+        let (service, runs, start, end) = (
+            column(&headers, "service_id")?,
+            column(&headers, weekday)?,
+            column(&headers, "start_date")?,
+            column(&headers, "end_date")?,
+        );
+        for record in rdr.records() {
+            let record = record?;
+            if &record[runs] == "1"
+                && &record[start] <= day.as_str()
+                && &record[end] >= day.as_str()
+            {
+                services.insert(record[service].to_string());
+            }
+        }
+    }
+    if calendar_dates.exists() {
+        let mut rdr = csv::Reader::from_path(&calendar_dates)?;
+        let headers = rdr.headers()?.clone();
+        let (service, date, exception) = (
+            column(&headers, "service_id")?,
+            column(&headers, "date")?,
+            column(&headers, "exception_type")?,
+        );
+        for record in rdr.records() {
+            let record = record?;
+            if record[date] != day {
+                continue;
+            }
+            match &record[exception] {
+                "1" => services.insert(record[service].to_string()),
+                "2" => services.remove(&record[service]),
+                _ => false,
+            };
+        }
+    }
+    Ok(services)
+}
+
+impl Feed {
+    /// Loads all connections of a GTFS feed in `dir` that run on `date` and depart between
+    /// `from` and `until` (seconds after midnight, plus the maximum travel time).
+    pub fn load(dir: &Path, date: NaiveDate, from: u32, until: u32) -> anyhow::Result<Self> {
+        // stations
+        let mut rdr = csv::Reader::from_path(dir.join("stops.txt")).context("reading stops")?;
+        let headers = rdr.headers()?.clone();
+        let (id_col, name_col) = (column(&headers, "stop_id")?, column(&headers, "stop_name")?);
+        let parent_col = column(&headers, "parent_station").ok();
+        let mut stop_names = HashMap::new();
+        let mut parents = HashMap::new();
+        for record in rdr.records() {
+            let record = record?;
+            let id = record[id_col].to_string();
+            stop_names.insert(id.clone(), record[name_col].to_string());
+            let parent = parent_col.map(|c| record[c].trim()).unwrap_or("");
+            if !parent.is_empty() {
+                parents.insert(id, parent.to_string());
+            }
+        }
+        let mut names = Vec::new();
+        let mut station_ids: HashMap<String, usize> = HashMap::new();
+        let mut stop_stations = HashMap::new();
+        for (id, name) in &stop_names {
+            let key = parents.get(id).unwrap_or(id);
+            let station = *station_ids.entry(key.clone()).or_insert_with(|| {
+                names.push(stop_names.get(key).unwrap_or(name).clone());
+                names.len() - 1
+            });
+            stop_stations.insert(id.clone(), station);
+        }
+
+        // trips
+        let services = active_services(dir, date)?;
+        let mut rdr = csv::Reader::from_path(dir.join("trips.txt")).context("reading trips")?;
+        let headers = rdr.headers()?.clone();
+        let (trip_col, service_col) = (
+            column(&headers, "trip_id")?,
+            column(&headers, "service_id")?,
+        );
+        let mut trip_ids = HashMap::new();
+        for record in rdr.records() {
+            let record = record?;
+            if services.contains(&record[service_col]) {
+                let index = trip_ids.len();
+                trip_ids.insert(record[trip_col].to_string(), index);
+            }
+        }
+
+        // stop times
+        let mut rdr =
+            csv::Reader::from_path(dir.join("stop_times.txt")).context("reading stop times")?;
+        let headers = rdr.headers()?.clone();
+        let (trip_col, arrival_col, departure_col, stop_col, sequence_col) = (
+            column(&headers, "trip_id")?,
+            column(&headers, "arrival_time")?,
+            column(&headers, "departure_time")?,
+            column(&headers, "stop_id")?,
+            column(&headers, "stop_sequence")?,
+        );
+        let mut stop_times = Vec::new();
+        let mut record = csv::StringRecord::new();
+        while rdr.read_record(&mut record)? {
+            let Some(&trip) = trip_ids.get(&record[trip_col]) else {
+                continue;
+            };
+            let Some(&station) = stop_stations.get(&record[stop_col]) else {
+                continue;
+            };
+            let (Some(arrival), Some(departure)) = (
+                parse_gtfs_time(&record[arrival_col]),
+                parse_gtfs_time(&record[departure_col]),
+            ) else {
+                continue;
+            };
+            let sequence: u32 = record[sequence_col].trim().parse().unwrap_or(0);
+            stop_times.push((trip, sequence, station, arrival, departure));
+        }
+        stop_times.sort_unstable_by_key(|(trip, sequence, ..)| (*trip, *sequence));
+
+        let mut connections: Vec<Connection> = stop_times
+            .windows(2)
+            .filter(|pair| pair[0].0 == pair[1].0 && pair[0].2 != pair[1].2)
+            .map(|pair| Connection {
+                from: pair[0].2,
+                to: pair[1].2,
+                departure: pair[0].4,
+                arrival: pair[1].3,
+                trip: pair[0].0,
+            })
+            .filter(|c| c.departure >= from && c.departure <= until + MAX_TRAVEL_SECONDS)
+            .collect();
+        connections.sort_unstable_by_key(|c| c.departure);
+
+        Ok(Feed {
+            names,
+            connections,
+            trips: trip_ids.len(),
+        })
+    }
+
+    /// Finds the station that is called by any of the given names
+    pub fn find_station<'a>(&self, names: impl Iterator<Item = &'a str>) -> Option<usize> {
+        let wanted: Vec<String> = names.map(normalise_station_name).collect();
+        self.names
+            .iter()
+            .position(|n| wanted.contains(&normalise_station_name(n)))
+    }
+
+    /// Calculates the earliest arrival at every station when leaving from any of the `sources` at
+    /// `start`. Unreachable stations have `u32::MAX`.
+    fn earliest_arrivals(&self, sources: &[usize], start: u32) -> Vec<u32> {
+        let mut arrival = vec![u32::MAX; self.names.len()];
+        let mut ready = vec![u32::MAX; self.names.len()];
+        let mut boarded = vec![false; self.trips];
+        for &source in sources {
+            arrival[source] = start;
+            ready[source] = start;
+        }
+        let first = self.connections.partition_point(|c| c.departure < start);
+        for c in &self.connections[first..] {
+            if c.departure > start + MAX_TRAVEL_SECONDS {
+                break;
+            }
+            if boarded[c.trip] || ready[c.from] <= c.departure {
+                boarded[c.trip] = true;
+                if c.arrival < arrival[c.to] {
+                    arrival[c.to] = c.arrival;
+                    ready[c.to] = c.arrival + TRANSFER_SECONDS;
+                }
+            }
+        }
+        arrival
+    }
+
+    /// The average travel time in minutes from any of the stations in `from` to any of the
+    /// stations in each of the `targets`, sampled over the time window. Targets that can't be
+    /// reached at all are `None`.
+    pub fn travel_minutes(
+        &self,
+        from: &[usize],
+        targets: &[Vec<usize>],
+        window: &TimeWindow,
+    ) -> Vec<Option<u64>> {
+        let mut totals = vec![(0u64, 0u64); targets.len()];
+        for start in window.samples() {
+            let arrivals = self.earliest_arrivals(from, start);
+            for (target, total) in targets.iter().zip(totals.iter_mut()) {
+                if let Some(arrival) = target
+                    .iter()
+                    .map(|s| arrivals[*s])
+                    .filter(|a| *a != u32::MAX)
+                    .min()
+                {
+                    total.0 += (arrival - start) as u64;
+                    total.1 += 1;
+                }
+            }
+        }
+        totals
+            .into_iter()
+            .map(|(seconds, samples)| {
+                (samples > 0).then(|| (seconds as f64 / samples as f64 / 60.0).round() as u64)
+            })
+            .collect()
+    }
+
+    /// The number of departures per hour from a station during the time window
+    pub fn departures_per_hour(&self, station: usize, window: &TimeWindow) -> f32 {
+        let departures: HashSet<usize> = self
+            .connections
+            .iter()
+            .filter(|c| {
+                c.from == station && c.departure >= window.from && c.departure < window.until
+            })
+            .map(|c| c.trip)
+            .collect();
+        departures.len() as f32 / window.hours().max(1.0 / 60.0)
+    }
+}
@@ -1,7 +1,17 @@
-use crate::parsley::{get_data, parse_record};
-use libtruinlag::ChallengeSet;
+use crate::gtfs::{Feed, TimeWindow};
+use crate::parsley::{
+    Record, get_data, parse_distance_record, parse_record, parse_sector_record, parse_zone_record,
+    read_records,
+};
+use crate::report;
+use anyhow::Context;
 use libtruinlag::commands::*;
+use libtruinlag::{
+    ChallengeFilter, ChallengeSet, ChallengeType, InputChallenge, PartialInputChallenge, Sector,
+    Station, Zone,
+};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 const CHALLENGE_SHEET: &str = "https://docs.google.com/spreadsheets/d/e/2PACX-1vRcImsj8yCZNaKSx4wYk6GZnBkZ_Eody246mqM4UjsvYIW3wqd37kIhhIlrWJ3tiwSLbN9RWzMVs-V1/pub?gid=1012921349&single=true&output=csv";
 const ZONENKAFF_SHEET: &str = "https://docs.google.com/spreadsheets/d/e/2PACX-1vRcImsj8yCZNaKSx4wYk6GZnBkZ_Eody246mqM4UjsvYIW3wqd37kIhhIlrWJ3tiwSLbN9RWzMVs-V1/pub?gid=1336941165&single=true&output=csv";
@@ -31,7 +41,149 @@ fn printnnl(text: &str) {
     std::io::stdout().flush().unwrap();
 }
 
-pub async fn import_challenges(mut sender: libtruinlag::api::SendConnection) {
+/// Where `import_challenges` takes its data from
+pub enum ImportSource {
+    /// The published google sheets
+    Sheets,
+    /// A single local csv or json file with challenges, or a directory containing `challenges`,
+    /// `zones`, `distances` and `sectors` files as csv or json. Missing files are skipped.
+    Local(PathBuf),
+}
+
+impl ImportSource {
+    /// Reads the table with the given name, `None` if the source doesn't have it
+    async fn table(
+        &self,
+        name: &str,
+        url: &str,
+    ) -> anyhow::Result<Option<Vec<anyhow::Result<Record>>>> {
+        match self {
+            ImportSource::Sheets => Ok(Some(get_data(url).await?)),
+            ImportSource::Local(path) if path.is_dir() => {
+                match ["csv", "json"]
+                    .iter()
+                    .map(|extension| path.join(format!("{}.{}", name, extension)))
+                    .find(|file| file.exists())
+                {
+                    Some(file) => Ok(Some(read_records(&file)?)),
+                    None => Ok(None),
+                }
+            }
+            ImportSource::Local(path) if name == "challenges" => Ok(Some(read_records(path)?)),
+            ImportSource::Local(_) => Ok(None),
+        }
+    }
+}
+
+/// Collects the problems and changes found during an import
+#[derive(Default)]
+struct ImportReport {
+    errors: Vec<String>,
+    changes: Vec<String>,
+    /// counts down from `u64::MAX` to give things that would be added in a dry run an id
+    placeholder_ids: u64,
+}
+
+impl ImportReport {
+    fn row_error(&mut self, table: &str, row: usize, label: &str, error: anyhow::Error) {
+        self.errors.push(format!(
+            "{} row {} ({}): {:#}",
+            table,
+            row + 1,
+            label,
+            error
+        ));
+    }
+
+    fn placeholder_id(&mut self) -> u64 {
+        self.placeholder_ids += 1;
+        u64::MAX - self.placeholder_ids
+    }
+
+    fn print(&self, dry_run: bool) {
+        if dry_run {
+            println!("\nchanges that would be made:");
+        } else {
+            println!("\nchanges made:");
+        }
+        if self.changes.is_empty() {
+            println!("  none");
+        }
+        for change in &self.changes {
+            println!("  {}", change);
+        }
+        if !self.errors.is_empty() {
+            eprintln!("\n{} rows couldn't be imported:", self.errors.len());
+            for error in &self.errors {
+                eprintln!("  {}", error);
+            }
+        }
+    }
+}
+
+/// Loads a table from the source, reporting if it or some of its rows can't be read. The rows
+/// that could be read are returned along with their index.
+async fn load_table(
+    source: &ImportSource,
+    name: &str,
+    url: &str,
+    report: &mut ImportReport,
+) -> Option<Vec<(usize, Record)>> {
+    printnnl(&format!("fetching {} data...", name));
+    match source.table(name, url).await {
+        Ok(Some(rows)) => {
+            println!("  done!");
+            let mut records = Vec::new();
+            for (i, row) in rows.into_iter().enumerate() {
+                match row {
+                    Ok(record) => records.push((i, record)),
+                    Err(err) => report.row_error(name, i, "unreadable", err),
+                }
+            }
+            Some(records)
+        }
+        Ok(None) => {
+            println!("  not present, skipping");
+            None
+        }
+        Err(err) => {
+            println!("  failed!");
+            report.errors.push(format!("{}: {:#}", name, err));
+            None
+        }
+    }
+}
+
+/// Identifies a challenge across imports, since challenges from the sheets have no id
+fn challenge_key(challenge: &InputChallenge) -> String {
+    format!(
+        "{:?}|{:?}|{:?}",
+        challenge.kind, challenge.title, challenge.place
+    )
+}
+
+/// Lists the fields that differ between two challenges
+fn changed_fields(old: &InputChallenge, new: &InputChallenge) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return vec!["everything".into()];
+    };
+    new.iter()
+        .filter(|(field, value)| field.as_str() != "id" && old.get(field.as_str()) != Some(value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+/// Imports challenge sets, zones, travel times, sectors and challenges from the google sheets or
+/// local files. Challenges that already exist (same type, title and place) are updated instead of
+/// added again. With `dry_run`, nothing is changed and only the differences are printed.
+pub async fn import_challenges(
+    mut sender: libtruinlag::api::SendConnection,
+    source: ImportSource,
+    dry_run: bool,
+) {
+    let mut report = ImportReport::default();
     let sheet_sets = [
         "og",
         "geso",
@@ -43,168 +195,236 @@ pub async fn import_challenges(mut sender: libtruinlag::api::SendConnection) {
     ];
 
     printnnl("fetching challenge sets...");
-    let challenge_sets = sender.get_challenge_sets().await.unwrap();
+    let mut challenge_sets = sender.get_challenge_sets().await.unwrap();
     println!("  done!");
-    let challenge_set_names: Vec<String> = challenge_sets.iter().map(|s| s.name.clone()).collect();
 
     printnnl("adding missing challenge sets");
     for sset in sheet_sets {
-        printnnl(".");
-        if !challenge_set_names.contains(&sset.into()) {
-            sender.add_challenge_set(sset.into()).await.unwrap();
+        if !challenge_sets.iter().any(|s| s.name == sset) {
+            printnnl(".");
+            report.changes.push(format!("+ challenge set {}", sset));
+            if dry_run {
+                let id = report.placeholder_id();
+                challenge_sets.push(ChallengeSet {
+                    name: sset.into(),
+                    id,
+                });
+            } else {
+                sender.add_challenge_set(sset.into()).await.unwrap();
+            }
         }
     }
     println!("  done!");
 
-    printnnl("re-fetching challenge sets... ");
-    let challenge_sets: Vec<ChallengeSet> = sender
-        .get_challenge_sets()
-        .await
-        .unwrap()
-        .iter()
+    if !dry_run {
+        printnnl("re-fetching challenge sets... ");
+        challenge_sets = sender.get_challenge_sets().await.unwrap();
+        println!("  done!");
+    }
+    let challenge_sets: Vec<ChallengeSet> = challenge_sets
+        .into_iter()
         .filter(|s| sheet_sets.contains(&s.name.as_str()))
-        .cloned()
         .collect();
-    println!("  done!");
 
     printnnl("fetching existing zones...");
-    let truin_zones = sender.get_zones().await.unwrap();
-    println!("  done!");
-    printnnl("fetching sheet zonic kaff data...");
-    let sheet_zones: Vec<HashMap<String, String>> = get_data(ZONENKAFF_SHEET).await.to_vec();
+    let mut truin_zones = sender.get_zones().await.unwrap();
     println!("  done!");
+    let mut sheet_zones = Vec::new();
+    if let Some(records) = load_table(&source, "zones", ZONENKAFF_SHEET, &mut report).await {
+        for &(i, ref record) in &records {
+            match parse_zone_record(record) {
+                Ok(zone) => sheet_zones.push(zone),
+                Err(err) => report.row_error(
+                    "zones",
+                    i,
+                    record.get("Zone").map_or("?", |z| z.as_str()),
+                    err,
+                ),
+            }
+        }
+    }
     let s_bahn_zones = [
         110, 112, 117, 120, 121, 132, 133, 134, 141, 142, 151, 154, 155, 156, 180, 181,
     ];
     printnnl("adding missing zones");
     for sheet_zone in &sheet_zones {
-        if truin_zones
-            .iter()
-            .any(|tz| tz.zone == sheet_zone.get("Zone").unwrap().parse().unwrap())
-        {
+        if truin_zones.iter().any(|tz| tz.zone == sheet_zone.zone) {
             continue;
         }
         printnnl(".");
-        sender
-            .send(EngineAction::AddZone {
-                zone: sheet_zone.get("Zone").unwrap().parse().unwrap(),
-                num_conn_zones: sheet_zone.get("num conn zones").unwrap().parse().unwrap(),
-                num_connections: sheet_zone.get("num connections").unwrap().parse().unwrap(),
-                train_through: sheet_zone
-                    .get("train through")
-                    .unwrap()
-                    .to_lowercase()
-                    .parse()
-                    .unwrap(),
-                mongus: sheet_zone
-                    .get("Mongus")
-                    .unwrap()
-                    .to_lowercase()
-                    .parse()
-                    .unwrap(),
-                s_bahn_zone: s_bahn_zones
-                    .contains(&sheet_zone.get("Zone").unwrap().parse::<i64>().unwrap()),
-            })
-            .await
-            .unwrap();
+        report.changes.push(format!("+ zone {}", sheet_zone.zone));
+        let s_bahn_zone = s_bahn_zones.contains(&sheet_zone.zone);
+        if dry_run {
+            let id = report.placeholder_id();
+            truin_zones.push(Zone {
+                zone: sheet_zone.zone,
+                num_conn_zones: sheet_zone.num_conn_zones,
+                num_connections: sheet_zone.num_connections,
+                train_through: sheet_zone.train_through,
+                mongus: sheet_zone.mongus,
+                s_bahn_zone,
+                minutes_to: HashMap::new(),
+                travel_bands: Vec::new(),
+                close_sectors: Vec::new(),
+                id,
+            });
+        } else {
+            sender
+                .send(EngineAction::AddZone {
+                    zone: sheet_zone.zone,
+                    num_conn_zones: sheet_zone.num_conn_zones,
+                    num_connections: sheet_zone.num_connections,
+                    train_through: sheet_zone.train_through,
+                    mongus: sheet_zone.mongus,
+                    s_bahn_zone,
+                })
+                .await
+                .unwrap();
+        }
     }
     println!("  done!");
 
-    printnnl("re-fetching zones...");
-    let truin_zones = sender.get_zones().await.unwrap();
-    println!("  done!");
-
-    printnnl("fetching sheet zone distance data...");
-    let connections = get_data(DISTANCES_SHEET).await;
-    println!("  done!");
-    struct Connection {
-        pub zone_from: u64,
-        pub zone_to: u64,
-        pub minutes: u64,
+    if !dry_run {
+        printnnl("re-fetching zones...");
+        truin_zones = sender.get_zones().await.unwrap();
+        println!("  done!");
     }
-    let mut cookednections = Vec::new();
-    for conn in connections {
-        cookednections.push(Connection {
-            zone_from: truin_zones
-                .iter()
-                .find(|z| z.zone == conn.get("Zone A").unwrap().parse().unwrap())
-                .unwrap()
-                .id,
-            zone_to: truin_zones
-                .iter()
-                .find(|z| z.zone == conn.get("Zone B").unwrap().parse().unwrap())
-                .unwrap()
-                .id,
-            minutes: conn.get("Travel Time").unwrap().parse().unwrap(),
-        });
-    }
-    printnnl("adding connections to db");
-    for conn in cookednections {
-        printnnl(".");
-        sender
-            .send(EngineAction::AddMinutesTo {
-                from_zone: conn.zone_from,
-                to_zone: conn.zone_to,
-                minutes: conn.minutes,
-            })
-            .await
-            .unwrap();
+
+    if let Some(records) = load_table(&source, "distances", DISTANCES_SHEET, &mut report).await {
+        printnnl("adding connections to db");
+        for &(i, ref record) in &records {
+            let connection = parse_distance_record(record).and_then(|(from, to, minutes)| {
+                let find = |zone| {
+                    truin_zones
+                        .iter()
+                        .find(|z| z.zone == zone)
+                        .context(format!("couldn't find zone {}", zone))
+                };
+                Ok((find(from)?, find(to)?, minutes))
+            });
+            let (from_zone, to_zone, minutes) = match connection {
+                Ok(connection) => connection,
+                Err(err) => {
+                    let label = format!(
+                        "{} -> {}",
+                        record.get("Zone A").map_or("?", |z| z.as_str()),
+                        record.get("Zone B").map_or("?", |z| z.as_str())
+                    );
+                    report.row_error("distances", i, &label, err);
+                    continue;
+                }
+            };
+            match from_zone.minutes_to.get(&to_zone.id) {
+                Some(old) if *old == minutes => continue,
+                Some(old) => report.changes.push(format!(
+                    "~ travel time {} -> {}: {} -> {} min",
+                    from_zone.zone, to_zone.zone, old, minutes
+                )),
+                None => report.changes.push(format!(
+                    "+ travel time {} -> {}: {} min",
+                    from_zone.zone, to_zone.zone, minutes
+                )),
+            }
+            printnnl(".");
+            if !dry_run {
+                sender
+                    .send(EngineAction::AddMinutesTo {
+                        from_zone: from_zone.id,
+                        to_zone: to_zone.id,
+                        minutes,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+        println!("  done!");
     }
-    println!("  done!");
 
     printnnl("fetching existing sectors...");
-    let truin_sectors = sender.get_sectors().await.unwrap();
-    println!("  done!");
-    printnnl("fetching sheet sector data...");
-    let sheet_sectors: Vec<(char, Vec<char>)> = get_data(SECTOR_SHEET)
-        .await
-        .iter()
-        .map(|s| {
-            (
-                s.get("sector").unwrap().chars().next().unwrap(),
-                s.get("neighbours").unwrap().chars().collect(),
-            )
-        })
-        .collect();
+    let mut truin_sectors = sender.get_sectors().await.unwrap();
     println!("  done!");
+    let mut sheet_sectors = Vec::new();
+    if let Some(records) = load_table(&source, "sectors", SECTOR_SHEET, &mut report).await {
+        for &(i, ref record) in &records {
+            match parse_sector_record(record) {
+                Ok(sector) => sheet_sectors.push(sector),
+                Err(err) => report.row_error(
+                    "sectors",
+                    i,
+                    record.get("sector").map_or("?", |s| s.as_str()),
+                    err,
+                ),
+            }
+        }
+    }
     printnnl("adding missing sectors");
     for (sheet_sector, _) in &sheet_sectors {
-        printnnl(".");
         if !truin_sectors.iter().any(|s| &s.name == sheet_sector) {
-            sender
-                .send(EngineAction::AddSector(*sheet_sector))
-                .await
-                .unwrap();
+            printnnl(".");
+            report.changes.push(format!("+ sector {}", sheet_sector));
+            if dry_run {
+                let id = report.placeholder_id();
+                truin_sectors.push(Sector {
+                    name: *sheet_sector,
+                    neighbours: Vec::new(),
+                    id,
+                });
+            } else {
+                sender
+                    .send(EngineAction::AddSector(*sheet_sector))
+                    .await
+                    .unwrap();
+            }
         }
     }
     println!("  done!");
 
-    printnnl("re-fetching sectors...");
-    let truin_sectors = sender.get_sectors().await.unwrap();
-    println!("  done!");
+    if !dry_run {
+        printnnl("re-fetching sectors...");
+        truin_sectors = sender.get_sectors().await.unwrap();
+        println!("  done!");
+    }
 
     printnnl("adding missing sector neighbourhoods...");
-    for truin_sector in &truin_sectors {
-        if let Some((_sheet_sector, sheet_neighbours)) =
-            sheet_sectors.iter().find(|s| s.0 == truin_sector.name)
-        {
-            for sheet_neighbour in sheet_neighbours {
-                let sheet_neighbour_id = truin_sectors
-                    .iter()
-                    .find(|s| &s.name == sheet_neighbour)
+    let mut added_neighbourhoods = Vec::new();
+    for (i, (sheet_sector, sheet_neighbours)) in sheet_sectors.iter().enumerate() {
+        let Some(truin_sector) = truin_sectors.iter().find(|s| &s.name == sheet_sector) else {
+            continue;
+        };
+        for sheet_neighbour in sheet_neighbours {
+            let Some(neighbour) = truin_sectors.iter().find(|s| &s.name == sheet_neighbour) else {
+                report.row_error(
+                    "sectors",
+                    i,
+                    &sheet_sector.to_string(),
+                    anyhow::anyhow!("couldn't find neighbour {}", sheet_neighbour),
+                );
+                continue;
+            };
+            let pair = (
+                truin_sector.id.min(neighbour.id),
+                truin_sector.id.max(neighbour.id),
+            );
+            if truin_sector.neighbours.contains(&neighbour.id)
+                || neighbour.neighbours.contains(&truin_sector.id)
+                || added_neighbourhoods.contains(&pair)
+            {
+                continue;
+            }
+            printnnl(".");
+            added_neighbourhoods.push(pair);
+            report.changes.push(format!(
+                "+ neighbourhood {} - {}",
+                truin_sector.name, neighbour.name
+            ));
+            if !dry_run {
+                sender
+                    .send(EngineAction::AddNeighbourhood(
+                        truin_sector.id,
+                        neighbour.id,
+                    ))
+                    .await
                     .unwrap();
-                if !(sheet_neighbour_id.id > truin_sector.id
-                    || truin_sector.neighbours.contains(&sheet_neighbour_id.id))
-                {
-                    printnnl(".");
-                    sender
-                        .send(EngineAction::AddNeighbourhood(
-                            truin_sector.id,
-                            sheet_neighbour_id.id,
-                        ))
-                        .await
-                        .unwrap();
-                }
             }
         }
     }
@@ -212,63 +432,393 @@ pub async fn import_challenges(mut sender: libtruinlag::api::SendConnection) {
 
     printnnl("resetting close sectors of zones...");
     for truin_zone in &truin_zones {
-        let sheet_zone = match sheet_zones
-            .iter()
-            .find(|z| z.get("Zone").unwrap().parse::<u64>().unwrap() == truin_zone.zone)
-        {
-            Some(z) => z,
-            None => {
-                printnnl("!");
-                continue;
-            }
+        let Some(sheet_zone) = sheet_zones.iter().find(|z| z.zone == truin_zone.zone) else {
+            continue;
         };
-        let mut zone_sector_ids: Vec<u64> = sheet_zone
-            .get("sectors")
-            .unwrap()
-            .trim()
-            .chars()
-            .map(|c| truin_sectors.iter().find(|s| s.name == c).unwrap().id)
-            .collect();
+        let mut zone_sector_ids = Vec::new();
+        for c in &sheet_zone.sectors {
+            match truin_sectors.iter().find(|s| &s.name == c) {
+                Some(sector) => zone_sector_ids.push(sector.id),
+                None => report.errors.push(format!(
+                    "zone {}: couldn't find close sector {}",
+                    truin_zone.zone, c
+                )),
+            }
+        }
         zone_sector_ids.sort();
         let mut truin_close_sectors: Vec<u64> =
             truin_zone.close_sectors.iter().map(|s| s.id).collect();
         truin_close_sectors.sort();
         if zone_sector_ids != truin_close_sectors {
             printnnl(".");
-            sender
-                .send(EngineAction::SetCloseSectors {
-                    zone_id: truin_zone.id,
-                    sector_ids: zone_sector_ids,
-                })
-                .await
-                .unwrap();
+            report.changes.push(format!(
+                "~ close sectors of zone {}: {}",
+                truin_zone.zone,
+                sheet_zone.sectors.iter().collect::<String>()
+            ));
+            if !dry_run {
+                sender
+                    .send(EngineAction::SetCloseSectors {
+                        zone_id: truin_zone.id,
+                        sector_ids: zone_sector_ids,
+                    })
+                    .await
+                    .unwrap();
+            }
         }
     }
     println!("  done!");
 
-    printnnl("fetching UC4 challenge data...");
-    let records = get_data(CHALLENGE_SHEET).await;
+    let Some(records) = load_table(&source, "challenges", CHALLENGE_SHEET, &mut report).await
+    else {
+        report.print(dry_run);
+        return;
+    };
+
+    printnnl("fetching stations...");
+    let stations = sender.get_stations().await.unwrap();
     println!("  done!");
 
     let mut challenges = Vec::new();
-    println!("parsing challenges...");
-    for (i, record) in records.iter().enumerate() {
-        let challenge = parse_record(record, &challenge_sets, &truin_zones, &truin_sectors);
+    let mut skipped = 0;
+    printnnl("parsing challenges...");
+    for &(i, ref record) in &records {
+        let challenge = parse_record(
+            record,
+            &challenge_sets,
+            &truin_zones,
+            &truin_sectors,
+            &stations,
+        );
         match challenge {
-            Ok(c) => {
-                if let Some(c) = c {
-                    challenges.push(c);
-                }
+            Ok(Some(c)) => challenges.push(c),
+            Ok(None) => skipped += 1,
+            Err(err) => {
+                let label = ["title", "place"]
+                    .iter()
+                    .filter_map(|field| record.get(*field))
+                    .find(|text| !text.trim().is_empty())
+                    .map_or("untitled", |text| text.as_str());
+                report.row_error("challenges", i, label, err)
             }
-            Err(err) => eprintln!("Error while parsing line {}:\n{}\n", i, err),
         }
     }
-    println!("done!");
+    println!(
+        "  done! ({} challenges, {} skipped because of their status)",
+        challenges.len(),
+        skipped
+    );
+
+    printnnl("fetching existing challenges...");
+    let mut existing: Vec<InputChallenge> = sender
+        .get_raw_challenges()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.into())
+        .collect();
+    println!("  done!");
 
     printnnl("adding challenges to db");
-    for challenge in challenges {
+    for mut challenge in challenges {
+        let key = challenge_key(&challenge);
+        let label = challenge
+            .title
+            .clone()
+            .or(challenge.place.clone())
+            .unwrap_or_default();
+        match existing.iter().position(|c| challenge_key(c) == key) {
+            Some(index) => {
+                let old = existing.swap_remove(index);
+                if challenge.place_coordinates.is_none() {
+                    challenge.place_coordinates = old.place_coordinates;
+                }
+                challenge.id = old.id;
+                let changed = changed_fields(&old, &challenge);
+                if changed.is_empty() {
+                    continue;
+                }
+                printnnl(".");
+                report
+                    .changes
+                    .push(format!("~ challenge {} ({})", label, changed.join(", ")));
+                if dry_run {
+                    continue;
+                }
+                if let Err(err) = sender.set_raw_challenge(challenge).await {
+                    report
+                        .errors
+                        .push(format!("challenge {}: couldn't update: {}", label, err));
+                }
+            }
+            None => {
+                printnnl(".");
+                report.changes.push(format!("+ challenge {}", label));
+                if dry_run {
+                    continue;
+                }
+                if let Err(err) = sender.add_raw_challenge(challenge).await {
+                    report
+                        .errors
+                        .push(format!("challenge {}: couldn't add: {}", label, err));
+                }
+            }
+        }
+    }
+    println!("  done!");
+    for challenge in existing {
+        report.changes.push(format!(
+            "  challenge {} is only in the db and is kept",
+            challenge
+                .title
+                .or(challenge.place)
+                .unwrap_or_else(|| "untitled".into())
+        ));
+    }
+
+    report.print(dry_run);
+}
+
+/// Finds a place in the feed by its name or, if it is a known station, by any of its aliases
+fn feed_station(feed: &Feed, name: &str, stations: &[Station]) -> Option<usize> {
+    let station = stations.iter().find(|s| s.is_called(name));
+    feed.find_station(
+        std::iter::once(name).chain(station.into_iter().flat_map(|s| {
+            std::iter::once(s.name.as_str()).chain(s.aliases.iter().map(|a| a.as_str()))
+        })),
+    )
+}
+
+/// Calculates the travel times between all pairs of zones that have stations in the feed
+fn zone_minutes<'a>(
+    feed: &Feed,
+    zone_stations: &[(&'a Zone, Vec<usize>)],
+    window: &TimeWindow,
+) -> Vec<(&'a Zone, &'a Zone, u64)> {
+    let targets: Vec<Vec<usize>> = zone_stations.iter().map(|(_, s)| s.clone()).collect();
+    let mut minutes_to = Vec::new();
+    for (from_zone, from_stations) in zone_stations {
         printnnl(".");
-        sender.add_raw_challenge(challenge).await.unwrap();
+        let minutes = feed.travel_minutes(from_stations, &targets, window);
+        for ((to_zone, _), minutes) in zone_stations.iter().zip(minutes) {
+            match minutes {
+                Some(minutes) => minutes_to.push((*from_zone, *to_zone, minutes)),
+                None => eprintln!(
+                    "\nzone {} can't be reached from zone {}",
+                    to_zone.zone, from_zone.zone
+                ),
+            }
+        }
+    }
+    minutes_to
+}
+
+/// Derives zone-to-zone travel times and the departures and time to the centre station of
+/// ZKaff challenges from a local GTFS feed. Travel times for each of the `bands` are added as
+/// timed travel times for that part of the day. With `dry_run`, the results are only printed.
+pub async fn import_timetable(
+    mut sender: libtruinlag::api::SendConnection,
+    feed_dir: &std::path::Path,
+    date: chrono::NaiveDate,
+    window: TimeWindow,
+    bands: Vec<TimeWindow>,
+    centre: &str,
+    dry_run: bool,
+) {
+    printnnl("loading GTFS feed...");
+    let (from, until) = bands
+        .iter()
+        .fold((window.from, window.until), |(from, until), b| {
+            (from.min(b.from), until.max(b.until))
+        });
+    let feed = match Feed::load(feed_dir, date, from, until) {
+        Ok(feed) => feed,
+        Err(err) => {
+            println!();
+            eprintln!("couldn't load the GTFS feed: {:#}", err);
+            return;
+        }
+    };
+    println!("  done!");
+
+    printnnl("fetching zones and stations...");
+    let truin_zones = sender.get_zones().await.unwrap();
+    let stations = sender.get_stations().await.unwrap();
+    println!("  done!");
+
+    let zone_stations: Vec<(&Zone, Vec<usize>)> = truin_zones
+        .iter()
+        .filter_map(|zone| {
+            let found: Vec<usize> = stations
+                .iter()
+                .filter(|s| s.zone == Some(zone.zone))
+                .filter_map(|s| feed_station(&feed, &s.name, &stations))
+                .collect();
+            if found.is_empty() {
+                eprintln!(
+                    "zone {} has no stations in the feed, skipping it",
+                    zone.zone
+                );
+                None
+            } else {
+                Some((zone, found))
+            }
+        })
+        .collect();
+
+    printnnl("calculating travel times between zones");
+    let minutes_to = zone_minutes(&feed, &zone_stations, &window);
+    println!("  done!");
+
+    if dry_run {
+        for (from_zone, to_zone, minutes) in &minutes_to {
+            println!("{} -> {}: {} min", from_zone.zone, to_zone.zone, minutes);
+        }
+    } else {
+        printnnl("adding connections to db");
+        for (from_zone, to_zone, minutes) in &minutes_to {
+            printnnl(".");
+            sender
+                .send(EngineAction::AddMinutesTo {
+                    from_zone: from_zone.id,
+                    to_zone: to_zone.id,
+                    minutes: *minutes,
+                })
+                .await
+                .unwrap();
+        }
+        println!("  done!");
+    }
+
+    for band in &bands {
+        let time = |seconds: u32| {
+            chrono::NaiveTime::from_num_seconds_from_midnight_opt(seconds % 86400, 0)
+                .expect("is within a day")
+        };
+        let (band_from, band_until) = (time(band.from), time(band.until));
+        printnnl(&format!(
+            "calculating travel times between zones from {} until {}",
+            band_from, band_until
+        ));
+        let minutes_to = zone_minutes(&feed, &zone_stations, band);
+        println!("  done!");
+        if dry_run {
+            for (from_zone, to_zone, minutes) in &minutes_to {
+                println!(
+                    "{} -> {} ({}-{}): {} min",
+                    from_zone.zone, to_zone.zone, band_from, band_until, minutes
+                );
+            }
+        } else {
+            printnnl("adding timed connections to db");
+            for (from_zone, to_zone, minutes) in &minutes_to {
+                printnnl(".");
+                sender
+                    .send(EngineAction::AddTimedMinutesTo {
+                        from_zone: from_zone.id,
+                        to_zone: to_zone.id,
+                        from: band_from,
+                        until: band_until,
+                        minutes: *minutes,
+                    })
+                    .await
+                    .unwrap();
+            }
+            println!("  done!");
+        }
+    }
+
+    let Some(centre_station) = feed_station(&feed, centre, &stations) else {
+        eprintln!("the centre station {} isn't in the feed", centre);
+        return;
+    };
+
+    printnnl("fetching challenges...");
+    let challenges = sender.get_raw_challenges().await.unwrap();
+    println!("  done!");
+
+    printnnl("updating zkaff challenges");
+    for mut challenge in challenges
+        .into_iter()
+        .filter(|c| matches!(c.kind, ChallengeType::ZKaff))
+    {
+        let Some(place) = challenge.place.clone() else {
+            continue;
+        };
+        let Some(feed_station) = feed_station(&feed, &place, &stations) else {
+            eprintln!("\n{} isn't in the feed, skipping it", place);
+            continue;
+        };
+        let departures = feed
+            .departures_per_hour(feed_station, &window)
+            .round()
+            .min(u8::MAX as f32) as u8;
+        let Some(time_to_hb) =
+            feed.travel_minutes(&[feed_station], &[vec![centre_station]], &window)[0]
+                .map(|m| m.min(u8::MAX as u64) as u8)
+        else {
+            eprintln!("\n{} can't reach the centre station, skipping it", place);
+            continue;
+        };
+        if dry_run {
+            println!(
+                "\n{}: {} departures per hour, {} min to {} (was {} and {})",
+                place, departures, time_to_hb, centre, challenge.departures, challenge.time_to_hb
+            );
+        } else if challenge.departures != departures || challenge.time_to_hb != time_to_hb {
+            printnnl(".");
+            challenge.departures = departures;
+            challenge.time_to_hb = time_to_hb;
+            if let Err(err) = sender.set_raw_challenge(challenge).await {
+                eprintln!("\ncouldn't update {}: {}", place, err);
+            }
+        }
     }
     println!("  done!");
 }
+
+/// Previews a patch of all challenges matching the filter and applies it once confirmed
+pub async fn patch_challenges(
+    mut sender: libtruinlag::api::SendConnection,
+    filter: ChallengeFilter,
+    patch: PartialInputChallenge,
+    add_sets: Vec<u64>,
+    remove_sets: Vec<u64>,
+    comment: Option<String>,
+    yes: bool,
+) {
+    let preview = match sender
+        .patch_challenges(
+            filter.clone(),
+            patch.clone(),
+            add_sets.clone(),
+            remove_sets.clone(),
+            comment.clone(),
+            true,
+        )
+        .await
+    {
+        Ok(preview) => preview,
+        Err(err) => return crate::print_error(err),
+    };
+    report::print_challenges(&preview);
+    if preview.is_empty() {
+        return;
+    }
+    if yes
+        || get_input(&format!(
+            "Apply the patch to these {} challenges? (yes/no) ",
+            preview.len()
+        ))
+        .as_str()
+            == "yes"
+    {
+        match sender
+            .patch_challenges(filter, patch, add_sets, remove_sets, comment, false)
+            .await
+        {
+            Ok(patched) => println!("patched {} challenges", patched.len()),
+            Err(err) => crate::print_error(err),
+        }
+    }
+}
@@ -1,14 +1,19 @@
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use clap_complete::{generate, shells::Zsh};
 use colored::Colorize;
 use libtruinlag::{
-    Challenge, PartialGameConfig,
+    Challenge, ChallengeFilter, ChallengeStatus, ChallengeType, ExportFormat, LocationPolicy,
+    MessageScope, PartialGameConfig, PartialInputChallenge, ReferenceStrategy, VerificationMode,
     api::{SendConnection, connect},
     commands::EngineAction,
 };
+use partially::Partial;
+use std::ops::RangeInclusive;
 
+mod gtfs;
 mod interactive;
 mod parsley;
+mod report;
 
 async fn run_command(command: EngineAction, mut sender: SendConnection) {
     match sender.send(command).await {
@@ -16,16 +21,18 @@ async fn run_command(command: EngineAction, mut sender: SendConnection) {
             eprintln!("{}", "Command executed successfully:".green().bold());
             println!("{:#?}", response);
         }
-        Err(err) => {
-            eprintln!(
-                "{}",
-                "There was an issue executing the command:".red().bold()
-            );
-            println!("{}", err);
-        }
+        Err(err) => print_error(err),
     }
 }
 
+fn print_error(err: impl std::fmt::Display) {
+    eprintln!(
+        "{}",
+        "There was an issue executing the command:".red().bold()
+    );
+    println!("{}", err);
+}
+
 async fn get_player_by_name(name: &str, sender: &mut SendConnection) -> u64 {
     match name.parse() {
         Ok(id) => id,
@@ -93,6 +100,171 @@ async fn get_zone_by_number(number: u64, sender: &mut SendConnection) -> u64 {
         .id
 }
 
+async fn get_challenge_set_by_name(name: &str, sender: &mut SendConnection) -> u64 {
+    match name.parse() {
+        Ok(id) => id,
+        Err(_) => {
+            sender
+                .get_challenge_sets()
+                .await
+                .unwrap()
+                .iter()
+                .find(|s| s.name.to_lowercase() == name.to_lowercase())
+                .expect("couldn't find challenge set by name")
+                .id
+        }
+    }
+}
+
+/// Parses a point in time given either as `HH:MM` (today) or as an RFC 3339 date and time into a
+/// timestamp in seconds since epoch.
+fn parse_time(time: &str) -> i64 {
+    match chrono::NaiveTime::parse_from_str(time, "%H:%M") {
+        Ok(time) => chrono::Local::now()
+            .with_time(time)
+            .earliest()
+            .expect("time should exist today")
+            .timestamp(),
+        Err(_) => chrono::DateTime::parse_from_rfc3339(time)
+            .expect("time should be either HH:MM or RFC 3339")
+            .timestamp(),
+    }
+}
+
+/// Adds the arguments that decide what happens to references when removing something
+fn with_reference_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("cascade")
+                .long("cascade")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("reassign")
+                .help("Also remove everything that refers to it, where possible"),
+        )
+        .arg(
+            Arg::new("reassign")
+                .long("reassign")
+                .value_parser(clap::value_parser!(u64))
+                .help("Make everything that refers to it refer to this instead"),
+        )
+}
+
+fn reference_strategy(cascade: bool, reassign: Option<u64>) -> ReferenceStrategy {
+    match reassign {
+        Some(id) => ReferenceStrategy::Reassign(id),
+        None if cascade => ReferenceStrategy::Cascade,
+        None => ReferenceStrategy::Refuse,
+    }
+}
+
+/// Adds the arguments that build a `ChallengeFilter`, see `challenge_filter`
+fn with_filter_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("id")
+                .long("id")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(u64))
+                .help("Only challenges with this ID"),
+        )
+        .arg(
+            Arg::new("kind")
+                .long("kind")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(ChallengeType))
+                .help("Only challenges of this kind"),
+        )
+        .arg(
+            Arg::new("status")
+                .long("status")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(ChallengeStatus))
+                .help("Only challenges with this status"),
+        )
+        .arg(
+            Arg::new("in_set")
+                .long("in_set")
+                .action(ArgAction::Append)
+                .help("Only challenges in this challenge set (name or ID)"),
+        )
+        .arg(
+            Arg::new("in_zone")
+                .long("in_zone")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(u64))
+                .help("Only challenges in this zone"),
+        )
+        .arg(
+            Arg::new("in_sector")
+                .long("in_sector")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(u64))
+                .help("Only challenges in the sector with this ID"),
+        )
+        .arg(
+            Arg::new("kaffskala")
+                .long("kaffskala")
+                .help("Only challenges with a kaffskala in this range, e.g. 2-4"),
+        )
+        .arg(
+            Arg::new("grade")
+                .long("grade")
+                .help("Only challenges with a grade in this range, e.g. 1-3"),
+        )
+        .arg(
+            Arg::new("search")
+                .long("search")
+                .help("Only challenges with these words in their title or description"),
+        )
+}
+
+/// Parses a range like `2-4` or a single number
+fn parse_range(range: &str) -> RangeInclusive<u8> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    start.trim().parse().expect("invalid range")..=end.trim().parse().expect("invalid range")
+}
+
+async fn challenge_filter(sub_args: &ArgMatches, sender: &mut SendConnection) -> ChallengeFilter {
+    let mut filter = ChallengeFilter {
+        ids: sub_args
+            .get_many::<u64>("id")
+            .unwrap_or_default()
+            .copied()
+            .collect(),
+        kinds: sub_args
+            .get_many::<ChallengeType>("kind")
+            .unwrap_or_default()
+            .copied()
+            .collect(),
+        statuses: sub_args
+            .get_many::<ChallengeStatus>("status")
+            .unwrap_or_default()
+            .copied()
+            .collect(),
+        sectors: sub_args
+            .get_many::<u64>("in_sector")
+            .unwrap_or_default()
+            .copied()
+            .collect(),
+        kaffskala: sub_args
+            .get_one::<String>("kaffskala")
+            .map(|r| parse_range(r)),
+        grade: sub_args.get_one::<String>("grade").map(|r| parse_range(r)),
+        text: sub_args.get_one::<String>("search").cloned(),
+        ..Default::default()
+    };
+    for set in sub_args.get_many::<String>("in_set").unwrap_or_default() {
+        filter
+            .sets
+            .push(get_challenge_set_by_name(set, sender).await);
+    }
+    for zone in sub_args.get_many::<u64>("in_zone").unwrap_or_default() {
+        filter.zones.push(get_zone_by_number(*zone, sender).await);
+    }
+    filter
+}
+
 fn cli() -> Command {
     Command::new("tredit")
         .about("A command line utility to control truinlag")
@@ -205,7 +377,83 @@ fn cli() -> Command {
         )
         .subcommand(
             Command::new("import")
-                .about("Download, parse and import all challenges from the google sheet"),
+                .about(
+                    "Parse and import all challenges, zones and sectors from the google sheets \
+                    or from local files",
+                )
+                .arg(Arg::new("from").long("from").required(false).help(
+                    "A csv or json file with challenges, or a directory with challenges, \
+                            zones, distances and sectors files, instead of the google sheets",
+                ))
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry_run")
+                        .required(false)
+                        .num_args(0)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only print what would change instead of changing the DB"),
+                ),
+        )
+        .subcommand(
+            Command::new("import_timetable")
+                .about(
+                    "Calculate zone travel times and zkaff departures and time to the centre \
+                    from a local GTFS feed",
+                )
+                .arg(Arg::new("Directory").required(true))
+                .arg(
+                    Arg::new("date")
+                        .long("date")
+                        .required(false)
+                        .help("Day of the timetable to use as YYYY-MM-DD, today if not provided"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .required(false)
+                        .default_value("09:00")
+                        .help("Start of the sampled time window as HH:MM"),
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .required(false)
+                        .default_value("18:00")
+                        .help("End of the sampled time window as HH:MM"),
+                )
+                .arg(
+                    Arg::new("band")
+                        .long("band")
+                        .required(false)
+                        .action(clap::ArgAction::Append)
+                        .help(
+                            "Additional part of the day as HH:MM-HH:MM to calculate separate \
+                            zone travel times for, can be given multiple times",
+                        ),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .required(false)
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Minutes between two sampled departures"),
+                )
+                .arg(
+                    Arg::new("centre")
+                        .long("centre")
+                        .required(false)
+                        .default_value("Zürich HB")
+                        .help("Station that time_to_hb is measured to"),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry_run")
+                        .required(false)
+                        .num_args(0)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only print the results instead of changing the DB"),
+                ),
         )
         .subcommand(
             Command::new("delete_challenges")
@@ -219,6 +467,152 @@ fn cli() -> Command {
                 ),
         )
         .subcommand(Command::new("get_challenges").about("Get all challenges from the truinlag DB"))
+        .subcommand(
+            Command::new("delete_challenge")
+                .about("Delete a single challenge from the truinlag DB")
+                .arg(
+                    Arg::new("Challenge ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .required(false)
+                        .num_args(0)
+                        .action(clap::ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new("challenge_history")
+                .about("Print all revisions of a challenge")
+                .arg(
+                    Arg::new("Challenge ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("revert_challenge")
+                .about("Restore a challenge to one of its revisions")
+                .arg(
+                    Arg::new("Challenge ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("Revision")
+                        .required(true)
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("edit_challenge")
+                .about("Change a challenge on behalf of a player, following the review rules")
+                .arg(Arg::new("Player").required(true))
+                .arg(
+                    Arg::new("Challenge ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("set_status")
+                        .long("set_status")
+                        .value_parser(clap::value_parser!(ChallengeStatus))
+                        .help("Change the status of the challenge"),
+                )
+                .arg(
+                    Arg::new("comment")
+                        .long("comment")
+                        .help("Why the challenge is changed, kept in its history"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Any other changes as JSON, e.g. '{\"grade\": 3}'"),
+                ),
+        )
+        .subcommand(
+            Command::new("set_reviewer")
+                .about("Allow or disallow a player to approve challenges")
+                .arg(Arg::new("Player").required(true))
+                .arg(
+                    Arg::new("Reviewer")
+                        .required(true)
+                        .value_parser(clap::value_parser!(bool)),
+                ),
+        )
+        .subcommand(
+            Command::new("set_language")
+                .about("Set the language a player gets challenges in, none for the default")
+                .arg(Arg::new("Player").required(true))
+                .arg(Arg::new("Language").required(false)),
+        )
+        .subcommand(
+            Command::new("missing_translations")
+                .about("List the challenges that lack a translation, per language")
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .help("Only this language, e.g. fr_ch"),
+                ),
+        )
+        .subcommand(
+            with_filter_args(Command::new("query_challenges"))
+                .about("Search the challenges, one page at a time")
+                .arg(
+                    Arg::new("page")
+                        .long("page")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("The page to show, starting at 0"),
+                )
+                .arg(
+                    Arg::new("page_size")
+                        .long("page_size")
+                        .default_value("50")
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            with_filter_args(Command::new("patch_challenges"))
+                .about("Change all challenges matching the filters at once, after a preview")
+                .arg(
+                    Arg::new("set_status")
+                        .long("set_status")
+                        .value_parser(clap::value_parser!(ChallengeStatus))
+                        .help("Change the status of the challenges"),
+                )
+                .arg(
+                    Arg::new("add_set")
+                        .long("add_set")
+                        .action(ArgAction::Append)
+                        .help("Add the challenges to this challenge set (name or ID)"),
+                )
+                .arg(
+                    Arg::new("remove_set")
+                        .long("remove_set")
+                        .action(ArgAction::Append)
+                        .help("Remove the challenges from this challenge set (name or ID)"),
+                )
+                .arg(
+                    Arg::new("comment")
+                        .long("comment")
+                        .help("Why the challenges are changed, kept in their history"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Any other changes as JSON, e.g. '{\"grade\": 3}'"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .required(false)
+                        .num_args(0)
+                        .action(clap::ArgAction::Append),
+                ),
+        )
         .subcommand(
             Command::new("start")
                 .about("Start the game")
@@ -230,12 +624,127 @@ fn cli() -> Command {
                 .arg(Arg::new("Session").required(true)),
         )
         .subcommand(Command::new("get_zones").about("Get all zones from the truinlag DB"))
+        .subcommand(
+            Command::new("update_zone")
+                .about("Change the properties of a zone")
+                .arg(
+                    Arg::new("Zone")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("number")
+                        .long("number")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("num_conn_zones")
+                        .long("num_conn_zones")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("num_connections")
+                        .long("num_connections")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("train_through")
+                        .long("train_through")
+                        .value_parser(clap::value_parser!(bool)),
+                )
+                .arg(
+                    Arg::new("mongus")
+                        .long("mongus")
+                        .value_parser(clap::value_parser!(bool)),
+                )
+                .arg(
+                    Arg::new("s_bahn_zone")
+                        .long("s_bahn_zone")
+                        .value_parser(clap::value_parser!(bool)),
+                ),
+        )
+        .subcommand(with_reference_args(
+            Command::new("remove_zone")
+                .about("Remove a zone, refusing if anything refers to it unless told otherwise")
+                .arg(
+                    Arg::new("Zone")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        ))
+        .subcommand(
+            Command::new("export_database")
+                .about(
+                    "Back up the whole truinlag DB into an archive, also while games are running",
+                )
+                .arg(Arg::new("File").required(true)),
+        )
+        .subcommand(
+            Command::new("import_database")
+                .about("Replace the whole truinlag DB with an archive made by export_database")
+                .arg(Arg::new("File").required(true))
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .required(false)
+                        .num_args(0)
+                        .action(clap::ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new("import_stations")
+                .about("Replace the stations with those from a GTFS stops.txt or a csv file")
+                .arg(Arg::new("File").required(true)),
+        )
+        .subcommand(Command::new("get_stations").about("Get all stations from the truinlag DB"))
+        .subcommand(
+            Command::new("import_sector_boundaries")
+                .about(
+                    "Import sector outlines from a GeoJSON file with a sector letter per feature",
+                )
+                .arg(Arg::new("File").required(true)),
+        )
+        .subcommand(
+            Command::new("sector_map")
+                .about("Export the sector outlines and neighbourhoods as GeoJSON")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(false)
+                        .help("File to write to, printed to stdout if not provided"),
+                ),
+        )
+        .subcommand(
+            Command::new("import_zone_boundaries")
+                .about("Import zone outlines from a GeoJSON file with a zone property per feature")
+                .arg(Arg::new("File").required(true)),
+        )
         .subcommand(
             Command::new("get_locations")
                 .about("Get all teams and locations from a session")
                 .arg(Arg::new("Session").required(true)),
         )
         .subcommand(Command::new("get_challenge_sets").about("Get all challenge sets"))
+        .subcommand(
+            Command::new("rename_challenge_set")
+                .about("Rename a challenge set")
+                .arg(
+                    Arg::new("Challenge Set ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(Arg::new("Name").required(true)),
+        )
+        .subcommand(with_reference_args(
+            Command::new("remove_challenge_set")
+                .about("Remove a challenge set, refusing if it is in use unless told otherwise")
+                .arg(
+                    Arg::new("Challenge Set ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        ))
         .subcommand(
             Command::new("get_game_config")
                 .about("Gets the game config from a session")
@@ -267,6 +776,71 @@ fn cli() -> Command {
                 .arg(Arg::new("Session").required(true))
                 .arg(Arg::new("Number of hunters").required(true)),
         )
+        .subcommand(
+            Command::new("set_catch_confirmation")
+                .about("Set whether caught teams have to confirm catches in future games")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Enabled")
+                        .required(true)
+                        .value_parser(clap::value_parser!(bool))
+                        .help("true or false"),
+                ),
+        )
+        .subcommand(
+            Command::new("set_completion_verification")
+                .about("Set whether completions are checked against team locations in future games")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Mode")
+                        .required(true)
+                        .value_parser(["off", "warn", "enforce"]),
+                ),
+        )
+        .subcommand(
+            Command::new("set_catch_radius")
+                .about("Set how close catchers have to be to catch a team in future games")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Metres")
+                        .required(true)
+                        .value_parser(clap::value_parser!(f64)),
+                ),
+        )
+        .subcommand(
+            Command::new("set_catch_accuracy_margin")
+                .about(
+                    "Set by how much location accuracy may extend the catch radius in future games",
+                )
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Metres")
+                        .required(true)
+                        .value_parser(clap::value_parser!(f64)),
+                ),
+        )
+        .subcommand(
+            Command::new("set_max_location_age")
+                .about(
+                    "Set how old a location may be before it is considered stale in future games",
+                )
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Seconds")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("set_location_policy")
+                .about("Set what happens to catches when a team's location is missing or stale")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Policy")
+                        .required(true)
+                        .value_parser(["allow", "reject", "confirm"]),
+                ),
+        )
         .subcommand(
             Command::new("set_challenge_sets")
                 .about("Set the challenge sets that are used in future games")
@@ -294,51 +868,441 @@ fn cli() -> Command {
                 .arg(Arg::new("Name").required(true)),
         )
         .subcommand(Command::new("get_sectors").about("Get all sectors"))
-}
-
-#[tokio::main]
-async fn main() {
-    let mut args = cli().get_matches();
-    let generate_arg = args.contains_id("generate_zsh_completions");
-    let address = args.get_one("address").cloned();
-    let release = args.contains_id("release");
-    let address = address.unwrap_or(format!(
-        "/tmp/truinsocket_{}{}",
-        if release { "" } else { "dev_" },
-        env!("CARGO_PKG_VERSION")
-    ));
-    let args = args.remove_subcommand();
-    let (name, mut sub_args) = match args {
-        None => {
-            if generate_arg {
-                generate(Zsh, &mut cli(), "tredit", &mut std::io::stdout());
-            } else {
-                println!("Hiii, I'm tredit :3")
-            }
-            return;
-        }
-        Some(args) => args,
-    };
-    let (mut sender, _recvr) = connect(Some(&address)).await.unwrap();
-
-    match name.as_str() {
-        "get_sectors" => run_command(EngineAction::GetSectors, sender).await,
-
-        "rename_player" => {
-            let player_id = get_session_by_name(
-                sub_args.get_one::<String>("Player").expect("required"),
-                &mut sender,
-            )
-            .await;
-            let name = sub_args.remove_one("Name").expect("required");
-            run_command(
-                EngineAction::RenamePlayer {
-                    player_id,
-                    new_name: name,
-                },
-                sender,
-            )
-            .await
+        .subcommand(
+            Command::new("rename_sector")
+                .about("Rename a sector")
+                .arg(
+                    Arg::new("Sector ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("Name")
+                        .required(true)
+                        .value_parser(clap::value_parser!(char)),
+                ),
+        )
+        .subcommand(with_reference_args(
+            Command::new("remove_sector")
+                .about("Remove a sector, refusing if anything refers to it unless told otherwise")
+                .arg(
+                    Arg::new("Sector ID")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        ))
+        .subcommand(
+            Command::new("export_geojson")
+                .about("Export the team trails, catches and completions of a session as GeoJSON")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .required(false)
+                        .help("Start of the time window, either HH:MM (today) or RFC 3339"),
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .required(false)
+                        .help("End of the time window, either HH:MM (today) or RFC 3339"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(false)
+                        .help("File to write to, printed to stdout if not provided"),
+                ),
+        )
+        .subcommand(
+            Command::new("export_past_game")
+                .about("Export a past game as GPX, KML or GeoJSON to view it in a mapping app")
+                .alias("export-past-game")
+                .arg(Arg::new("Past Game ID").required(true))
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .required(false)
+                        .default_value("gpx")
+                        .value_parser(clap::value_parser!(ExportFormat))
+                        .help("gpx, kml or geojson"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(false)
+                        .help("File to write to, printed to stdout if not provided"),
+                ),
+        )
+        .subcommand(
+            Command::new("past_game_stats")
+                .about("Print statistics about a past game")
+                .arg(Arg::new("Past Game ID").required(true)),
+        )
+        .subcommand(
+            Command::new("challenge_analytics").about(
+                "Print how often each challenge was offered, chosen and skipped in past games",
+            ),
+        )
+        .subcommand(
+            Command::new("player_stats")
+                .about("Print a player's all-time statistics")
+                .arg(Arg::new("Player").required(true)),
+        )
+        .subcommand(
+            Command::new("leaderboard").about("Print the all-time leaderboard of all players"),
+        )
+        .subcommand(
+            Command::new("send_message")
+                .about("Send a chat message as the organisers")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Scope")
+                        .required(true)
+                        .help("all, catchers, organisers or a team"),
+                )
+                .arg(Arg::new("Text").required(true)),
+        )
+        .subcommand(
+            Command::new("get_messages")
+                .about("Print the chat history of a session")
+                .arg(Arg::new("Session").required(true)),
+        )
+        .subcommand(
+            Command::new("disputed_catches")
+                .about("Print all disputed catches that still need a decision")
+                .arg(Arg::new("Session").required(true)),
+        )
+        .subcommand(
+            Command::new("suspicious_locations")
+                .about("Print all locations that were ignored because they seemed implausible")
+                .arg(Arg::new("Session").required(true)),
+        )
+        .subcommand(
+            Command::new("location_quality")
+                .about("Print how well the location tracking of each player works")
+                .arg(Arg::new("Session").required(true)),
+        )
+        .subcommand(
+            Command::new("resolve_catch")
+                .about("Decide on a disputed catch")
+                .arg(Arg::new("Session").required(true))
+                .arg(Arg::new("Proposal ID").required(true))
+                .arg(
+                    Arg::new("Decision")
+                        .required(true)
+                        .value_parser(["uphold", "reject"]),
+                ),
+        )
+        .subcommand(
+            Command::new("announce")
+                .about("Send or schedule an announcement")
+                .arg(Arg::new("Session").required(true))
+                .arg(
+                    Arg::new("Target")
+                        .required(true)
+                        .help("all, catchers or a team"),
+                )
+                .arg(Arg::new("Text").required(true))
+                .arg(
+                    Arg::new("at")
+                        .long("at")
+                        .required(false)
+                        .help("Either HH:MM (today) or RFC 3339, sent right away if not provided"),
+                ),
+        )
+        .subcommand(
+            Command::new("get_announcements")
+                .about("Print all sent and scheduled announcements of a session")
+                .arg(Arg::new("Session").required(true)),
+        )
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = cli().get_matches();
+    let generate_arg = args.contains_id("generate_zsh_completions");
+    let address = args.get_one("address").cloned();
+    let release = args.contains_id("release");
+    let address = address.unwrap_or(format!(
+        "/tmp/truinsocket_{}{}",
+        if release { "" } else { "dev_" },
+        env!("CARGO_PKG_VERSION")
+    ));
+    let args = args.remove_subcommand();
+    let (name, mut sub_args) = match args {
+        None => {
+            if generate_arg {
+                generate(Zsh, &mut cli(), "tredit", &mut std::io::stdout());
+            } else {
+                println!("Hiii, I'm tredit :3")
+            }
+            return;
+        }
+        Some(args) => args,
+    };
+    let (mut sender, _recvr) = connect(Some(&address)).await.unwrap();
+
+    match name.as_str() {
+        "disputed_catches" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let teams = sender.get_session_state(session_id).await.unwrap().0;
+            match sender.get_disputed_catches(session_id).await {
+                Ok(proposals) => report::print_catch_proposals(&proposals, &teams),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "suspicious_locations" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let teams = sender.get_session_state(session_id).await.unwrap().0;
+            match sender.get_suspicious_locations(session_id).await {
+                Ok(locations) => report::print_suspicious_locations(&locations, &teams),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "location_quality" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let teams = sender.get_session_state(session_id).await.unwrap().0;
+            match sender.get_location_quality(session_id).await {
+                Ok(quality) => report::print_location_quality(&quality, &teams),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "resolve_catch" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let proposal_id = sub_args
+                .get_one::<String>("Proposal ID")
+                .expect("required")
+                .parse()
+                .expect("proposal ID should be a number");
+            let uphold = sub_args.get_one::<String>("Decision").expect("required") == "uphold";
+            run_command(
+                EngineAction::ResolveDisputedCatch {
+                    session_id,
+                    proposal_id,
+                    uphold,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "announce" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let target = sub_args.get_one::<String>("Target").expect("required");
+            let target = match target.parse::<MessageScope>() {
+                Ok(target) => target,
+                Err(_) => {
+                    MessageScope::Team(get_team_by_name(session_id, target, &mut sender).await)
+                }
+            };
+            let text = sub_args
+                .get_one::<String>("Text")
+                .expect("required")
+                .clone();
+            let time = sub_args.get_one::<String>("at").map(|t| parse_time(t));
+            match sender
+                .schedule_announcement(session_id, target, text, time)
+                .await
+            {
+                Ok(()) => eprintln!("{}", "Announcement scheduled".green().bold()),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "get_announcements" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let teams = sender.get_session_state(session_id).await.unwrap().0;
+            match sender.get_announcements(session_id).await {
+                Ok(announcements) => report::print_announcements(&announcements, &teams),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "send_message" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let scope = sub_args.get_one::<String>("Scope").expect("required");
+            let scope = match scope.parse::<MessageScope>() {
+                Ok(scope) => scope,
+                Err(_) => {
+                    MessageScope::Team(get_team_by_name(session_id, scope, &mut sender).await)
+                }
+            };
+            let text = sub_args
+                .get_one::<String>("Text")
+                .expect("required")
+                .clone();
+            match sender.send_message(session_id, scope, text).await {
+                Ok(()) => eprintln!("{}", "Message sent".green().bold()),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "get_messages" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let teams = sender.get_session_state(session_id).await.unwrap().0;
+            match sender.get_messages(session_id).await {
+                Ok(messages) => report::print_messages(&messages, &teams),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "export_geojson" => {
+            let session_id = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let since = sub_args.get_one::<String>("since").map(|t| parse_time(t));
+            let until = sub_args.get_one::<String>("until").map(|t| parse_time(t));
+            match sender.export_geojson(session_id, since, until).await {
+                Ok(geojson) => match sub_args.get_one::<String>("output") {
+                    Some(path) => {
+                        std::fs::write(path, geojson).expect("couldn't write file");
+                        eprintln!("{}", format!("Wrote GeoJSON to {}", path).green().bold());
+                    }
+                    None => println!("{}", geojson),
+                },
+                Err(err) => print_error(err),
+            }
+        }
+
+        "export_past_game" => {
+            let past_game_id = sub_args
+                .get_one::<String>("Past Game ID")
+                .expect("required")
+                .parse()
+                .expect("past game ID should be a number");
+            let format = *sub_args
+                .get_one::<ExportFormat>("format")
+                .expect("has default");
+            match sender.export_past_game(past_game_id, format).await {
+                Ok(export) => match sub_args.get_one::<String>("output") {
+                    Some(path) => {
+                        std::fs::write(path, export).expect("couldn't write file");
+                        eprintln!("{}", format!("Wrote past game to {}", path).green().bold());
+                    }
+                    None => println!("{}", export),
+                },
+                Err(err) => print_error(err),
+            }
+        }
+
+        "past_game_stats" => {
+            let past_game_id = sub_args
+                .get_one::<String>("Past Game ID")
+                .expect("required")
+                .parse()
+                .expect("past game ID should be a number");
+            match sender.get_past_game_stats(past_game_id).await {
+                Ok(stats) => report::print_past_game_stats(&stats),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "challenge_analytics" => match sender.get_challenge_analytics().await {
+            Ok(analytics) => report::print_challenge_analytics(analytics),
+            Err(err) => print_error(err),
+        },
+
+        "player_stats" => {
+            let player_id = get_player_by_name(
+                sub_args.get_one::<String>("Player").expect("required"),
+                &mut sender,
+            )
+            .await;
+            match sender.get_player_stats(player_id).await {
+                Ok(stats) => report::print_player_stats(&stats),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "leaderboard" => match sender.get_leaderboard().await {
+            Ok(leaderboard) => report::print_leaderboard(&leaderboard),
+            Err(err) => print_error(err),
+        },
+
+        "get_sectors" => run_command(EngineAction::GetSectors, sender).await,
+
+        "rename_sector" => {
+            run_command(
+                EngineAction::RenameSector {
+                    sector_id: *sub_args.get_one::<u64>("Sector ID").expect("required"),
+                    name: *sub_args.get_one::<char>("Name").expect("required"),
+                },
+                sender,
+            )
+            .await
+        }
+
+        "remove_sector" => {
+            let strategy = reference_strategy(
+                sub_args.get_flag("cascade"),
+                sub_args.get_one::<u64>("reassign").copied(),
+            );
+            run_command(
+                EngineAction::RemoveSector {
+                    sector_id: *sub_args.get_one::<u64>("Sector ID").expect("required"),
+                    strategy,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "rename_player" => {
+            let player_id = get_session_by_name(
+                sub_args.get_one::<String>("Player").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let name = sub_args.remove_one("Name").expect("required");
+            run_command(
+                EngineAction::RenamePlayer {
+                    player_id,
+                    new_name: name,
+                },
+                sender,
+            )
+            .await
         }
         "remove_team" => {
             let session_id = get_session_by_name(
@@ -388,6 +1352,136 @@ async fn main() {
             .await
         }
 
+        "set_catch_confirmation" => {
+            let session = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let config = PartialGameConfig {
+                catch_confirmation: sub_args.get_one::<bool>("Enabled").copied(),
+                ..Default::default()
+            };
+            run_command(
+                EngineAction::SetGameConfig {
+                    session_id: session,
+                    config,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "set_completion_verification" => {
+            let session = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let mode = sub_args
+                .get_one::<String>("Mode")
+                .expect("required")
+                .parse::<VerificationMode>()
+                .expect("checked by clap");
+            let config = PartialGameConfig {
+                completion_verification: Some(mode),
+                ..Default::default()
+            };
+            run_command(
+                EngineAction::SetGameConfig {
+                    session_id: session,
+                    config,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "set_catch_radius" => {
+            let session = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let config = PartialGameConfig {
+                catch_radius: sub_args.get_one::<f64>("Metres").copied(),
+                ..Default::default()
+            };
+            run_command(
+                EngineAction::SetGameConfig {
+                    session_id: session,
+                    config,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "set_catch_accuracy_margin" => {
+            let session = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let config = PartialGameConfig {
+                catch_accuracy_margin: sub_args.get_one::<f64>("Metres").copied(),
+                ..Default::default()
+            };
+            run_command(
+                EngineAction::SetGameConfig {
+                    session_id: session,
+                    config,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "set_max_location_age" => {
+            let session = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let config = PartialGameConfig {
+                max_location_age: sub_args.get_one::<u64>("Seconds").copied(),
+                ..Default::default()
+            };
+            run_command(
+                EngineAction::SetGameConfig {
+                    session_id: session,
+                    config,
+                },
+                sender,
+            )
+            .await
+        }
+
+        "set_location_policy" => {
+            let session = get_session_by_name(
+                sub_args.get_one::<String>("Session").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let policy = sub_args
+                .get_one::<String>("Policy")
+                .expect("required")
+                .parse::<LocationPolicy>()
+                .expect("checked by clap");
+            let config = PartialGameConfig {
+                location_policy: Some(policy),
+                ..Default::default()
+            };
+            run_command(
+                EngineAction::SetGameConfig {
+                    session_id: session,
+                    config,
+                },
+                sender,
+            )
+            .await
+        }
+
         "set_num_catchers" => {
             let session = get_session_by_name(
                 sub_args.get_one::<String>("Session").expect("required"),
@@ -487,6 +1581,31 @@ async fn main() {
 
         "get_challenge_sets" => run_command(EngineAction::GetChallengeSets, sender).await,
 
+        "rename_challenge_set" => {
+            let set_id = *sub_args
+                .get_one::<u64>("Challenge Set ID")
+                .expect("required");
+            let name = sub_args.remove_one::<String>("Name").expect("required");
+            match sender.rename_challenge_set(set_id, name).await {
+                Ok(()) => eprintln!("{}", "Renamed challenge set".green().bold()),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "remove_challenge_set" => {
+            let set_id = *sub_args
+                .get_one::<u64>("Challenge Set ID")
+                .expect("required");
+            let strategy = reference_strategy(
+                sub_args.get_flag("cascade"),
+                sub_args.get_one::<u64>("reassign").copied(),
+            );
+            match sender.remove_challenge_set(set_id, strategy).await {
+                Ok(()) => eprintln!("{}", "Removed challenge set".green().bold()),
+                Err(err) => print_error(err),
+            }
+        }
+
         "get_locations" => {
             let session = get_session_by_name(
                 sub_args.get_one::<String>("Session").expect("required"),
@@ -498,6 +1617,106 @@ async fn main() {
 
         "get_zones" => run_command(EngineAction::GetAllZones, sender).await,
 
+        "update_zone" => {
+            let zone_id = get_zone_by_number(
+                *sub_args.get_one::<u64>("Zone").expect("required"),
+                &mut sender,
+            )
+            .await;
+            run_command(
+                EngineAction::UpdateZone {
+                    zone_id,
+                    zone: sub_args.get_one::<u64>("number").copied(),
+                    num_conn_zones: sub_args.get_one::<u64>("num_conn_zones").copied(),
+                    num_connections: sub_args.get_one::<u64>("num_connections").copied(),
+                    train_through: sub_args.get_one::<bool>("train_through").copied(),
+                    mongus: sub_args.get_one::<bool>("mongus").copied(),
+                    s_bahn_zone: sub_args.get_one::<bool>("s_bahn_zone").copied(),
+                },
+                sender,
+            )
+            .await
+        }
+
+        "remove_zone" => {
+            let zone_id = get_zone_by_number(
+                *sub_args.get_one::<u64>("Zone").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let reassign = match sub_args.get_one::<u64>("reassign") {
+                Some(zone) => Some(get_zone_by_number(*zone, &mut sender).await),
+                None => None,
+            };
+            let strategy = reference_strategy(sub_args.get_flag("cascade"), reassign);
+            run_command(EngineAction::RemoveZone { zone_id, strategy }, sender).await
+        }
+
+        "export_database" => match sender.export_database().await {
+            Ok(archive) => {
+                let path = sub_args.get_one::<String>("File").expect("required");
+                std::fs::write(path, archive).expect("couldn't write file");
+                eprintln!(
+                    "{}",
+                    format!("Wrote database archive to {}", path).green().bold()
+                );
+            }
+            Err(err) => print_error(err),
+        },
+
+        "import_database" => {
+            let path = sub_args.get_one::<String>("File").expect("required");
+            let archive = std::fs::read_to_string(path).expect("couldn't read file");
+            if sub_args.contains_id("yes")
+                || interactive::get_input(
+                    "This replaces everything in the DB. Are you sure (yes/no) ",
+                )
+                .as_str()
+                    == "yes"
+            {
+                match sender.import_database(archive).await {
+                    Ok(()) => eprintln!("{}", "Imported database archive".green().bold()),
+                    Err(err) => print_error(err),
+                }
+            }
+        }
+
+        "import_stations" => {
+            let path = sub_args.get_one::<String>("File").expect("required");
+            match parsley::read_records(std::path::Path::new(path))
+                .and_then(|rows| rows.into_iter().collect::<anyhow::Result<Vec<_>>>())
+                .and_then(|records| parsley::parse_stations(&records))
+            {
+                Ok(stations) => run_command(EngineAction::ImportStations(stations), sender).await,
+                Err(err) => print_error(err),
+            }
+        }
+
+        "get_stations" => run_command(EngineAction::GetStations, sender).await,
+
+        "import_sector_boundaries" => {
+            let path = sub_args.get_one::<String>("File").expect("required");
+            let geojson = std::fs::read_to_string(path).expect("couldn't read file");
+            run_command(EngineAction::ImportSectorBoundaries(geojson), sender).await
+        }
+
+        "sector_map" => match sender.get_sector_map().await {
+            Ok(geojson) => match sub_args.get_one::<String>("output") {
+                Some(path) => {
+                    std::fs::write(path, geojson).expect("couldn't write file");
+                    eprintln!("{}", format!("Wrote GeoJSON to {}", path).green().bold());
+                }
+                None => println!("{}", geojson),
+            },
+            Err(err) => print_error(err),
+        },
+
+        "import_zone_boundaries" => {
+            let path = sub_args.get_one::<String>("File").expect("required");
+            let geojson = std::fs::read_to_string(path).expect("couldn't read file");
+            run_command(EngineAction::ImportZoneBoundaries(geojson), sender).await
+        }
+
         "stop" => {
             let session = get_session_by_name(
                 sub_args.get_one::<String>("Session").expect("required"),
@@ -518,6 +1737,175 @@ async fn main() {
 
         "get_challenges" => run_command(EngineAction::GetRawChallenges, sender).await,
 
+        "delete_challenge" => {
+            let id = *sub_args.get_one::<u64>("Challenge ID").expect("required");
+            let filter = ChallengeFilter {
+                ids: vec![id],
+                ..Default::default()
+            };
+            let challenges = match sender.query_challenges(filter, 0, 1).await {
+                Ok((challenges, _)) if !challenges.is_empty() => challenges,
+                Ok(_) => return print_error(format!("there is no challenge with ID {}", id)),
+                Err(err) => return print_error(err),
+            };
+            let sets = sender.get_challenge_sets().await.unwrap();
+            report::print_challenge_page(&challenges, &sets);
+            if sub_args.contains_id("yes")
+                || interactive::get_input("Delete this challenge? (yes/no) ").as_str() == "yes"
+            {
+                match sender.delete_challenge(id).await {
+                    Ok(()) => eprintln!("{}", "Deleted challenge".green().bold()),
+                    Err(err) => print_error(err),
+                }
+            }
+        }
+
+        "query_challenges" => {
+            let filter = challenge_filter(&sub_args, &mut sender).await;
+            let page = *sub_args.get_one::<usize>("page").unwrap();
+            let page_size = *sub_args.get_one::<usize>("page_size").unwrap();
+            match sender
+                .query_challenges(filter, page * page_size, page_size)
+                .await
+            {
+                Ok((challenges, total)) => {
+                    let sets = sender.get_challenge_sets().await.unwrap();
+                    report::print_challenge_page(&challenges, &sets);
+                    println!(
+                        "page {} of {}, {} challenges in total",
+                        page + 1,
+                        total.div_ceil(page_size.max(1)).max(1),
+                        total
+                    );
+                }
+                Err(err) => print_error(err),
+            }
+        }
+
+        "patch_challenges" => {
+            let filter = challenge_filter(&sub_args, &mut sender).await;
+            let mut patch: PartialInputChallenge = match sub_args.get_one::<String>("json") {
+                Some(json) => match serde_json::from_str(json) {
+                    Ok(patch) => patch,
+                    Err(err) => return print_error(format!("invalid JSON patch: {}", err)),
+                },
+                None => PartialInputChallenge::default(),
+            };
+            if let Some(status) = sub_args.get_one::<ChallengeStatus>("set_status") {
+                patch.status = Some(*status);
+            }
+            let mut add_sets = Vec::new();
+            for set in sub_args.get_many::<String>("add_set").unwrap_or_default() {
+                add_sets.push(get_challenge_set_by_name(set, &mut sender).await);
+            }
+            let mut remove_sets = Vec::new();
+            for set in sub_args
+                .get_many::<String>("remove_set")
+                .unwrap_or_default()
+            {
+                remove_sets.push(get_challenge_set_by_name(set, &mut sender).await);
+            }
+            interactive::patch_challenges(
+                sender,
+                filter,
+                patch,
+                add_sets,
+                remove_sets,
+                sub_args.get_one::<String>("comment").cloned(),
+                sub_args.contains_id("yes"),
+            )
+            .await
+        }
+
+        "challenge_history" => {
+            let challenge_id = *sub_args.get_one::<u64>("Challenge ID").expect("required");
+            match sender.get_challenge_history(challenge_id).await {
+                Ok(revisions) => {
+                    let players = sender.get_global_state().await.unwrap().1;
+                    report::print_challenge_history(&revisions, &players)
+                }
+                Err(err) => print_error(err),
+            }
+        }
+
+        "revert_challenge" => {
+            let challenge_id = *sub_args.get_one::<u64>("Challenge ID").expect("required");
+            let revision = *sub_args.get_one::<usize>("Revision").expect("required");
+            match sender.revert_challenge(challenge_id, revision, None).await {
+                Ok(()) => eprintln!(
+                    "{}",
+                    format!("Reverted challenge to revision {}", revision)
+                        .green()
+                        .bold()
+                ),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "edit_challenge" => {
+            let author = get_player_by_name(
+                sub_args.get_one::<String>("Player").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let challenge_id = *sub_args.get_one::<u64>("Challenge ID").expect("required");
+            let mut patch: PartialInputChallenge = match sub_args.get_one::<String>("json") {
+                Some(json) => match serde_json::from_str(json) {
+                    Ok(patch) => patch,
+                    Err(err) => return print_error(format!("invalid JSON patch: {}", err)),
+                },
+                None => PartialInputChallenge::default(),
+            };
+            if let Some(status) = sub_args.get_one::<ChallengeStatus>("set_status") {
+                patch.status = Some(*status);
+            }
+            let filter = ChallengeFilter {
+                ids: vec![challenge_id],
+                ..Default::default()
+            };
+            let mut challenge = match sender.query_challenges(filter, 0, 1).await {
+                Ok((mut challenges, _)) if !challenges.is_empty() => challenges.remove(0),
+                Ok(_) => {
+                    return print_error(format!("there is no challenge with ID {}", challenge_id));
+                }
+                Err(err) => return print_error(err),
+            };
+            challenge.apply_some(patch);
+            let comment = sub_args.get_one::<String>("comment").cloned();
+            match sender.edit_challenge(challenge, author, comment).await {
+                Ok(()) => eprintln!("{}", "Edited the challenge".green().bold()),
+                Err(err) => print_error(err),
+            }
+        }
+
+        "set_reviewer" => {
+            let player = get_player_by_name(
+                sub_args.get_one::<String>("Player").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let reviewer = *sub_args.get_one::<bool>("Reviewer").expect("required");
+            run_command(EngineAction::SetPlayerReviewer(player, reviewer), sender).await
+        }
+
+        "set_language" => {
+            let player = get_player_by_name(
+                sub_args.get_one::<String>("Player").expect("required"),
+                &mut sender,
+            )
+            .await;
+            let language = sub_args.get_one::<String>("Language").cloned();
+            run_command(EngineAction::SetPlayerLanguage(player, language), sender).await
+        }
+
+        "missing_translations" => match sender.get_raw_challenges().await {
+            Ok(challenges) => report::print_missing_translations(
+                &challenges,
+                sub_args.get_one::<String>("language").map(|l| l.as_str()),
+            ),
+            Err(err) => print_error(err),
+        },
+
         "delete_challenges" => {
             if sub_args.contains_id("yes")
                 || interactive::get_input("Are you sure (yes/no) ").as_str() == "yes"
@@ -526,7 +1914,58 @@ async fn main() {
             }
         }
 
-        "import" => interactive::import_challenges(sender).await,
+        "import" => {
+            let source = match sub_args.get_one::<String>("from") {
+                Some(path) => interactive::ImportSource::Local(path.into()),
+                None => interactive::ImportSource::Sheets,
+            };
+            interactive::import_challenges(sender, source, sub_args.get_flag("dry_run")).await
+        }
+
+        "import_timetable" => {
+            let parse_clock = |time: &str| {
+                use chrono::Timelike;
+                chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                    .expect("time should be HH:MM")
+                    .num_seconds_from_midnight()
+            };
+            let seconds =
+                |arg: &str| parse_clock(sub_args.get_one::<String>(arg).expect("has default"));
+            let window = gtfs::TimeWindow {
+                from: seconds("from"),
+                until: seconds("until"),
+                interval: sub_args.get_one::<u32>("interval").expect("has default") * 60,
+            };
+            let bands = sub_args
+                .get_many::<String>("band")
+                .unwrap_or_default()
+                .map(|band| {
+                    let (from, until) = band.split_once('-').expect("band should be HH:MM-HH:MM");
+                    let band = gtfs::TimeWindow {
+                        from: parse_clock(from),
+                        until: parse_clock(until),
+                        interval: window.interval,
+                    };
+                    assert!(band.from < band.until, "bands can't span midnight");
+                    band
+                })
+                .collect();
+            let date = match sub_args.get_one::<String>("date") {
+                Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .expect("date should be YYYY-MM-DD"),
+                None => chrono::Local::now().date_naive(),
+            };
+            interactive::import_timetable(
+                sender,
+                std::path::Path::new(sub_args.get_one::<String>("Directory").expect("required")),
+                date,
+                window,
+                bands,
+                sub_args.get_one::<String>("centre").expect("has default"),
+                sub_args.get_flag("dry_run"),
+            )
+            .await
+        }
 
         "rename_team" => {
             let session = get_session_by_name(
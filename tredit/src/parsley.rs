@@ -1,41 +1,72 @@
 use anyhow::Context;
 use libtruinlag::{
-    ChallengeSet, ChallengeStatus, ChallengeType, InputChallenge, RandomPlaceType, Sector,
-    TextError, Zone,
+    ChallengeSet, ChallengeStatus, ChallengeType, InputChallenge, RandomPlaceType, Sector, Station,
+    TextError, Zone, normalise_station_name,
 };
 use std::collections::HashMap;
+use std::path::Path;
 use std::str::FromStr;
 
-pub async fn get_data(url: &str) -> Vec<HashMap<String, String>> {
-    let mut records = Vec::new();
+/// A row of a sheet, with the column names as keys
+pub type Record = HashMap<String, String>;
 
+/// Downloads a published google sheet as csv and reads it into records. Rows that can't be read
+/// are kept as errors, so that the rest of the sheet can still be used.
+pub async fn get_data(url: &str) -> anyhow::Result<Vec<anyhow::Result<Record>>> {
     let csv_bytes = reqwest::get(url)
         .await
-        .unwrap()
+        .context("couldn't download the sheet")?
+        .error_for_status()?
         .text()
-        .await
-        .unwrap()
+        .await?
         .into_bytes();
+    read_csv(csv_bytes.as_slice())
+}
 
-    let mut rdr = csv::Reader::from_reader(csv_bytes.as_slice());
-    for result in rdr.deserialize() {
-        let challenge_data: HashMap<String, String> = match result {
-            Ok(result) => result,
-            Err(error) => {
-                eprintln!(
-                    "Error occurred in gathering data:\n{}\nIgnoring and continuing",
-                    error
-                );
-                continue;
-            }
-        };
-        records.push(challenge_data);
-    }
+fn read_csv(reader: impl std::io::Read) -> anyhow::Result<Vec<anyhow::Result<Record>>> {
+    let mut rdr = csv::Reader::from_reader(reader);
+    rdr.headers().context("couldn't read the header")?;
+    Ok(rdr
+        .deserialize()
+        .map(|result| result.context("couldn't read the row"))
+        .collect())
+}
+
+/// Reads a json array of objects into records. Values that aren't strings are converted into
+/// their json representation and `null` becomes an empty field.
+fn read_json(reader: impl std::io::Read) -> anyhow::Result<Vec<anyhow::Result<Record>>> {
+    let rows: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_reader(reader).context("expected an array of objects")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(text) => text,
+                        serde_json::Value::Null => String::new(),
+                        other => other.to_string(),
+                    };
+                    (key, value)
+                })
+                .collect()
+        })
+        .map(Ok)
+        .collect())
+}
 
-    records
+/// Reads a local csv or json file (depending on its extension) into records, the same way
+/// `get_data` does for online sheets.
+pub fn read_records(path: &Path) -> anyhow::Result<Vec<anyhow::Result<Record>>> {
+    let file = std::fs::File::open(path).context(format!("couldn't open {}", path.display()))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => read_json(file),
+        _ => read_csv(file),
+    }
+    .context(format!("couldn't read {}", path.display()))
 }
 
-fn parse_bool(record: &HashMap<String, String>, field: &str) -> anyhow::Result<bool> {
+fn parse_bool(record: &Record, field: &str) -> anyhow::Result<bool> {
     match record
         .get(field)
         .context(format!("field \"{}\" not found", field))?
@@ -48,7 +79,24 @@ fn parse_bool(record: &HashMap<String, String>, field: &str) -> anyhow::Result<b
     }
 }
 
-fn parse_to<T>(record: &HashMap<String, String>, field: &str) -> anyhow::Result<T>
+/// Parses the optional `latitude` and `longitude` columns. Older sheets don't have them at all.
+fn parse_coordinates(record: &Record) -> anyhow::Result<Option<(f32, f32)>> {
+    if !record.contains_key("latitude") || !record.contains_key("longitude") {
+        return Ok(None);
+    }
+    match (
+        parse_to_option(record, "latitude")?,
+        parse_to_option(record, "longitude")?,
+    ) {
+        (Some(latitude), Some(longitude)) => Ok(Some((latitude, longitude))),
+        (None, None) => Ok(None),
+        _ => Err(TextError(
+            "either both or none of latitude and longitude have to be given".into(),
+        ))?,
+    }
+}
+
+fn parse_to<T>(record: &Record, field: &str) -> anyhow::Result<T>
 where
     T: FromStr,
     <T as FromStr>::Err: Sync,
@@ -67,7 +115,7 @@ where
         ))
 }
 
-fn parse_to_option<T>(record: &HashMap<String, String>, field: &str) -> anyhow::Result<Option<T>>
+fn parse_to_option<T>(record: &Record, field: &str) -> anyhow::Result<Option<T>>
 where
     T: FromStr,
     <T as FromStr>::Err: Sync,
@@ -89,7 +137,7 @@ where
     }
 }
 
-fn parse_to_or<T>(record: &HashMap<String, String>, field: &str, default: T) -> anyhow::Result<T>
+fn parse_to_or<T>(record: &Record, field: &str, default: T) -> anyhow::Result<T>
 where
     T: FromStr,
     <T as FromStr>::Err: Sync,
@@ -103,10 +151,11 @@ where
     }
 }
 pub fn parse_record(
-    record: &HashMap<String, String>,
+    record: &Record,
     challenge_sets: &[ChallengeSet],
     truin_zones: &[Zone],
     truin_sectors: &[Sector],
+    stations: &[Station],
 ) -> anyhow::Result<Option<InputChallenge>> {
     let status: ChallengeStatus = record
         .get("status")
@@ -210,7 +259,7 @@ pub fn parse_record(
         .trim();
     if !(zone_text.is_empty() || zone_text == "%z" || zone_text == "%s") {
         for zone in zone_text.split(",").map(|s| s.trim()) {
-            let zone = zone.parse().context("couldn't parse a zone")?;
+            let zone = zone.parse::<u64>().context("couldn't parse a zone")?;
             zones.push(
                 truin_zones
                     .iter()
@@ -252,7 +301,7 @@ pub fn parse_record(
         translated_titles.insert("de_ch".into(), title);
     }
     if let Some(title) = title_fr_ch {
-        translated_titles.insert("de_fr".into(), title);
+        translated_titles.insert("fr_ch".into(), title);
     }
     if let Some(title) = title_en_uk {
         translated_titles.insert("en_uk".into(), title);
@@ -275,6 +324,7 @@ pub fn parse_record(
         title: parse_to_option(record, "title")?,
         description: parse_to_option(record, "description")?,
         place: parse_to_option(record, "place")?,
+        place_coordinates: parse_coordinates(record)?,
         kaffskala: parse_to_option(record, "kaffskala")?,
         grade: parse_to_option(record, "grade")?,
         zone: zones,
@@ -301,7 +351,121 @@ pub fn parse_record(
         translated_descriptions,
     };
 
-    challenge.check_validity()?;
+    challenge.check_validity(stations)?;
 
     Ok(Some(challenge))
 }
+
+/// Parses stations either from a GTFS `stops.txt` or from a plain csv file with the columns
+/// `name`, `latitude`, `longitude`, `zone` and `aliases` (separated by `;`).
+///
+/// In GTFS files, only the stations themselves are used and their individual platforms are
+/// skipped. Stops that share a name are merged into one station.
+pub fn parse_stations(records: &[Record]) -> anyhow::Result<Vec<Station>> {
+    let gtfs = records
+        .first()
+        .is_some_and(|r| r.contains_key("stop_name") && r.contains_key("stop_lat"));
+    let mut stations: Vec<Station> = Vec::new();
+    for record in records {
+        let station = if gtfs {
+            let is_station = record.get("location_type").is_some_and(|t| t.trim() == "1");
+            let has_parent = record
+                .get("parent_station")
+                .is_some_and(|p| !p.trim().is_empty());
+            if !is_station && has_parent {
+                continue;
+            }
+            Station {
+                name: parse_to(record, "stop_name")?,
+                latitude: parse_to(record, "stop_lat")?,
+                longitude: parse_to(record, "stop_lon")?,
+                zone: record.get("zone_id").and_then(|z| z.trim().parse().ok()),
+                aliases: Vec::new(),
+                id: None,
+            }
+        } else {
+            Station {
+                name: parse_to(record, "name")?,
+                latitude: parse_to(record, "latitude")?,
+                longitude: parse_to(record, "longitude")?,
+                zone: parse_to_option(record, "zone")?,
+                aliases: record
+                    .get("aliases")
+                    .map(|a| {
+                        a.split(';')
+                            .map(|a| a.trim().to_string())
+                            .filter(|a| !a.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                id: None,
+            }
+        };
+        let name = normalise_station_name(&station.name);
+        if name.is_empty() {
+            continue;
+        }
+        if !stations
+            .iter()
+            .any(|s| normalise_station_name(&s.name) == name)
+        {
+            stations.push(station);
+        }
+    }
+    Ok(stations)
+}
+
+/// A zone as described in the zonic kaff sheet
+pub struct ZoneRecord {
+    pub zone: u64,
+    pub num_conn_zones: u64,
+    pub num_connections: u64,
+    pub train_through: bool,
+    pub mongus: bool,
+    /// the names of the close sectors
+    pub sectors: Vec<char>,
+}
+
+pub fn parse_zone_record(record: &Record) -> anyhow::Result<ZoneRecord> {
+    Ok(ZoneRecord {
+        zone: parse_to(record, "Zone")?,
+        num_conn_zones: parse_to(record, "num conn zones")?,
+        num_connections: parse_to(record, "num connections")?,
+        train_through: parse_bool(record, "train through")?,
+        mongus: parse_bool(record, "Mongus")?,
+        sectors: record
+            .get("sectors")
+            .context("field \"sectors\" not found")?
+            .trim()
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ',')
+            .collect(),
+    })
+}
+
+/// Parses a row of the distance sheet into the two zone numbers and the travel time in minutes
+pub fn parse_distance_record(record: &Record) -> anyhow::Result<(u64, u64, u64)> {
+    Ok((
+        parse_to(record, "Zone A")?,
+        parse_to(record, "Zone B")?,
+        parse_to(record, "Travel Time")?,
+    ))
+}
+
+/// Parses a row of the sector sheet into the sector name and the names of its neighbours
+pub fn parse_sector_record(record: &Record) -> anyhow::Result<(char, Vec<char>)> {
+    let sector = record
+        .get("sector")
+        .context("field \"sector\" not found")?
+        .trim()
+        .chars()
+        .next()
+        .context("sector is empty")?;
+    let neighbours = record
+        .get("neighbours")
+        .context("field \"neighbours\" not found")?
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .collect();
+    Ok((sector, neighbours))
+}
@@ -0,0 +1,449 @@
+use colored::Colorize;
+use libtruinlag::{
+    Announcement, CatchProposal, ChallengeAnalytics, ChallengeRevision, ChallengeSet,
+    ChallengeStatus, InputChallenge, LocationSuspicion, Message, MessageScope, PastGameStats,
+    Player, PlayerLocationQuality, PlayerStats, RawChallenge, SkippedChallenge, SuspiciousLocation,
+    Team,
+};
+
+/// Formats a duration in seconds as `h:mm`
+fn hours_minutes(seconds: i64) -> String {
+    format!("{}:{:02}", seconds / 3600, (seconds % 3600) / 60)
+}
+
+/// Formats a distance in metres as kilometres
+fn kilometres(metres: f64) -> String {
+    format!("{:.1} km", metres / 1000.0)
+}
+
+fn print_skipped(skipped: &[SkippedChallenge]) {
+    if skipped.is_empty() {
+        println!("    (none)");
+    }
+    for challenge in skipped {
+        println!(
+            "    {}x {} ({})",
+            challenge.times, challenge.title, challenge.id
+        );
+    }
+}
+
+pub fn print_past_game_stats(stats: &PastGameStats) {
+    println!(
+        "{} ({}, {})",
+        stats.name.bold(),
+        stats.id,
+        hours_minutes(stats.duration)
+    );
+    println!("  total points:          {}", stats.total_points);
+    println!(
+        "  total distance:        {}",
+        kilometres(stats.total_distance)
+    );
+    println!("  catches:               {}", stats.total_catches);
+    println!(
+        "  completed challenges:  {}",
+        stats.total_completed_challenges
+    );
+    println!(
+        "  avg. challenge points: {:.1}",
+        stats.average_challenge_points
+    );
+    println!("  most skipped:");
+    print_skipped(&stats.most_skipped);
+
+    let mut teams = stats.teams.clone();
+    teams.sort_by_key(|t| std::cmp::Reverse(t.points));
+    for team in teams {
+        println!();
+        println!("{} ({})", team.name.bold(), team.team_id);
+        println!("  points:                {}", team.points);
+        println!("  points per hour:       {:.1}", team.points_per_hour);
+        println!(
+            "  distance travelled:    {}",
+            kilometres(team.distance_travelled)
+        );
+        println!("  catches:               {}", team.catches);
+        println!("  times caught:          {}", team.times_caught);
+        println!("  completed challenges:  {}", team.completed_challenges);
+        println!(
+            "  avg. challenge points: {:.1}",
+            team.average_challenge_points
+        );
+        println!(
+            "  catcher / runner:      {} / {}",
+            hours_minutes(team.time_as_catcher),
+            hours_minutes(team.time_as_runner)
+        );
+        println!("  most skipped:");
+        print_skipped(&team.most_skipped);
+    }
+}
+
+pub fn print_challenge_analytics(mut analytics: Vec<ChallengeAnalytics>) {
+    // the challenges that get skipped the most are the most interesting ones for curators
+    let skip_rate = |a: &ChallengeAnalytics| a.skipped as f64 / a.offered.max(1) as f64;
+    analytics.sort_by(|a, b| skip_rate(b).total_cmp(&skip_rate(a)));
+    println!(
+        "{}",
+        format!(
+            "{:>5} {:>7} {:>6} {:>7} {:>6} {:>8} {:>8}  title",
+            "id", "offered", "chosen", "skipped", "skip%", "avg.pts", "avg.time"
+        )
+        .bold()
+    );
+    for challenge in analytics {
+        println!(
+            "{:>5} {:>7} {:>6} {:>7} {:>5.0}% {:>8} {:>8}  {}",
+            challenge.id,
+            challenge.offered,
+            challenge.chosen,
+            challenge.skipped,
+            skip_rate(&challenge) * 100.0,
+            challenge
+                .average_points
+                .map(|p| format!("{:.1}", p))
+                .unwrap_or("-".into()),
+            challenge
+                .average_completion_time
+                .map(hours_minutes)
+                .unwrap_or("-".into()),
+            challenge.title
+        );
+    }
+}
+
+fn favourite_zones(stats: &PlayerStats) -> String {
+    stats
+        .favourite_zones
+        .iter()
+        .map(|(zone, count)| format!("{} ({}x)", zone, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn print_player_stats(stats: &PlayerStats) {
+    println!("{} ({})", stats.name.bold(), stats.id);
+    println!("  games played:          {}", stats.games_played);
+    println!("  points contributed:    {}", stats.points_contributed);
+    println!("  catches:               {}", stats.catches);
+    println!("  times caught:          {}", stats.times_caught);
+    println!("  challenges completed:  {}", stats.challenges_completed);
+    println!("  favourite zones:       {}", favourite_zones(stats));
+}
+
+pub fn print_leaderboard(leaderboard: &[PlayerStats]) {
+    println!(
+        "{}",
+        format!(
+            "{:>3} {:<20} {:>5} {:>6} {:>7} {:>6} {:>10}",
+            "#", "name", "games", "points", "catches", "caught", "challenges"
+        )
+        .bold()
+    );
+    for (rank, stats) in leaderboard.iter().enumerate() {
+        println!(
+            "{:>3} {:<20} {:>5} {:>6} {:>7} {:>6} {:>10}",
+            rank + 1,
+            stats.name,
+            stats.games_played,
+            stats.points_contributed,
+            stats.catches,
+            stats.times_caught,
+            stats.challenges_completed
+        );
+    }
+}
+
+fn scope_name(scope: MessageScope, teams: &[Team]) -> String {
+    match scope {
+        MessageScope::Team(team) => teams
+            .get(team)
+            .map(|t| t.name.clone())
+            .unwrap_or(format!("team {}", team)),
+        MessageScope::Catchers => "catchers".into(),
+        MessageScope::All => "all".into(),
+        MessageScope::Organisers => "organisers".into(),
+    }
+}
+
+fn clock_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn date_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+fn player_name(player: u64, teams: &[Team]) -> String {
+    teams
+        .iter()
+        .flat_map(|t| &t.players)
+        .find(|p| p.id == player)
+        .map(|p| p.name.clone())
+        .unwrap_or(format!("player {}", player))
+}
+
+/// Prints a session's chat history, using the session's teams to look up names
+pub fn print_messages(messages: &[Message], teams: &[Team]) {
+    for message in messages {
+        let author = match message.author {
+            None => "organisers".to_string(),
+            Some(author) => player_name(author, teams),
+        };
+        println!(
+            "{} {} -> {}: {}",
+            clock_time(message.time),
+            author.bold(),
+            scope_name(message.scope, teams),
+            message.text
+        );
+        if !message.picture_ids.is_empty() {
+            println!("      pictures: {:?}", message.picture_ids);
+        }
+    }
+}
+
+/// Prints a session's announcements along with how many players have read them
+pub fn print_announcements(announcements: &[Announcement], teams: &[Team]) {
+    for announcement in announcements {
+        let status = if announcement.delivered {
+            format!("read by {}", announcement.acknowledged_by.len())
+        } else {
+            "scheduled".to_string()
+        };
+        println!(
+            "{:>3} {} -> {} ({}): {}",
+            announcement.id,
+            clock_time(announcement.time),
+            scope_name(announcement.target, teams).bold(),
+            status,
+            announcement.text
+        );
+    }
+}
+
+/// Prints catch proposals, e.g. the disputed ones that the organisers need to decide on
+pub fn print_catch_proposals(proposals: &[CatchProposal], teams: &[Team]) {
+    if proposals.is_empty() {
+        println!("no disputed catches :)");
+    }
+    for proposal in proposals {
+        println!(
+            "{:>3} {} {} caught {} ({:?})",
+            proposal.id,
+            clock_time(proposal.time),
+            scope_name(MessageScope::Team(proposal.catcher), teams).bold(),
+            scope_name(MessageScope::Team(proposal.caught), teams).bold(),
+            proposal.status
+        );
+        if let Some(reason) = &proposal.dispute_reason {
+            println!("      reason: {}", reason);
+        }
+    }
+}
+
+/// Prints the locations that were ignored because they seemed implausible
+pub fn print_suspicious_locations(locations: &[SuspiciousLocation], teams: &[Team]) {
+    if locations.is_empty() {
+        println!("no suspicious locations :)");
+    }
+    for location in locations {
+        let suspicion = match location.suspicion {
+            LocationSuspicion::FutureTimestamp(seconds) => {
+                format!("timestamp {} s in the future", seconds)
+            }
+            LocationSuspicion::OutOfOrder => "older than the previous location".to_string(),
+            LocationSuspicion::TooFast {
+                implied_speed,
+                reported_speed,
+            } => format!(
+                "moved at {:.0} km/h, reported {:.0} km/h",
+                implied_speed * 3.6,
+                reported_speed * 3.6
+            ),
+        };
+        println!(
+            "{} {} ({}) at {:.5}, {:.5}: {}",
+            clock_time(location.received),
+            player_name(location.player, teams).bold(),
+            scope_name(MessageScope::Team(location.team), teams),
+            location.location.latitude,
+            location.location.longitude,
+            suspicion
+        );
+    }
+}
+
+/// Prints how well the location tracking of each player works, worst accuracy first
+pub fn print_location_quality(quality: &[PlayerLocationQuality], teams: &[Team]) {
+    let mut quality = quality.to_vec();
+    quality.sort_by(|a, b| {
+        b.average_accuracy
+            .unwrap_or(f32::INFINITY)
+            .total_cmp(&a.average_accuracy.unwrap_or(f32::INFINITY))
+    });
+    println!(
+        "{}",
+        format!(
+            "{:<20} {:<15} {:>8} {:>5} {:>10} {:>8} {:>5} {:>6}",
+            "player", "team", "received", "used", "suspicious", "accuracy", "last", "splits"
+        )
+        .bold()
+    );
+    for player in quality {
+        let splits = if player.separated {
+            format!("{}!", player.times_separated).red().to_string()
+        } else {
+            player.times_separated.to_string()
+        };
+        println!(
+            "{:<20} {:<15} {:>8} {:>5} {:>10} {:>8} {:>5} {:>6}",
+            player_name(player.player, teams),
+            scope_name(MessageScope::Team(player.team), teams),
+            player.received,
+            player.used,
+            player.suspicious,
+            player
+                .average_accuracy
+                .map(|a| format!("{:.0} m", a))
+                .unwrap_or("-".into()),
+            player.last_location.map(clock_time).unwrap_or("-".into()),
+            splits
+        );
+    }
+}
+
+/// Prints a short overview of challenges, one per line
+pub fn print_challenges(challenges: &[RawChallenge]) {
+    if challenges.is_empty() {
+        println!("no challenges");
+    }
+    for challenge in challenges {
+        let sets: Vec<&str> = challenge.sets.iter().map(|s| s.name.as_str()).collect();
+        let zones: Vec<String> = challenge.zone.iter().map(|z| z.zone.to_string()).collect();
+        println!(
+            "{:>5} {:<9} {:<17} {} [{}] ({})",
+            challenge.id.map_or("-".to_string(), |id| id.to_string()),
+            format!("{:?}", challenge.status),
+            format!("{:?}", challenge.kind),
+            challenge.title.as_deref().unwrap_or("untitled").bold(),
+            sets.join(", "),
+            zones.join(", ")
+        );
+    }
+}
+
+/// Prints a page of challenge search results, one per line
+pub fn print_challenge_page(challenges: &[InputChallenge], sets: &[ChallengeSet]) {
+    if challenges.is_empty() {
+        println!("no challenges");
+    }
+    for challenge in challenges {
+        let sets: Vec<String> = challenge
+            .sets
+            .iter()
+            .map(|id| {
+                sets.iter()
+                    .find(|s| s.id == *id)
+                    .map_or(id.to_string(), |s| s.name.clone())
+            })
+            .collect();
+        println!(
+            "{:>5} {:<9} {:<17} {} [{}]",
+            challenge.id.map_or("-".to_string(), |id| id.to_string()),
+            format!("{:?}", challenge.status),
+            format!("{:?}", challenge.kind),
+            challenge.title.as_deref().unwrap_or("untitled").bold(),
+            sets.join(", ")
+        );
+    }
+}
+
+/// Prints, for every language that challenges are translated to, the challenges whose title or
+/// description is missing in that language. Rejected challenges are left out.
+pub fn print_missing_translations(challenges: &[RawChallenge], language: Option<&str>) {
+    let challenges: Vec<&RawChallenge> = challenges
+        .iter()
+        .filter(|c| c.status != ChallengeStatus::Rejected)
+        .collect();
+    let mut languages: Vec<&str> = match language {
+        Some(language) => vec![language],
+        None => challenges
+            .iter()
+            .flat_map(|c| {
+                c.translated_titles
+                    .keys()
+                    .chain(c.translated_descriptions.keys())
+            })
+            .map(|l| l.as_str())
+            .collect(),
+    };
+    languages.sort_unstable();
+    languages.dedup();
+    if languages.is_empty() {
+        println!("no translations");
+    }
+    for language in languages {
+        let missing: Vec<(&RawChallenge, Vec<&str>)> = challenges
+            .iter()
+            .map(|c| {
+                let mut parts = Vec::new();
+                if c.title.is_some() && !c.translated_titles.contains_key(language) {
+                    parts.push("title");
+                }
+                if c.description.is_some() && !c.translated_descriptions.contains_key(language) {
+                    parts.push("description");
+                }
+                (*c, parts)
+            })
+            .filter(|(_, parts)| !parts.is_empty())
+            .collect();
+        println!(
+            "{}: {} of {} challenges missing",
+            language.bold(),
+            missing.len(),
+            challenges.len()
+        );
+        for (challenge, parts) in missing {
+            println!(
+                "    {:>5} {} ({})",
+                challenge.id.map_or("-".to_string(), |id| id.to_string()),
+                challenge.title.as_deref().unwrap_or("untitled"),
+                parts.join(", ")
+            );
+        }
+    }
+}
+
+/// Prints the revisions of a challenge, oldest first
+pub fn print_challenge_history(revisions: &[ChallengeRevision], players: &[Player]) {
+    for revision in revisions {
+        let author = match revision.author {
+            None => "organisers".to_string(),
+            Some(author) => players
+                .iter()
+                .find(|p| p.id == author)
+                .map_or(format!("player {}", author), |p| p.name.clone()),
+        };
+        println!(
+            "{:>3} {} {} {:?}: {}",
+            revision.revision,
+            date_time(revision.time),
+            author.bold(),
+            revision.challenge.status,
+            revision.challenge.title.as_deref().unwrap_or("untitled")
+        );
+        if let Some(comment) = &revision.comment {
+            println!("      {}", comment);
+        }
+    }
+}
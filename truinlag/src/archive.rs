@@ -0,0 +1,273 @@
+//! Backups of the whole database.
+//!
+//! An archive holds every collection of the `EngineSchema` together with the ids of its entries,
+//! so that all references between entries survive an export and import. Archives are JSON, since
+//! that keeps them readable and lets fields that were added later fall back to their defaults.
+
+use crate::{
+    PastGameEntry, PictureEntry, PlayerEntry, SectorEntry, StationEntry, ZoneEntry,
+    challenge::{ChallengeEntry, ChallengeRevisionEntry, ChallengeSetEntry},
+    session::Session,
+};
+use libtruinlag::commands::Error;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The version of the archive format. Archives with a different version can't be imported.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// The highest id an entry of an archive may have. The engine keeps its collections in vectors
+/// indexed by id, so a huge id would make it allocate huge amounts of memory on import.
+pub const MAX_ID: u64 = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DatabaseArchive {
+    pub version: u32,
+    /// the version of truinlag that created the archive
+    pub truinlag_version: String,
+    pub created: chrono::DateTime<chrono::Local>,
+    pub sessions: Vec<(u64, Session)>,
+    pub players: Vec<(u64, PlayerEntry)>,
+    pub challenges: Vec<(u64, ChallengeEntry)>,
+    pub challenge_sets: Vec<(u64, ChallengeSetEntry)>,
+    #[serde(default)]
+    pub challenge_revisions: Vec<(u64, ChallengeRevisionEntry)>,
+    pub zones: Vec<(u64, ZoneEntry)>,
+    pub sectors: Vec<(u64, SectorEntry)>,
+    #[serde(default)]
+    pub stations: Vec<(u64, StationEntry)>,
+    pub past_games: Vec<(u64, PastGameEntry)>,
+    pub pictures: Vec<(u64, PictureEntry)>,
+}
+
+impl DatabaseArchive {
+    /// Parses an archive and makes sure it can be imported as it is.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+        let header: Header = serde_json::from_str(json)
+            .map_err(|err| Error::BadData(format!("not a database archive: {}", err)))?;
+        if header.version != ARCHIVE_VERSION {
+            return Err(Error::BadData(format!(
+                "the archive has version {}, but only version {} is supported",
+                header.version, ARCHIVE_VERSION
+            )));
+        }
+        let archive: Self = serde_json::from_str(json)
+            .map_err(|err| Error::BadData(format!("couldn't read the archive: {}", err)))?;
+        archive.check_references()?;
+        Ok(archive)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|err| {
+            error!("Engine: couldn't serialise database archive: {}", err);
+            Error::InternalError
+        })
+    }
+
+    /// Makes sure that all ids that entries refer to exist in the archive. All problems are
+    /// collected into a single error.
+    pub fn check_references(&self) -> Result<(), Error> {
+        fn ids<T>(entries: &[(u64, T)]) -> HashSet<u64> {
+            entries.iter().map(|(id, _)| *id).collect()
+        }
+        let sessions = ids(&self.sessions);
+        let players = ids(&self.players);
+        let challenges = ids(&self.challenges);
+        let challenge_sets = ids(&self.challenge_sets);
+        let zones = ids(&self.zones);
+        let zone_numbers: HashSet<u64> = self.zones.iter().map(|(_, zone)| zone.zone).collect();
+        let sectors = ids(&self.sectors);
+        let pictures = ids(&self.pictures);
+
+        let mut problems = Vec::new();
+        let mut check = |exists: bool, what: String| {
+            if !exists {
+                problems.push(what);
+            }
+        };
+
+        let unique_ids = ids(&self.sessions).len() == self.sessions.len()
+            && players.len() == self.players.len()
+            && challenges.len() == self.challenges.len()
+            && challenge_sets.len() == self.challenge_sets.len()
+            && ids(&self.challenge_revisions).len() == self.challenge_revisions.len()
+            && zones.len() == self.zones.len()
+            && sectors.len() == self.sectors.len()
+            && ids(&self.stations).len() == self.stations.len()
+            && ids(&self.past_games).len() == self.past_games.len()
+            && pictures.len() == self.pictures.len();
+        check(unique_ids, "a collection contains an id twice".into());
+        let highest_id = [
+            &sessions,
+            &players,
+            &challenges,
+            &challenge_sets,
+            &ids(&self.challenge_revisions),
+            &zones,
+            &sectors,
+            &ids(&self.stations),
+            &ids(&self.past_games),
+            &pictures,
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .copied()
+        .unwrap_or(0);
+        check(
+            highest_id <= MAX_ID,
+            format!("id {} is above the maximum of {}", highest_id, MAX_ID),
+        );
+
+        for (id, player) in &self.players {
+            if let Some(session) = player.session {
+                check(
+                    sessions.contains(&session),
+                    format!("player {} is in missing session {}", id, session),
+                );
+            }
+            if let Some(picture) = player.picture {
+                check(
+                    pictures.contains(&picture),
+                    format!("player {} has missing picture {}", id, picture),
+                );
+            }
+        }
+        for (id, session) in &self.sessions {
+            for set in session.config.challenge_sets.iter().flatten() {
+                check(
+                    challenge_sets.contains(set),
+                    format!("session {} uses missing challenge set {}", id, set),
+                );
+            }
+            if let Some(zone) = session.config.start_zone {
+                check(
+                    zone_numbers.contains(&zone),
+                    format!("session {} starts in missing zone {}", id, zone),
+                );
+            }
+            if let Some(zone) = session.config.centre_zone {
+                check(
+                    zone_numbers.contains(&zone),
+                    format!("session {} has missing centre zone {}", id, zone),
+                );
+            }
+            for team in &session.teams {
+                // before a game starts, the zone of a team isn't set yet
+                if session.game.is_some() {
+                    check(
+                        zones.contains(&team.current_zone_id),
+                        format!(
+                            "team {} of session {} is in missing zone {}",
+                            team.name, id, team.current_zone_id
+                        ),
+                    );
+                }
+                for player in &team.players {
+                    check(
+                        players.contains(player),
+                        format!(
+                            "team {} of session {} has missing player {}",
+                            team.name, id, player
+                        ),
+                    );
+                }
+                if let Some(sector) = team.current_sector_id {
+                    check(
+                        sectors.contains(&sector),
+                        format!(
+                            "team {} of session {} is in missing sector {}",
+                            team.name, id, sector
+                        ),
+                    );
+                }
+                if let Some(picture) = team.picture {
+                    check(
+                        pictures.contains(&picture),
+                        format!(
+                            "team {} of session {} has missing picture {}",
+                            team.name, id, picture
+                        ),
+                    );
+                }
+                for picture in team.periods.iter().flat_map(|p| &p.pictures) {
+                    check(
+                        pictures.contains(picture),
+                        format!(
+                            "team {} of session {} has missing period picture {}",
+                            team.name, id, picture
+                        ),
+                    );
+                }
+            }
+        }
+        for (id, challenge) in &self.challenges {
+            for set in &challenge.sets {
+                check(
+                    challenge_sets.contains(set),
+                    format!("challenge {} is in missing set {}", id, set),
+                );
+            }
+            for zone in &challenge.zone {
+                check(
+                    zones.contains(zone),
+                    format!("challenge {} is in missing zone {}", id, zone),
+                );
+            }
+            for sector in &challenge.sector {
+                check(
+                    sectors.contains(sector),
+                    format!("challenge {} is in missing sector {}", id, sector),
+                );
+            }
+        }
+        for (id, zone) in &self.zones {
+            for sector in &zone.sectors {
+                check(
+                    sectors.contains(sector),
+                    format!("zone {} has missing close sector {}", id, sector),
+                );
+            }
+            for target in zone
+                .minutes_to
+                .keys()
+                .chain(zone.travel_bands.iter().flat_map(|b| b.minutes_to.keys()))
+            {
+                check(
+                    zones.contains(target),
+                    format!("zone {} has a travel time to missing zone {}", id, target),
+                );
+            }
+        }
+        for (id, sector) in &self.sectors {
+            for neighbour in &sector.neighbours {
+                check(
+                    sectors.contains(neighbour),
+                    format!("sector {} has missing neighbour {}", id, neighbour),
+                );
+            }
+        }
+        for (id, station) in &self.stations {
+            if let Some(zone) = station.zone {
+                check(
+                    zones.contains(&zone),
+                    format!("station {} is in missing zone {}", id, zone),
+                );
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::BadData(format!(
+                "the archive has broken references: {}",
+                problems.join("; ")
+            )))
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::TimerHook;
+use libtruinlag::{CatchProposal, CatchStatus};
+use serde::{Deserialize, Serialize};
+
+/// The representation of a catch proposal in the db. Proposals are only made if catch
+/// confirmation is turned on. They are kept after they have been dealt with, so that their index
+/// can be used as a stable id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatchProposalEntry {
+    pub catcher: usize,
+    pub caught: usize,
+    /// the period id of the catcher team when the catch was proposed, used to check that nothing
+    /// happened to the catcher in the meantime
+    pub catcher_period_id: usize,
+    pub status: CatchStatus,
+    pub time: chrono::DateTime<chrono::Local>,
+    pub deadline: chrono::DateTime<chrono::Local>,
+    /// The timer that makes the catch go through if the caught team doesn't react. It is `Some`
+    /// as long as the proposal is pending.
+    pub timeout: Option<TimerHook>,
+    pub dispute_reason: Option<String>,
+}
+
+impl CatchProposalEntry {
+    /// Converts the engine-internal catch proposal to a truinlag catch proposal
+    pub fn to_sendable(&self, id: usize) -> CatchProposal {
+        CatchProposal {
+            catcher: self.catcher,
+            caught: self.caught,
+            status: self.status,
+            time: self.time.timestamp(),
+            deadline: self.deadline.timestamp(),
+            dispute_reason: self.dispute_reason.clone(),
+            id,
+        }
+    }
+
+    /// Whether the proposal still waits for a decision, either by the caught team or by the
+    /// organisers
+    pub fn is_open(&self) -> bool {
+        matches!(self.status, CatchStatus::Pending | CatchStatus::Disputed)
+    }
+}
@@ -14,6 +14,10 @@ use rand::rng;
 use rand_distr::{Distribution, Normal};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use strsim::normalized_damerau_levenshtein as strcmp;
+
+/// How similar a word has to be to a search term to count as a match, between 0 and 1
+const SEARCH_THRESHOLD: f64 = 0.75;
 
 /// The representation of a challenge set inside the db
 #[derive(Debug, Clone, Collection, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -35,7 +39,7 @@ impl ChallengeSetEntry {
 
 /// The representation of a raw challenge inside the db. Conforms roughly to the challenge data
 /// layout presented in UC4
-#[derive(Debug, Clone, Collection, Serialize, Deserialize)]
+#[derive(Debug, Clone, Collection, Serialize, Deserialize, PartialEq)]
 #[collection(name = "challenge", views = [UnspecificChallengeEntries, SpecificChallengeEntries, GoodChallengeEntries])]
 pub struct ChallengeEntry {
     pub kind: ChallengeType,
@@ -48,6 +52,10 @@ pub struct ChallengeEntry {
     /// Applicable to challenges of types `Kaff` and `ZKaff`. The name of the transit station the
     /// challenge corresponds to.
     pub place: Option<String>,
+    /// The latitude and longitude of the place, if known. Used to check that teams are actually
+    /// at the place when they complete the challenge.
+    #[serde(default)]
+    pub place_coordinates: Option<(f32, f32)>,
     pub comment: String,
     pub kaffskala: Option<u8>,
     pub grade: Option<u8>,
@@ -76,10 +84,11 @@ pub struct ChallengeEntry {
 }
 
 impl ChallengeEntry {
-    /// Calculates the distance to a challenge from a provided zone
+    /// Calculates the distance to a challenge from a provided zone, using the travel durations of
+    /// the current time of day
     pub fn distance(&self, from: &DBEntry<ZoneEntry>) -> u64 {
         match self.closest_zone(from) {
-            Some(zone) => *from.contents.minutes_to.get(&zone).unwrap_or(&0),
+            Some(zone) => *from.contents.current_minutes_to().get(&zone).unwrap_or(&0),
             None => 0,
         }
     }
@@ -87,10 +96,11 @@ impl ChallengeEntry {
     /// Selects the zone from the challenge's zones that is the closest from a provided zone and
     /// returns its id.
     pub fn closest_zone(&self, from: &DBEntry<ZoneEntry>) -> Option<u64> {
+        let minutes_to = from.contents.current_minutes_to();
         self.zone
             .iter()
             .filter(|z| {
-                if from.contents.minutes_to.contains_key(z) {
+                if minutes_to.contains_key(z) {
                     true
                 } else {
                     error!(
@@ -102,7 +112,7 @@ impl ChallengeEntry {
                     false
                 }
             })
-            .min_by_key(|z| from.contents.minutes_to.get(z).unwrap())
+            .min_by_key(|z| minutes_to.get(z).unwrap())
             .cloned()
     }
 
@@ -131,6 +141,7 @@ impl ChallengeEntry {
             description: self.description.clone(),
             random_place: self.random_place,
             place: self.place.clone(),
+            place_coordinates: self.place_coordinates,
             comment: self.comment.clone(),
             kaffskala: self.kaffskala,
             grade: self.grade,
@@ -177,6 +188,110 @@ impl ChallengeEntry {
         })
     }
 
+    /// Converts the challenge into an `InputChallenge`, which refers to sets, zones and sectors
+    /// only by their ids
+    pub fn to_input(&self, id: u64) -> InputChallenge {
+        InputChallenge {
+            kind: self.kind,
+            sets: self.sets.clone(),
+            status: self.status,
+            title: self.title.clone(),
+            description: self.description.clone(),
+            random_place: self.random_place,
+            place: self.place.clone(),
+            place_coordinates: self.place_coordinates,
+            comment: self.comment.clone(),
+            kaffskala: self.kaffskala,
+            grade: self.grade,
+            zone: self.zone.clone(),
+            sectors: self.sector.clone(),
+            bias_sat: self.bias_sat,
+            bias_sun: self.bias_sun,
+            walking_time: self.walking_time,
+            stationary_time: self.stationary_time,
+            additional_points: self.additional_points,
+            repetitions: self.repetitions.clone(),
+            points_per_rep: self.points_per_rep,
+            station_distance: self.station_distance,
+            time_to_hb: self.time_to_hb,
+            departures: self.departures,
+            dead_end: self.dead_end,
+            no_disembark: self.no_disembark,
+            fixed: self.fixed,
+            in_perimeter_override: self.in_perimeter_override,
+            translated_titles: self.translated_titles.clone(),
+            translated_descriptions: self.translated_descriptions.clone(),
+            action: self.action.clone(),
+            id: Some(id),
+        }
+    }
+
+    /// Checks whether the challenge with the given id matches a filter, except for its text,
+    /// which has to be checked with `search_score`
+    pub fn matches(&self, id: u64, filter: &ChallengeFilter) -> bool {
+        fn any_of(wanted: &[u64], present: &[u64]) -> bool {
+            wanted.is_empty() || wanted.iter().any(|w| present.contains(w))
+        }
+        (filter.ids.is_empty() || filter.ids.contains(&id))
+            && (filter.kinds.is_empty() || filter.kinds.contains(&self.kind))
+            && (filter.statuses.is_empty() || filter.statuses.contains(&self.status))
+            && any_of(&filter.sets, &self.sets)
+            && any_of(&filter.zones, &self.zone)
+            && any_of(&filter.sectors, &self.sector)
+            && filter
+                .kaffskala
+                .as_ref()
+                .is_none_or(|range| self.kaffskala.is_some_and(|k| range.contains(&k)))
+            && filter
+                .grade
+                .as_ref()
+                .is_none_or(|range| self.grade.is_some_and(|g| range.contains(&g)))
+    }
+
+    /// Rates how well the challenge matches a text search, from 0 to 1. Every word of the query
+    /// has to be part of or similar to a word in the title or description, in any language.
+    /// Returns `None` if the challenge doesn't match.
+    pub fn search_score(&self, query: &str) -> Option<f64> {
+        fn words(text: &str) -> impl Iterator<Item = &str> {
+            text.split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+        }
+        let texts: Vec<String> = self
+            .title
+            .iter()
+            .chain(&self.description)
+            .chain(self.translated_titles.values())
+            .chain(self.translated_descriptions.values())
+            .map(|t| t.to_lowercase())
+            .collect();
+        let challenge_words: Vec<&str> = texts.iter().flat_map(|t| words(t)).collect();
+        let query = query.to_lowercase();
+        let mut total = 0.0;
+        let mut terms = 0;
+        for term in words(&query) {
+            let score = challenge_words
+                .iter()
+                .map(|w| {
+                    if w.contains(term) {
+                        1.0
+                    } else {
+                        strcmp(w, term)
+                    }
+                })
+                .fold(0.0, f64::max);
+            if score < SEARCH_THRESHOLD {
+                return None;
+            }
+            total += score;
+            terms += 1;
+        }
+        Some(if terms == 0 {
+            1.0
+        } else {
+            total / terms as f64
+        })
+    }
+
     /// Generates points for and returns an open challenge
     #[allow(clippy::too_many_arguments)]
     pub fn challenge(
@@ -300,7 +415,7 @@ impl ChallengeEntry {
             points += (config.travel_minutes_multiplier
                 * ((*current_zone
                     .contents
-                    .minutes_to
+                    .current_minutes_to()
                     .get(&z.id)
                     .unwrap_or_else(|| {
                         error!(
@@ -330,11 +445,9 @@ impl ChallengeEntry {
                     * config.zkaff_departures_multiplier) as i64;
                 trace!("adding departures points => {points}")
             }
-            Zoneable => {
-                if zone_zoneables {
-                    points += config.points_for_zoneable as i64;
-                    trace!("adding points for zoned zoneable => {points}")
-                }
+            Zoneable if zone_zoneables => {
+                points += config.points_for_zoneable as i64;
+                trace!("adding points for zoned zoneable => {points}")
             }
             _ => (),
         }
@@ -413,26 +526,42 @@ impl ChallengeEntry {
         if let Some(desc_override) = self.description.clone() {
             description = desc_override;
         }
-        if self.no_disembark {
-            title = format!("🛤️ {}", title);
-        }
-        if let Some(z) = &zone {
-            if matches!(self.kind, Zoneable) && zone_zoneables {
-                description.push_str(
-                    format!(
-                        " ⚠️ Damit ihr Pünkt überchömed, mached das i de Zone {}.",
-                        z.contents.zone
-                    )
-                    .as_str(),
-                );
+        // the translations go through the same substitutions as the default texts
+        let render_title = |mut title: String| {
+            if self.no_disembark {
+                title = format!("🛤️ {}", title);
             }
-            title = title.replace("%z", format!("{}", z.contents.zone).as_str());
-            description = description.replace("%z", format!("{}", z.contents.zone).as_str());
-            title = title.replace("%s", format!("{}", z.contents.zone).as_str());
-            description = description.replace("%s", format!("{}", z.contents.zone).as_str());
-        }
-        title = title.replace("%r", format!("{}", reps).as_str());
-        description = description.replace("%r", format!("{}", reps).as_str());
+            if let Some(z) = &zone {
+                title = title.replace("%z", format!("{}", z.contents.zone).as_str());
+                title = title.replace("%s", format!("{}", z.contents.zone).as_str());
+            }
+            title.replace("%r", format!("{}", reps).as_str())
+        };
+        let render_description = |mut description: String, language: Option<&str>| {
+            if let Some(z) = &zone {
+                if matches!(self.kind, Zoneable) && zone_zoneables {
+                    description.push_str(&zone_warning(language, z.contents.zone));
+                }
+                description = description.replace("%z", format!("{}", z.contents.zone).as_str());
+                description = description.replace("%s", format!("{}", z.contents.zone).as_str());
+            }
+            description.replace("%r", format!("{}", reps).as_str())
+        };
+        let title = render_title(title);
+        let description = render_description(description, None);
+        let translated_titles = self
+            .translated_titles
+            .iter()
+            .map(|(language, t)| (language.clone(), render_title(t.clone())))
+            .collect();
+        let translated_descriptions = self
+            .translated_descriptions
+            .iter()
+            .map(|(language, d)| {
+                let description = render_description(d.clone(), Some(language));
+                (language.clone(), description)
+            })
+            .collect();
 
         // generating challenge action
         let mut action = None;
@@ -458,15 +587,105 @@ impl ChallengeEntry {
         InOpenChallenge {
             title,
             description,
+            translated_titles,
+            translated_descriptions,
             points: points as u64,
             action,
             zone: zone.map(|z| z.id),
             sector: self.sector.first().cloned(), // just take the first, don't see better option rn
+            // a random place is somewhere else entirely
+            place_coordinates: self
+                .random_place
+                .is_none()
+                .then_some(self.place_coordinates)
+                .flatten(),
             id,
         }
     }
 }
 
+/// A version of a challenge, saved whenever the challenge is changed
+#[derive(Debug, Clone, Collection, Serialize, Deserialize)]
+#[collection(name = "challenge revision")]
+pub struct ChallengeRevisionEntry {
+    pub challenge_id: u64,
+    /// counts the revisions of the challenge, starting at 0
+    pub revision: usize,
+    /// the player that made the change, `None` for the organisers
+    pub author: Option<u64>,
+    pub time: chrono::DateTime<chrono::Local>,
+    pub comment: Option<String>,
+    /// the challenge as it was after the change
+    pub challenge: ChallengeEntry,
+}
+
+impl ChallengeRevisionEntry {
+    /// Converts the engine-internal `ChallengeRevisionEntry` type into a sendable truinlag
+    /// `ChallengeRevision` type
+    pub fn to_sendable(&self) -> ChallengeRevision {
+        ChallengeRevision {
+            revision: self.revision,
+            author: self.author,
+            time: self.time.timestamp(),
+            comment: self.comment.clone(),
+            challenge: self.challenge.to_input(self.challenge_id),
+        }
+    }
+}
+
+/// The warning that is appended to the description of zoneable challenges, which only give points
+/// in their zone. Languages without a translation of the warning get the Swiss German one.
+fn zone_warning(language: Option<&str>, zone: u64) -> String {
+    match language.and_then(|l| l.split('_').next()) {
+        Some("fr") => format!(
+            " ⚠️ Pour obtenir les points, faites-le dans la zone {}.",
+            zone
+        ),
+        Some("it") => format!(" ⚠️ Per ottenere i punti, fatelo nella zona {}.", zone),
+        Some("en") => format!(" ⚠️ To get the points, do this in zone {}.", zone),
+        _ => format!(
+            " ⚠️ Damit ihr Pünkt überchömed, mached das i de Zone {}.",
+            zone
+        ),
+    }
+}
+
+/// Checks a change of a challenge against the review rules, `old` is `None` for new challenges.
+/// Only reviewers may approve challenges or mark them as glorious and rejecting a challenge needs
+/// a comment. If anyone else changes an approved challenge, it goes back to `Edited` to be
+/// reviewed again.
+pub fn review(
+    old: Option<&ChallengeEntry>,
+    new: &mut ChallengeEntry,
+    reviewer: bool,
+    comment: &Option<String>,
+) -> Result<(), commands::Error> {
+    use ChallengeStatus::*;
+    match old {
+        Some(old) if old.status == new.status => {
+            if matches!(old.status, Approved | Glorious) && !reviewer {
+                new.status = Edited;
+            }
+        }
+        // the status of the challenge is new or changed
+        _ => match new.status {
+            Approved | Glorious if !reviewer => {
+                return Err(commands::Error::TextError(format!(
+                    "only reviewers can set challenges to {:?}",
+                    new.status
+                )));
+            }
+            Rejected if comment.as_ref().is_none_or(|c| c.trim().is_empty()) => {
+                return Err(commands::Error::BadData(
+                    "rejecting a challenge needs a comment".into(),
+                ));
+            }
+            _ => (),
+        },
+    }
+    Ok(())
+}
+
 impl From<InputChallenge> for ChallengeEntry {
     fn from(v: InputChallenge) -> Self {
         ChallengeEntry {
@@ -477,6 +696,7 @@ impl From<InputChallenge> for ChallengeEntry {
             description: v.description,
             random_place: v.random_place,
             place: v.place,
+            place_coordinates: v.place_coordinates,
             comment: v.comment,
             kaffskala: v.kaffskala,
             grade: v.grade,
@@ -598,10 +818,18 @@ pub struct InOpenChallenge {
     pub id: u64,
     pub title: String,
     pub description: String,
+    /// the title in other languages, by language
+    #[serde(default)]
+    pub translated_titles: HashMap<String, String>,
+    /// the description in other languages, by language
+    #[serde(default)]
+    pub translated_descriptions: HashMap<String, String>,
     pub points: u64,
     pub action: Option<ChallengeAction>,
     pub zone: Option<u64>, // id for ZoneEntry collection in db
     pub sector: Option<u64>,
+    #[serde(default)]
+    pub place_coordinates: Option<(f32, f32)>, // latitude and longitude
 }
 
 impl InOpenChallenge {
@@ -620,15 +848,108 @@ impl InOpenChallenge {
         }
     }
 
+    /// Checks whether a team at the given location is where the challenge has to be completed.
+    ///
+    /// The place coordinates are checked if there are any, otherwise the outlines of the
+    /// challenge's zone and sector. A location older than `max_location_age` counts as missing.
+    pub fn verify_location(
+        &self,
+        location: Option<&DetailedLocation>,
+        context: &SessionContext,
+    ) -> CompletionVerification {
+        let config = &context.config;
+        let zone = self
+            .zone
+            .and_then(|id| context.engine_context.zone_db.get(id).ok())
+            .filter(|z| z.contents.boundary.is_some());
+        let sector = self
+            .sector
+            .and_then(|id| context.engine_context.sector_db.get(id).ok())
+            .filter(|s| s.contents.boundary.is_some());
+        if self.place_coordinates.is_none() && zone.is_none() && sector.is_none() {
+            return CompletionVerification::Unchecked;
+        }
+        let now = chrono::Local::now().timestamp();
+        let Some(location) =
+            location.filter(|l| now - l.timestamp <= config.max_location_age.num_seconds())
+        else {
+            return CompletionVerification::NoLocation;
+        };
+        if let Some((latitude, longitude)) = self.place_coordinates {
+            let place = MinimalLocation {
+                latitude,
+                longitude,
+                timestamp: 0,
+            };
+            let distance = place.distance_to(&location.clone().into());
+            let allowed = config.completion_radius
+                + (location.accuracy as f64).min(config.catch_accuracy_margin);
+            return if distance <= allowed {
+                CompletionVerification::Verified
+            } else {
+                CompletionVerification::Failed(format!(
+                    "the team is {:.0} m away from the place, but at most {:.0} m are allowed",
+                    distance, allowed
+                ))
+            };
+        }
+        use geo::Contains;
+        let point = geo::Point::new(location.longitude as f64, location.latitude as f64);
+        let outside_zone = zone.filter(|z| {
+            !z.contents
+                .boundary
+                .as_ref()
+                .is_some_and(|b| b.contains(&point))
+        });
+        let outside_sector = sector.filter(|s| {
+            !s.contents
+                .boundary
+                .as_ref()
+                .is_some_and(|b| b.contains(&point))
+        });
+        match (outside_zone, outside_sector) {
+            (Some(zone), _) => CompletionVerification::Failed(format!(
+                "the team is not in zone {}",
+                zone.contents.zone
+            )),
+            (_, Some(sector)) => CompletionVerification::Failed(format!(
+                "the team is not in sector {}",
+                sector.contents.name
+            )),
+            (None, None) => CompletionVerification::Verified,
+        }
+    }
+
     /// Converts the engine-internal `InOpenChallenge` type into a sendable truinlag `Challenge` type
     pub fn to_sendable(&self) -> libtruinlag::Challenge {
+        self.to_localised_sendable(None)
+    }
+
+    /// Like `to_sendable`, but with the title and description in the given language where there
+    /// is a translation
+    pub fn to_localised_sendable(&self, language: Option<&str>) -> libtruinlag::Challenge {
+        let translated = |translations: &HashMap<String, String>, default: &String| {
+            language
+                .and_then(|l| translations.get(l))
+                .unwrap_or(default)
+                .clone()
+        };
         libtruinlag::Challenge {
-            title: self.title.clone(),
+            title: translated(&self.translated_titles, &self.title),
             points: self.points,
-            description: self.description.clone(),
+            description: translated(&self.translated_descriptions, &self.description),
             id: self.id,
         }
     }
+
+    /// The sendable challenge in every language it has a title or description in, by language
+    pub fn translations(&self) -> HashMap<String, libtruinlag::Challenge> {
+        self.translated_titles
+            .keys()
+            .chain(self.translated_descriptions.keys())
+            .map(|language| (language.clone(), self.to_localised_sendable(Some(language))))
+            .collect()
+    }
 }
 
 impl PartialEq for InOpenChallenge {
@@ -0,0 +1,134 @@
+use crate::TimerHook;
+use libtruinlag::{Announcement, Message, MessageScope};
+use serde::{Deserialize, Serialize};
+
+/// The number of messages a session keeps, older ones are dropped
+pub const MAX_MESSAGES: usize = 1000;
+
+/// The number of announcements a session keeps, older delivered ones are dropped
+pub const MAX_ANNOUNCEMENTS: usize = 1000;
+
+/// Adjusts the index of a team to the removal of the team at `removed`. Returns `None` if it is
+/// the removed team itself.
+fn shift_team(team: usize, removed: usize) -> Option<usize> {
+    match team.cmp(&removed) {
+        std::cmp::Ordering::Less => Some(team),
+        std::cmp::Ordering::Equal => None,
+        std::cmp::Ordering::Greater => Some(team - 1),
+    }
+}
+
+/// Adjusts a scope to the removal of the team at `removed`. Things addressed to the removed team
+/// are left to the organisers.
+fn shift_scope(scope: MessageScope, removed: usize) -> MessageScope {
+    match scope {
+        MessageScope::Team(team) => {
+            shift_team(team, removed).map_or(MessageScope::Organisers, MessageScope::Team)
+        }
+        other => other,
+    }
+}
+
+/// The representation of a chat message in the db
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEntry {
+    pub author: Option<u64>,
+    pub team: Option<usize>,
+    pub scope: MessageScope,
+    /// The teams the message was delivered to. They are determined when the message is sent, so
+    /// that e.g. a message to the catchers doesn't show up for teams that only become catchers
+    /// later on.
+    pub recipients: Vec<usize>,
+    pub text: String,
+    pub pictures: Vec<u64>,
+    pub time: chrono::DateTime<chrono::Local>,
+}
+
+impl MessageEntry {
+    /// Converts the engine-internal message to a truinlag message
+    pub fn to_sendable(&self, id: usize) -> Message {
+        Message {
+            scope: self.scope,
+            text: self.text.clone(),
+            picture_ids: self.pictures.clone(),
+            author: self.author,
+            team: self.team,
+            time: self.time.timestamp(),
+            id,
+        }
+    }
+
+    /// Whether the given team is allowed to read the message. `None` stands for the organisers,
+    /// who can read all messages.
+    pub fn is_visible_to(&self, team: Option<usize>) -> bool {
+        match team {
+            None => true,
+            Some(team) => self.recipients.contains(&team),
+        }
+    }
+
+    /// Keeps the team indices pointing at the right teams after the team at `removed` is removed
+    pub fn team_removed(&mut self, removed: usize) {
+        self.team = self.team.and_then(|t| shift_team(t, removed));
+        self.scope = shift_scope(self.scope, removed);
+        self.recipients = self
+            .recipients
+            .iter()
+            .filter_map(|t| shift_team(*t, removed))
+            .collect();
+    }
+}
+
+/// The representation of an organiser announcement in the db
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementEntry {
+    pub target: MessageScope,
+    pub text: String,
+    pub time: chrono::DateTime<chrono::Local>,
+    /// The alarm that delivers the announcement. It is `Some` until the announcement is
+    /// delivered.
+    pub delivery: Option<TimerHook>,
+    pub delivered: bool,
+    /// The teams the announcement was delivered to, like `MessageEntry::recipients`
+    pub recipients: Vec<usize>,
+    pub acknowledged_by: Vec<u64>,
+}
+
+impl AnnouncementEntry {
+    /// Converts the engine-internal announcement to a truinlag announcement
+    pub fn to_sendable(&self, id: usize) -> Announcement {
+        Announcement {
+            target: self.target,
+            text: self.text.clone(),
+            time: self.time.timestamp(),
+            delivered: self.delivered,
+            acknowledged_by: self.acknowledged_by.clone(),
+            id,
+        }
+    }
+
+    /// Whether the given team is allowed to read the announcement. Announcements to everyone
+    /// are visible to all teams once delivered, even ones that were created afterwards, so that
+    /// late joiners don't miss anything. `None` stands for the organisers, who can also see
+    /// announcements that haven't been delivered yet.
+    pub fn is_visible_to(&self, team: Option<usize>) -> bool {
+        match team {
+            None => true,
+            Some(_) if !self.delivered => false,
+            Some(team) => match self.target {
+                MessageScope::All => true,
+                _ => self.recipients.contains(&team),
+            },
+        }
+    }
+
+    /// Like `MessageEntry::team_removed`
+    pub fn team_removed(&mut self, removed: usize) {
+        self.target = shift_scope(self.target, removed);
+        self.recipients = self
+            .recipients
+            .iter()
+            .filter_map(|t| shift_team(*t, removed))
+            .collect();
+    }
+}
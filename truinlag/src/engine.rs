@@ -1,12 +1,15 @@
 use crate::{
     ClonedDBEntry, DBMirror, EngineContext, EngineSchema, MutDBEntry, PastGameEntry, PictureEntry,
-    PlayerEntry, SectorEntry, SessionContext, TimerTracker, ZoneEntry,
-    challenge::{ChallengeEntry, ChallengeSetEntry},
+    PlayerEntry, SectorEntry, SessionContext, StationEntry, TimerTracker, ZoneEntry,
+    archive::{self, DatabaseArchive},
+    challenge::{self, ChallengeEntry, ChallengeRevisionEntry, ChallengeSetEntry},
+    export, location, migration,
     runtime::{
         InternEngineCommand, InternEngineResponse, InternEngineResponsePackage,
         InternEngineResponseResult, RuntimeRequest,
     },
     session::Session,
+    stats,
 };
 use bonsaidb::{
     core::{
@@ -25,6 +28,7 @@ use libtruinlag::{
     *,
 };
 use log::{debug, error, info, trace, warn};
+use partially::Partial;
 use std::{
     collections::HashMap,
     path::Path,
@@ -84,6 +88,62 @@ where
     Ok(())
 }
 
+/// Everything that refers to an entry which is about to be removed
+#[derive(Debug, Default)]
+struct References {
+    /// descriptions of all entries that refer to it
+    all: Vec<String>,
+    /// descriptions of the entries whose reference can't simply be dropped, like a session that
+    /// starts its games in a zone
+    required: Vec<String>,
+}
+
+impl References {
+    /// Checks whether the references allow removing the entry with the given strategy.
+    fn check(&self, strategy: ReferenceStrategy) -> Result<(), commands::Error> {
+        match strategy {
+            ReferenceStrategy::Refuse if !self.all.is_empty() => Err(Referenced(self.all.clone())),
+            ReferenceStrategy::Cascade if !self.required.is_empty() => {
+                Err(Referenced(self.required.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Removes `id` from a list of references and adds the `replacement` instead, if there is one.
+fn replace_reference(ids: &mut Vec<u64>, id: u64, replacement: Option<u64>) {
+    let len = ids.len();
+    ids.retain(|i| *i != id);
+    if ids.len() < len
+        && let Some(replacement) = replacement
+        && !ids.contains(&replacement)
+    {
+        ids.push(replacement);
+    }
+}
+
+/// Describes a challenge for the list of references in an error
+fn describe_challenge(id: u64, challenge: &ChallengeEntry) -> String {
+    format!(
+        "challenge {} ({})",
+        id,
+        challenge.title.as_deref().unwrap_or("untitled")
+    )
+}
+
+/// Maps the id of each challenge to the ids of its revisions
+fn index_revisions(revisions: &DBMirror<ChallengeRevisionEntry>) -> HashMap<u64, Vec<u64>> {
+    let mut index: HashMap<u64, Vec<u64>> = HashMap::new();
+    for revision in revisions.get_all() {
+        index
+            .entry(revision.contents.challenge_id)
+            .or_default()
+            .push(revision.id);
+    }
+    index
+}
+
 /// The engine is the core of truinlag that handles all requests and keeps tabs on the database.
 ///
 /// # In the abstract
@@ -110,13 +170,22 @@ pub struct Engine {
 
     sessions: DBMirror<Session>,
     challenges: DBMirror<ChallengeEntry>,
+    challenge_revisions: DBMirror<ChallengeRevisionEntry>,
     challenge_sets: DBMirror<ChallengeSetEntry>,
     zones: DBMirror<ZoneEntry>,
     sectors: DBMirror<SectorEntry>,
+    stations: DBMirror<StationEntry>,
     players: DBMirror<PlayerEntry>,
     past_games: DBMirror<PastGameEntry>,
     pictures: DBMirror<PictureEntry>,
 
+    // Player stats are computed from all past games, which is slow-ish, so they are cached until a
+    // new past game is added.
+    player_stats: Option<HashMap<u64, PlayerStats>>,
+    // The ids of the revisions of each challenge, so that finding them doesn't need a search
+    // through all revisions.
+    revision_index: HashMap<u64, Vec<u64>>,
+
     // pictures: Vec<u64>,
     autosave_in_progress: Arc<AtomicBool>,
     autosave_done: Arc<Notify>,
@@ -152,6 +221,9 @@ impl Engine {
         .create_database::<EngineSchema>("engine", true)
         .unwrap();
 
+        info!("Engine: checking database schema version...");
+        migration::migrate(&db).expect("database migration failed");
+
         // let pictures = PictureEntry::all(&db)
         //     .query() // this is dogshit but I don't know what else to do
         //     .unwrap()
@@ -161,12 +233,17 @@ impl Engine {
 
         info!("Engine: loading challenges...");
         let challenges = DBMirror::from_db(&db);
+        info!("Engine: loading challenge revisions...");
+        let challenge_revisions = DBMirror::from_db(&db);
+        let revision_index = index_revisions(&challenge_revisions);
         info!("Engine: loading challenge sets...");
         let challenge_sets = DBMirror::from_db(&db);
         info!("Engine: loading zones...");
         let zones = DBMirror::from_db(&db);
         info!("Engine: loading sectors...");
         let sectors = DBMirror::from_db(&db);
+        info!("Engine: loading stations...");
+        let stations = DBMirror::from_db(&db);
         info!("Engine: loading sessions...");
         let sessions: DBMirror<Session> = DBMirror::from_db(&db);
         info!("Engine: loading players...");
@@ -207,13 +284,17 @@ impl Engine {
             db,
             changes_since_save: false,
             challenges,
+            challenge_revisions,
             challenge_sets,
             zones,
             sectors,
+            stations,
             sessions,
             players,
             past_games,
             pictures,
+            player_stats: None,
+            revision_index,
             autosave_in_progress: Arc::new(AtomicBool::new(false)),
             autosave_done: Arc::new(Notify::new()),
             timer_tracker: TimerTracker::new(),
@@ -285,6 +366,7 @@ impl Engine {
                     picture_db: &mut self.pictures,
                     timer_tracker: &mut self.timer_tracker,
                     sector_db: &mut self.sectors,
+                    player_stats: &mut self.player_stats,
                 };
                 match self.sessions.get_mut(session_id) {
                     Err(_) => Success.into(), // = do nothing
@@ -294,6 +376,38 @@ impl Engine {
                 }
             }
 
+            InternEngineCommand::DeliverAnnouncement {
+                session_id,
+                announcement_id,
+            } => {
+                self.changes_since_save = true;
+                match self.sessions.get_mut(session_id) {
+                    Err(_) => Success.into(), // = do nothing
+                    Ok(session) => session
+                        .contents
+                        .deliver_announcement(announcement_id, session_id),
+                }
+            }
+
+            InternEngineCommand::CatchConfirmationTimedOut {
+                session_id,
+                proposal_id,
+            } => {
+                self.changes_since_save = true;
+                match self.get_contexed_session(session_id) {
+                    Err(_) => Success.into(), // = do nothing
+                    Ok((mut context, session)) => session
+                        .contents
+                        .catch_confirmation_timed_out(proposal_id, &mut context),
+                }
+            }
+
+            InternEngineCommand::ExportedDatabase(json) => match json {
+                Ok(json) => SendDatabase(json),
+                Err(err) => Error(err),
+            }
+            .into(),
+
             InternEngineCommand::UploadedImages(pictures_added) => {
                 UploadedPictures(pictures_added).into()
             }
@@ -348,6 +462,10 @@ impl Engine {
                     self.challenges.clear_pending_deletions();
                     let challenge_deletions = self.challenges.extract_deletions();
 
+                    let challenge_revision_changes = self.challenge_revisions.extract_changes();
+                    self.challenge_revisions.clear_pending_deletions();
+                    let challenge_revision_deletions = self.challenge_revisions.extract_deletions();
+
                     let challenge_set_changes = self.challenge_sets.extract_changes();
                     self.challenge_sets.clear_pending_deletions();
                     let challenge_set_deletions = self.challenge_sets.extract_deletions();
@@ -360,6 +478,10 @@ impl Engine {
                     self.sectors.clear_pending_deletions();
                     let sector_deletions = self.sectors.extract_deletions();
 
+                    let station_changes = self.stations.extract_changes();
+                    self.stations.clear_pending_deletions();
+                    let station_deletions = self.stations.extract_deletions();
+
                     let picture_changes = self.pictures.extract_changes();
                     self.pictures.clear_pending_deletions();
                     let picture_deletions = self.pictures.extract_deletions();
@@ -399,6 +521,18 @@ impl Engine {
                                 )
                                 .await
                                 .unwrap();
+                                vec_overwrite_in_transaction(
+                                    challenge_revision_changes,
+                                    &mut transaction,
+                                )
+                                .unwrap();
+                                vec_delete_in_transaction::<ChallengeRevisionEntry>(
+                                    challenge_revision_deletions,
+                                    &mut transaction,
+                                    &db,
+                                )
+                                .await
+                                .unwrap();
                                 vec_overwrite_in_transaction(
                                     challenge_set_changes,
                                     &mut transaction,
@@ -429,6 +563,15 @@ impl Engine {
                                 )
                                 .await
                                 .unwrap();
+                                vec_overwrite_in_transaction(station_changes, &mut transaction)
+                                    .unwrap();
+                                vec_delete_in_transaction::<StationEntry>(
+                                    station_deletions,
+                                    &mut transaction,
+                                    &db,
+                                )
+                                .await
+                                .unwrap();
                                 vec_overwrite_in_transaction(picture_changes, &mut transaction)
                                     .unwrap();
                                 vec_delete_in_transaction::<PictureEntry>(
@@ -517,6 +660,7 @@ impl Engine {
                     picture_db: &mut self.pictures,
                     timer_tracker: &mut self.timer_tracker,
                     sector_db: &mut self.sectors,
+                    player_stats: &mut self.player_stats,
                 },
                 session_id,
             ),
@@ -552,7 +696,9 @@ impl Engine {
             mongus,
             s_bahn_zone,
             minutes_to: HashMap::new(),
+            travel_bands: Vec::new(),
             sectors: Vec::new(),
+            boundary: None,
         });
         Success.into()
     }
@@ -564,11 +710,220 @@ impl Engine {
         minutes: u64,
     ) -> InternEngineResponseResult {
         let _ = self.zones.get(to_zone)?;
-        self.zones
-            .get_mut(from_zone)?
-            .contents
-            .minutes_to
-            .insert(to_zone, minutes);
+        let zone = &mut self.zones.get_mut(from_zone)?.contents;
+        zone.minutes_to.insert(to_zone, minutes);
+        // bands replace the whole map, so they need every zone, but keep their own durations
+        for band in &mut zone.travel_bands {
+            band.minutes_to.entry(to_zone).or_insert(minutes);
+        }
+        Ok(Success.into())
+    }
+
+    /// Sets the travel duration between two zones for a part of the day, adding a new travel band
+    /// if there is none for exactly that part of the day yet. New bands start out with the
+    /// durations of `minutes_to`.
+    fn add_timed_minutes_to(
+        &mut self,
+        from_zone: u64,
+        to_zone: u64,
+        from: chrono::NaiveTime,
+        until: chrono::NaiveTime,
+        minutes: u64,
+    ) -> InternEngineResponseResult {
+        let _ = self.zones.get(to_zone)?;
+        let zone = self.zones.get_mut(from_zone)?;
+        let base = &zone.contents.minutes_to;
+        let bands = &mut zone.contents.travel_bands;
+        let band = match bands
+            .iter()
+            .position(|b| b.from == from && b.until == until)
+        {
+            Some(index) => &mut bands[index],
+            None => {
+                bands.push(TravelBand {
+                    from,
+                    until,
+                    minutes_to: base.clone(),
+                });
+                bands.last_mut().expect("just pushed")
+            }
+        };
+        band.minutes_to.insert(to_zone, minutes);
+        Ok(Success.into())
+    }
+
+    /// Changes the given properties of a zone. Sessions refer to zones by their number, so if it
+    /// changes, the sessions that start in the zone or converge to it follow along.
+    #[allow(clippy::too_many_arguments)]
+    fn update_zone(
+        &mut self,
+        zone_id: u64,
+        zone: Option<u64>,
+        num_conn_zones: Option<u64>,
+        num_connections: Option<u64>,
+        train_through: Option<bool>,
+        mongus: Option<bool>,
+        s_bahn_zone: Option<bool>,
+    ) -> InternEngineResponseResult {
+        if let Some(number) = zone
+            && self
+                .zones
+                .get_all()
+                .iter()
+                .any(|z| z.id != zone_id && z.contents.zone == number)
+        {
+            return Err(AlreadyExists);
+        }
+        let entry = &mut self.zones.get_mut(zone_id)?.contents;
+        let old_number = entry.zone;
+        entry.zone = zone.unwrap_or(entry.zone);
+        entry.num_conn_zones = num_conn_zones.unwrap_or(entry.num_conn_zones);
+        entry.num_connections = num_connections.unwrap_or(entry.num_connections);
+        entry.train_through = train_through.unwrap_or(entry.train_through);
+        entry.mongus = mongus.unwrap_or(entry.mongus);
+        entry.s_bahn_zone = s_bahn_zone.unwrap_or(entry.s_bahn_zone);
+        if let Some(number) = zone
+            && number != old_number
+        {
+            self.replace_zone_number(old_number, number)?;
+        }
+        Ok(Success.into())
+    }
+
+    /// Makes all sessions that start in or converge to the zone with number `old` use the zone
+    /// with number `new` instead.
+    fn replace_zone_number(&mut self, old: u64, new: u64) -> Result<(), commands::Error> {
+        let affected: Vec<(u64, bool, bool)> = self
+            .sessions
+            .get_all()
+            .iter()
+            .map(|s| {
+                let config = s.contents.config();
+                (s.id, config.centre_zone == old, config.start_zone == old)
+            })
+            .filter(|(_, centre, start)| *centre || *start)
+            .collect();
+        for (session_id, centre, start) in affected {
+            let config = &mut self.sessions.get_mut(session_id)?.contents.config;
+            if centre {
+                config.centre_zone = Some(new);
+            }
+            if start {
+                config.start_zone = Some(new);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a zone along with all travel times from and to it. What happens to the challenges,
+    /// stations and sessions that refer to the zone depends on the strategy.
+    fn remove_zone(
+        &mut self,
+        zone_id: u64,
+        strategy: ReferenceStrategy,
+    ) -> InternEngineResponseResult {
+        if self.sessions.any(|s| s.game.is_some()) {
+            return Err(GameInProgress);
+        }
+        let number = self.zones.get(zone_id)?.contents.zone;
+        let replacement = match strategy {
+            ReferenceStrategy::Reassign(id) if id == zone_id => {
+                return Err(BadData("a zone can't be replaced by itself".into()));
+            }
+            ReferenceStrategy::Reassign(id) => Some((id, self.zones.get(id)?.contents.zone)),
+            ReferenceStrategy::Refuse | ReferenceStrategy::Cascade => None,
+        };
+
+        let mut references = References::default();
+        let mut challenges = Vec::new();
+        for challenge in self.challenges.get_all() {
+            if challenge.contents.zone.contains(&zone_id) {
+                references
+                    .all
+                    .push(describe_challenge(challenge.id, challenge.contents));
+                challenges.push(challenge.id);
+            }
+        }
+        let mut stations = Vec::new();
+        for station in self.stations.get_all() {
+            if station.contents.zone == Some(zone_id) {
+                references
+                    .all
+                    .push(format!("station {}", station.contents.name));
+                stations.push(station.id);
+            }
+        }
+        // sessions only know the zone number, which another zone might have as well
+        let number_in_use = self
+            .zones
+            .get_all()
+            .iter()
+            .any(|z| z.id != zone_id && z.contents.zone == number);
+        if !number_in_use {
+            for session in self.sessions.get_all() {
+                let config = session.contents.config();
+                if config.centre_zone == number || config.start_zone == number {
+                    let description = format!("session {}", session.contents.name);
+                    references.all.push(description.clone());
+                    references.required.push(description);
+                }
+            }
+        }
+        references.check(strategy)?;
+
+        for challenge_id in challenges {
+            let mut entry = self.challenges.get(challenge_id)?.contents.clone();
+            replace_reference(&mut entry.zone, zone_id, replacement.map(|(id, _)| id));
+            entry.last_edit = chrono::Local::now();
+            let comment = Some(format!("zone {} was removed", number));
+            self.save_challenge(Some(challenge_id), entry, None, comment)?;
+        }
+        for station_id in stations {
+            self.stations.get_mut(station_id)?.contents.zone = replacement.map(|(id, _)| id);
+        }
+        if !number_in_use && let Some((_, new_number)) = replacement {
+            self.replace_zone_number(number, new_number)?;
+        }
+        let travelling_zones: Vec<u64> = self
+            .zones
+            .get_all()
+            .iter()
+            .filter(|z| {
+                z.contents.minutes_to.contains_key(&zone_id)
+                    || z.contents
+                        .travel_bands
+                        .iter()
+                        .any(|b| b.minutes_to.contains_key(&zone_id))
+            })
+            .map(|z| z.id)
+            .collect();
+        for id in travelling_zones {
+            let zone = &mut self.zones.get_mut(id)?.contents;
+            zone.minutes_to.remove(&zone_id);
+            for band in &mut zone.travel_bands {
+                band.minutes_to.remove(&zone_id);
+            }
+        }
+        self.zones.delete(zone_id)?;
+        Ok(Success.into())
+    }
+
+    /// Sets the boundaries of the zones in a GeoJSON `FeatureCollection`. Nothing is changed if a
+    /// zone in the collection doesn't exist.
+    fn import_zone_boundaries(&mut self, geojson: String) -> InternEngineResponseResult {
+        let boundaries = location::parse_zone_boundaries(&geojson)?;
+        let ids = boundaries
+            .iter()
+            .map(|(zone, _)| {
+                self.zones
+                    .find(|z| z.zone == *zone)
+                    .map(|z| z.id)
+                    .ok_or(NotFound(format!("zone {}", zone)))
+            })
+            .collect::<Result<Vec<u64>, commands::Error>>()?;
+        for (id, (_, boundary)) in ids.into_iter().zip(boundaries) {
+            self.zones.get_mut(id)?.contents.boundary = Some(boundary);
+        }
         Ok(Success.into())
     }
 
@@ -595,7 +950,14 @@ impl Engine {
     fn set_raw_challenge(&mut self, challenge: InputChallenge) -> InternEngineResponseResult {
         match challenge.id {
             Some(id) => {
-                *self.challenges.get_mut(id)?.contents = challenge.clone().into();
+                let mut entry: ChallengeEntry = challenge.clone().into();
+                if entry.place_coordinates.is_none() {
+                    entry.place_coordinates = self.station_coordinates(&entry.place);
+                }
+                // the organisers' tools don't ask for a comment, so the challenge's own one has to
+                // do, e.g. for rejecting it
+                let comment = Some(entry.comment.clone()).filter(|c| !c.trim().is_empty());
+                self.save_challenge(Some(id), entry, None, comment)?;
                 Ok(Success.into())
             }
             None => Err(BadData(
@@ -607,11 +969,306 @@ impl Engine {
         }
     }
 
+    /// Checks a change of a challenge against the review rules and saves it, recording the change
+    /// as a new revision. Changes without an author are made by organisers, who count as
+    /// reviewers. Challenges from before revisions were recorded get their previous state
+    /// recorded first, so that they can be reverted to it. Returns the id of the challenge, which
+    /// is new if `id` is `None`. New challenges never get the id of a deleted one, so that the
+    /// history of the deleted challenge stays its own.
+    fn save_challenge(
+        &mut self,
+        id: Option<u64>,
+        mut entry: ChallengeEntry,
+        author: Option<u64>,
+        comment: Option<String>,
+    ) -> Result<u64, commands::Error> {
+        let reviewer = match author {
+            Some(author) => self.players.get(author)?.contents.reviewer,
+            None => true,
+        };
+        let (id, revision) = match id {
+            Some(id) => {
+                let challenge = self.challenges.get_mut(id)?;
+                challenge::review(Some(challenge.contents), &mut entry, reviewer, &comment)?;
+                let revisions = self.revision_index.entry(id).or_default();
+                if revisions.is_empty() {
+                    revisions.push(self.challenge_revisions.add(ChallengeRevisionEntry {
+                        challenge_id: id,
+                        revision: 0,
+                        author: None,
+                        time: challenge.contents.last_edit,
+                        comment: Some("before revisions were recorded".into()),
+                        challenge: challenge.contents.clone(),
+                    }));
+                }
+                *challenge.contents = entry.clone();
+                (id, revisions.len())
+            }
+            None => {
+                challenge::review(None, &mut entry, reviewer, &comment)?;
+                let id = self
+                    .challenges
+                    .get_all()
+                    .iter()
+                    .map(|c| c.id)
+                    .chain(self.revision_index.keys().copied())
+                    .max()
+                    .map_or(0, |id| id + 1);
+                self.challenges.insert(id, entry.clone());
+                (id, 0)
+            }
+        };
+        let revision_id = self.challenge_revisions.add(ChallengeRevisionEntry {
+            challenge_id: id,
+            revision,
+            author,
+            time: entry.last_edit,
+            comment,
+            challenge: entry,
+        });
+        self.revision_index.entry(id).or_default().push(revision_id);
+        Ok(id)
+    }
+
+    /// Makes sure that the sets, zones and sectors of a challenge exist
+    fn check_challenge_references(
+        &self,
+        challenge: &ChallengeEntry,
+    ) -> Result<(), commands::Error> {
+        for set in &challenge.sets {
+            let _ = self.challenge_sets.get(*set)?;
+        }
+        for zone in &challenge.zone {
+            let _ = self.zones.get(*zone)?;
+        }
+        for sector in &challenge.sector {
+            let _ = self.sectors.get(*sector)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes a challenge, but keeps its history. The deletion is recorded as a last revision,
+    /// which holds the challenge as it was when it got deleted.
+    fn delete_challenge_entry(&mut self, id: u64) -> Result<(), commands::Error> {
+        let challenge = self.challenges.get(id)?.contents.clone();
+        let revisions = self.revision_index.entry(id).or_default();
+        if revisions.is_empty() {
+            revisions.push(self.challenge_revisions.add(ChallengeRevisionEntry {
+                challenge_id: id,
+                revision: 0,
+                author: None,
+                time: challenge.last_edit,
+                comment: Some("before revisions were recorded".into()),
+                challenge: challenge.clone(),
+            }));
+        }
+        let revision = revisions.len();
+        revisions.push(self.challenge_revisions.add(ChallengeRevisionEntry {
+            challenge_id: id,
+            revision,
+            author: None,
+            time: chrono::Local::now(),
+            comment: Some("deleted".into()),
+            challenge,
+        }));
+        self.challenges.delete(id)
+    }
+
+    /// The revisions of a challenge, in no particular order
+    fn revisions_of(&self, challenge_id: u64) -> impl Iterator<Item = &ChallengeRevisionEntry> {
+        self.revision_index
+            .get(&challenge_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.challenge_revisions.get(*id).ok())
+            .map(|r| r.contents)
+    }
+
     fn delete_all_challenges(&mut self) -> InternEngineResponsePackage {
-        self.challenges.delete_all();
+        let ids: Vec<u64> = self.challenges.get_all().iter().map(|c| c.id).collect();
+        for id in ids {
+            if let Err(err) = self.delete_challenge_entry(id) {
+                error!("Engine: couldn't delete challenge {}: {}", id, err);
+            }
+        }
         Success.into()
     }
 
+    fn delete_challenge(&mut self, id: u64) -> InternEngineResponseResult {
+        self.delete_challenge_entry(id)?;
+        Ok(Success.into())
+    }
+
+    /// Changes a challenge on behalf of a player, who has to follow the review rules
+    fn edit_challenge(
+        &mut self,
+        challenge: InputChallenge,
+        author: u64,
+        comment: Option<String>,
+    ) -> InternEngineResponseResult {
+        let id = challenge
+            .id
+            .ok_or(BadData("the supplied challenge doesn't have an id".into()))?;
+        let mut entry: ChallengeEntry = challenge.into();
+        if entry.place_coordinates.is_none() {
+            entry.place_coordinates = self.station_coordinates(&entry.place);
+        }
+        self.check_challenge_references(&entry)?;
+        self.save_challenge(Some(id), entry, Some(author), comment)?;
+        Ok(Success.into())
+    }
+
+    /// Searches the challenges and sends a page of the results as `InputChallenge`s, which are a
+    /// lot smaller than fully expanded `RawChallenge`s. The results of a text search are sorted by
+    /// how well they match, all others by id.
+    fn query_challenges(
+        &self,
+        filter: ChallengeFilter,
+        offset: usize,
+        limit: usize,
+    ) -> InternEngineResponsePackage {
+        let mut results: Vec<_> = self
+            .challenges
+            .get_all()
+            .into_iter()
+            .filter(|c| c.contents.matches(c.id, &filter))
+            .filter_map(|c| match &filter.text {
+                Some(text) => c.contents.search_score(text).map(|score| (score, c)),
+                None => Some((0.0, c)),
+            })
+            .collect();
+        results.sort_by(|a, b| b.0.total_cmp(&a.0));
+        SendChallengePage {
+            total: results.len(),
+            challenges: results
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(|(_, c)| c.contents.to_input(c.id))
+                .collect(),
+        }
+        .into()
+    }
+
+    /// Sends all revisions of a challenge. Deleted challenges keep their history, so this works
+    /// for them as well.
+    fn get_challenge_history(&self, challenge_id: u64) -> InternEngineResponseResult {
+        let mut revisions: Vec<ChallengeRevision> = self
+            .revisions_of(challenge_id)
+            .map(|r| r.to_sendable())
+            .collect();
+        revisions.sort_unstable_by_key(|r| r.revision);
+        if revisions.is_empty() {
+            // the challenge hasn't been changed since revisions are recorded
+            let current = self.challenges.get(challenge_id)?;
+            revisions.push(ChallengeRevision {
+                revision: 0,
+                author: None,
+                time: current.contents.last_edit.timestamp(),
+                comment: Some("before revisions were recorded".into()),
+                challenge: current.contents.to_input(challenge_id),
+            });
+        }
+        Ok(SendChallengeHistory(revisions).into())
+    }
+
+    /// Restores a challenge to one of its revisions, which is recorded as a new revision. Players
+    /// have to follow the review rules, i.e. only reviewers can restore an approved revision.
+    fn revert_challenge(
+        &mut self,
+        challenge_id: u64,
+        revision: usize,
+        author: Option<u64>,
+    ) -> InternEngineResponseResult {
+        let mut entry = self
+            .revisions_of(challenge_id)
+            .find(|r| r.revision == revision)
+            .ok_or(NotFound(format!(
+                "revision {} of challenge {}",
+                revision, challenge_id
+            )))?
+            .challenge
+            .clone();
+        entry.last_edit = chrono::Local::now();
+        self.check_challenge_references(&entry)?;
+        let comment = Some(format!("reverted to revision {}", revision));
+        self.save_challenge(Some(challenge_id), entry, author, comment)?;
+        Ok(Success.into())
+    }
+
+    /// Applies a patch to all challenges that match a filter and sends them back as they are after
+    /// the patch. With `dry_run`, the challenges are left unchanged.
+    fn patch_challenges(
+        &mut self,
+        filter: ChallengeFilter,
+        mut patch: PartialInputChallenge,
+        add_sets: Vec<u64>,
+        remove_sets: Vec<u64>,
+        comment: Option<String>,
+        dry_run: bool,
+    ) -> InternEngineResponseResult {
+        if filter.text.is_some() {
+            return Err(BadData(
+                "a text search is too fuzzy to select challenges to patch".into(),
+            ));
+        }
+        // the patch applies to many challenges, so it can't have an id
+        patch.id = None;
+        for set in patch.sets.iter().flatten().chain(&add_sets) {
+            let _ = self.challenge_sets.get(*set)?;
+        }
+        for zone in patch.zone.iter().flatten() {
+            let _ = self.zones.get(*zone)?;
+        }
+        for sector in patch.sectors.iter().flatten() {
+            let _ = self.sectors.get(*sector)?;
+        }
+
+        let mut patched = Vec::new();
+        for challenge in self.challenges.get_all() {
+            if !challenge.contents.matches(challenge.id, &filter) {
+                continue;
+            }
+            let mut input = challenge.contents.to_input(challenge.id);
+            input.apply_some(patch.clone());
+            for set in &add_sets {
+                if !input.sets.contains(set) {
+                    input.sets.push(*set);
+                }
+            }
+            input.sets.retain(|s| !remove_sets.contains(s));
+            let mut entry: ChallengeEntry = input.into();
+            if patch.place.is_some() && patch.place_coordinates.is_none() {
+                entry.place_coordinates = self.station_coordinates(&entry.place);
+            }
+            // checked here already, so that nothing is saved if any of the challenges fails it
+            challenge::review(Some(challenge.contents), &mut entry, true, &comment)?;
+            // saving unchanged challenges would bump their last edit for nothing
+            let unchanged = ChallengeEntry {
+                last_edit: challenge.contents.last_edit,
+                ..entry.clone()
+            } == *challenge.contents;
+            if !unchanged {
+                patched.push((challenge.id, entry));
+            }
+        }
+
+        let sendable = {
+            let challenge_sets = self.challenge_sets.get_all();
+            let zones = self.zones.get_all();
+            patched
+                .iter()
+                .map(|(id, entry)| entry.to_sendable(*id, &challenge_sets, &zones, &self.sectors))
+                .collect::<Result<Vec<RawChallenge>, commands::Error>>()?
+        };
+        if !dry_run {
+            for (id, entry) in patched {
+                self.save_challenge(Some(id), entry, None, comment.clone())?;
+            }
+        }
+        Ok(SendRawChallenges(sendable).into())
+    }
+
     fn get_player_by_passphrase(&self, passphrase: String) -> InternEngineResponsePackage {
         match self.players.find(|p| p.passphrase == passphrase) {
             None => Error(NotFound(format!("player with passphrase {}", passphrase))).into(),
@@ -619,10 +1276,13 @@ impl Engine {
         }
     }
 
-    fn add_raw_challenge(&mut self, challenge: InputChallenge) -> InternEngineResponsePackage {
-        let entry: ChallengeEntry = challenge.clone().into();
-        self.challenges.add(entry);
-        Success.into()
+    fn add_raw_challenge(&mut self, challenge: InputChallenge) -> InternEngineResponseResult {
+        let mut entry: ChallengeEntry = challenge.clone().into();
+        if entry.place_coordinates.is_none() {
+            entry.place_coordinates = self.station_coordinates(&entry.place);
+        }
+        self.save_challenge(None, entry, None, None)?;
+        Ok(Success.into())
     }
 
     fn add_session(&mut self, name: String, mode: Mode) -> InternEngineResponsePackage {
@@ -652,6 +1312,8 @@ impl Engine {
                 passphrase,
                 session,
                 last_location: None,
+                reviewer: false,
+                language: None,
             });
             Success.into()
         }
@@ -755,7 +1417,7 @@ impl Engine {
         match session_id {
             Some(session_id) => {
                 let (context, session) = self.get_contexed_session(session_id)?;
-                Ok(session.contents.get_state(&context))
+                Ok(session.contents.get_state(&context, None))
             }
             None => {
                 let sessions = self
@@ -775,6 +1437,12 @@ impl Engine {
         }
     }
 
+    fn get_player_state(&mut self, session_id: u64, player_id: u64) -> InternEngineResponseResult {
+        let language = self.players.get(player_id)?.contents.language.clone();
+        let (context, session) = self.get_contexed_session(session_id)?;
+        Ok(session.contents.get_state(&context, language.as_deref()))
+    }
+
     fn add_challenge_set(&mut self, name: String) -> InternEngineResponsePackage {
         if self.challenge_sets.any(|s| s.name == name) {
             Error(AlreadyExists).into()
@@ -795,6 +1463,83 @@ impl Engine {
         .into()
     }
 
+    fn rename_challenge_set(&mut self, set_id: u64, name: String) -> InternEngineResponseResult {
+        if self
+            .challenge_sets
+            .get_all()
+            .iter()
+            .any(|s| s.id != set_id && s.contents.name == name)
+        {
+            return Err(AlreadyExists);
+        }
+        self.challenge_sets.get_mut(set_id)?.contents.name = name;
+        Ok(Success.into())
+    }
+
+    /// Removes a challenge set. What happens to the challenges in the set and the sessions that
+    /// play with it depends on the strategy.
+    fn remove_challenge_set(
+        &mut self,
+        set_id: u64,
+        strategy: ReferenceStrategy,
+    ) -> InternEngineResponseResult {
+        if self.sessions.any(|s| s.game.is_some()) {
+            return Err(GameInProgress);
+        }
+        let name = self.challenge_sets.get(set_id)?.contents.name.clone();
+        let replacement = match strategy {
+            ReferenceStrategy::Reassign(id) if id == set_id => {
+                return Err(BadData(
+                    "a challenge set can't be replaced by itself".into(),
+                ));
+            }
+            ReferenceStrategy::Reassign(id) => Some(self.challenge_sets.get(id)?.id),
+            ReferenceStrategy::Refuse | ReferenceStrategy::Cascade => None,
+        };
+
+        let mut references = References::default();
+        let mut challenges = Vec::new();
+        for challenge in self.challenges.get_all() {
+            if challenge.contents.sets.contains(&set_id) {
+                references
+                    .all
+                    .push(describe_challenge(challenge.id, challenge.contents));
+                challenges.push(challenge.id);
+            }
+        }
+        let mut sessions = Vec::new();
+        for session in self.sessions.get_all() {
+            if session.contents.config().challenge_sets.contains(&set_id) {
+                references
+                    .all
+                    .push(format!("session {}", session.contents.name));
+                sessions.push(session.id);
+            }
+        }
+        references.check(strategy)?;
+
+        for challenge_id in challenges {
+            let mut entry = self.challenges.get(challenge_id)?.contents.clone();
+            replace_reference(&mut entry.sets, set_id, replacement);
+            entry.last_edit = chrono::Local::now();
+            let comment = Some(format!("challenge set {} was removed", name));
+            self.save_challenge(Some(challenge_id), entry, None, comment)?;
+        }
+        for session_id in sessions {
+            if let Some(sets) = &mut self
+                .sessions
+                .get_mut(session_id)?
+                .contents
+                .config
+                .challenge_sets
+            {
+                replace_reference(sets, set_id, replacement);
+            }
+        }
+        self.challenge_sets.delete(set_id)?;
+        Ok(Success.into())
+    }
+
     // this is code for if pictures are not copied into memory. It should be used in some capacity
     // again in the future, but for now it's out of commission.
     //     fn upload_pictures(&mut self, pictures: Vec<Picture>) -> InternEngineResponsePackage {
@@ -890,6 +1635,17 @@ impl Engine {
         Ok(Success.into())
     }
 
+    fn set_player_language(
+        &mut self,
+        player_id: u64,
+        language: Option<String>,
+    ) -> InternEngineResponseResult {
+        self.players.get_mut(player_id)?.contents.language = language
+            .map(|l| l.trim().to_lowercase())
+            .filter(|l| !l.is_empty());
+        Ok(Success.into())
+    }
+
     fn add_sector(&mut self, name: char) -> InternEngineResponseResult {
         if self.sectors.any(|s| s.name == name) {
             Err(AlreadyExists)
@@ -897,6 +1653,7 @@ impl Engine {
             self.sectors.add(SectorEntry {
                 name,
                 neighbours: Vec::new(),
+                boundary: None,
             });
             Ok(Success.into())
         }
@@ -939,21 +1696,246 @@ impl Engine {
         .into())
     }
 
-    fn remove_sector(&mut self, sector_id: u64) -> InternEngineResponseResult {
-        self.sectors.delete(sector_id)?;
-        // this is really fucking inefficient but I don't care because who will ever remove sectors
-        loop {
-            match self.challenges.find_mut(|c| c.sector.contains(&sector_id)) {
-                None => break,
-                Some(challenge) => challenge.contents.sector.retain(|s| s != &sector_id),
+    /// Sets the boundaries of the sectors in a GeoJSON `FeatureCollection`, like
+    /// `import_zone_boundaries`
+    fn import_sector_boundaries(&mut self, geojson: String) -> InternEngineResponseResult {
+        let boundaries = location::parse_sector_boundaries(&geojson)?;
+        let ids = boundaries
+            .iter()
+            .map(|(name, _)| {
+                self.sectors
+                    .find(|s| s.name == *name)
+                    .map(|s| s.id)
+                    .ok_or(NotFound(format!("sector {}", name)))
+            })
+            .collect::<Result<Vec<u64>, commands::Error>>()?;
+        for (id, (_, boundary)) in ids.into_iter().zip(boundaries) {
+            self.sectors.get_mut(id)?.contents.boundary = Some(boundary);
+        }
+        Ok(Success.into())
+    }
+
+    fn get_sector_map(&self) -> InternEngineResponsePackage {
+        SendGeoJson(export::sector_map_geojson(&self.sectors.get_all())).into()
+    }
+
+    /// Takes a snapshot of every collection as a `DatabaseArchive`, which works while games are
+    /// running. The archive is serialised in the background, so that games aren't held up by it.
+    fn export_database(&self) -> InternEngineResponsePackage {
+        let archive = DatabaseArchive {
+            version: archive::ARCHIVE_VERSION,
+            truinlag_version: env!("CARGO_PKG_VERSION").into(),
+            created: chrono::Local::now(),
+            sessions: self.sessions.entries(),
+            players: self.players.entries(),
+            challenges: self.challenges.entries(),
+            challenge_sets: self.challenge_sets.entries(),
+            challenge_revisions: self.challenge_revisions.entries(),
+            zones: self.zones.entries(),
+            sectors: self.sectors.entries(),
+            stations: self.stations.entries(),
+            past_games: self.past_games.entries(),
+            pictures: self.pictures.entries(),
+        };
+        InternEngineResponse::DelayedLoopback(tokio::task::spawn_blocking(move || {
+            InternEngineCommand::ExportedDatabase(archive.to_json())
+        }))
+        .into()
+    }
+
+    /// Replaces the whole database with the contents of a `DatabaseArchive`. This is refused while
+    /// a game is running. The timers of the current sessions are cancelled and those of the
+    /// imported ones are restarted, like on startup.
+    fn import_database(&mut self, json: String) -> InternEngineResponseResult {
+        if self.sessions.any(|s| s.game.is_some()) {
+            return Err(GameInProgress);
+        }
+        let archive = archive::DatabaseArchive::from_json(&json)?;
+        // without a running game, only announcements can have timers
+        let mut requests: Vec<RuntimeRequest> = self
+            .sessions
+            .get_all()
+            .iter()
+            .flat_map(|s| &s.contents.announcements)
+            .filter_map(|a| a.delivery.as_ref().map(|hook| hook.cancel_request()))
+            .collect();
+        info!(
+            "Engine: importing database archive from {} (truinlag {})",
+            archive.created, archive.truinlag_version
+        );
+        self.sessions.replace_all(archive.sessions);
+        self.players.replace_all(archive.players);
+        self.challenges.replace_all(archive.challenges);
+        self.challenge_sets.replace_all(archive.challenge_sets);
+        self.challenge_revisions
+            .replace_all(archive.challenge_revisions);
+        self.revision_index = index_revisions(&self.challenge_revisions);
+        self.zones.replace_all(archive.zones);
+        self.sectors.replace_all(archive.sectors);
+        self.stations.replace_all(archive.stations);
+        self.past_games.replace_all(archive.past_games);
+        self.pictures.replace_all(archive.pictures);
+        self.player_stats = None;
+        for session in self.sessions.get_all() {
+            let restored = session.contents.setup();
+            // new timers mustn't get the ids of the restored ones
+            if let Some(max_id) = restored.iter().map(|r| r.id()).max() {
+                self.timer_tracker.current_id = self.timer_tracker.current_id.max(max_id);
+            }
+            requests.extend(restored);
+        }
+        Ok(InternEngineResponsePackage {
+            response: Success.into(),
+            runtime_requests: Some(requests),
+        })
+    }
+
+    /// Replaces all stations with the given ones and fills in the place coordinates of challenges
+    /// that don't have any yet
+    fn import_stations(&mut self, stations: Vec<Station>) -> InternEngineResponsePackage {
+        self.stations.delete_all();
+        for station in &stations {
+            let zone = station.zone.and_then(|number| {
+                let zone = self.zones.find(|z| z.zone == number).map(|z| z.id);
+                if zone.is_none() {
+                    warn!(
+                        "Engine: station {} is in zone {}, which doesn't exist",
+                        station.name, number
+                    );
+                }
+                zone
+            });
+            self.stations.add(StationEntry {
+                name: station.name.clone(),
+                latitude: station.latitude,
+                longitude: station.longitude,
+                zone,
+                aliases: station.aliases.clone(),
+            });
+        }
+        let missing: Vec<(u64, (f32, f32))> = self
+            .challenges
+            .get_all()
+            .iter()
+            .filter(|c| c.contents.place_coordinates.is_none())
+            .filter_map(|c| {
+                let place = c.contents.place.as_ref()?;
+                stations
+                    .iter()
+                    .find(|s| s.is_called(place))
+                    .map(|s| (c.id, (s.latitude, s.longitude)))
+            })
+            .collect();
+        for (id, coordinates) in missing {
+            if let Ok(challenge) = self.challenges.get_mut(id) {
+                challenge.contents.place_coordinates = Some(coordinates);
+            }
+        }
+        Success.into()
+    }
+
+    fn get_stations(&self) -> InternEngineResponsePackage {
+        SendStations(
+            self.stations
+                .get_all()
+                .iter()
+                .map(|s| s.contents.to_sendable(s.id, &self.zones))
+                .collect(),
+        )
+        .into()
+    }
+
+    /// Looks up the coordinates of a challenge place in the stations
+    fn station_coordinates(&self, place: &Option<String>) -> Option<(f32, f32)> {
+        let place = place.as_ref()?;
+        self.stations
+            .get_all()
+            .iter()
+            .map(|s| s.contents.to_sendable(s.id, &self.zones))
+            .find(|s| s.is_called(place))
+            .map(|s| (s.latitude, s.longitude))
+    }
+
+    fn rename_sector(&mut self, sector_id: u64, name: char) -> InternEngineResponseResult {
+        if self
+            .sectors
+            .get_all()
+            .iter()
+            .any(|s| s.id != sector_id && s.contents.name == name)
+        {
+            return Err(AlreadyExists);
+        }
+        self.sectors.get_mut(sector_id)?.contents.name = name;
+        Ok(Success.into())
+    }
+
+    /// Removes a sector along with its neighbourhoods. What happens to the challenges in the
+    /// sector and the zones that are close to it depends on the strategy.
+    fn remove_sector(
+        &mut self,
+        sector_id: u64,
+        strategy: ReferenceStrategy,
+    ) -> InternEngineResponseResult {
+        if self.sessions.any(|s| s.game.is_some()) {
+            return Err(GameInProgress);
+        }
+        let _ = self.sectors.get(sector_id)?;
+        let replacement = match strategy {
+            ReferenceStrategy::Reassign(id) if id == sector_id => {
+                return Err(BadData("a sector can't be replaced by itself".into()));
+            }
+            ReferenceStrategy::Reassign(id) => Some(self.sectors.get(id)?.id),
+            ReferenceStrategy::Refuse | ReferenceStrategy::Cascade => None,
+        };
+
+        let mut references = References::default();
+        let mut challenges = Vec::new();
+        for challenge in self.challenges.get_all() {
+            if challenge.contents.sector.contains(&sector_id) {
+                references
+                    .all
+                    .push(describe_challenge(challenge.id, challenge.contents));
+                challenges.push(challenge.id);
             }
         }
-        loop {
-            match self.zones.find_mut(|z| z.sectors.contains(&sector_id)) {
-                None => break,
-                Some(zone) => zone.contents.sectors.retain(|s| s != &sector_id),
+        let mut zones = Vec::new();
+        for zone in self.zones.get_all() {
+            if zone.contents.sectors.contains(&sector_id) {
+                references.all.push(format!("zone {}", zone.contents.zone));
+                zones.push(zone.id);
             }
         }
+        references.check(strategy)?;
+
+        for challenge_id in challenges {
+            let mut entry = self.challenges.get(challenge_id)?.contents.clone();
+            replace_reference(&mut entry.sector, sector_id, replacement);
+            entry.last_edit = chrono::Local::now();
+            let comment = Some(format!("sector {} was removed", sector_id));
+            self.save_challenge(Some(challenge_id), entry, None, comment)?;
+        }
+        for zone_id in zones {
+            replace_reference(
+                &mut self.zones.get_mut(zone_id)?.contents.sectors,
+                sector_id,
+                replacement,
+            );
+        }
+        let neighbours: Vec<u64> = self
+            .sectors
+            .get_all()
+            .iter()
+            .filter(|s| s.contents.neighbours.contains(&sector_id))
+            .map(|s| s.id)
+            .collect();
+        for neighbour in neighbours {
+            self.sectors
+                .get_mut(neighbour)?
+                .contents
+                .neighbours
+                .retain(|n| *n != sector_id);
+        }
+        self.sectors.delete(sector_id)?;
         Ok(Success.into())
     }
 
@@ -1009,8 +1991,160 @@ impl Engine {
         .into())
     }
 
+    fn export_past_game(
+        &self,
+        past_game_id: u64,
+        format: ExportFormat,
+    ) -> InternEngineResponseResult {
+        let past_game = self.past_games.get(past_game_id)?;
+        Ok(SendPastGameExport(past_game.contents.to_map().render(format)).into())
+    }
+
+    fn get_past_game_stats(&self, past_game_id: u64) -> InternEngineResponseResult {
+        let past_game = self.past_games.get(past_game_id)?;
+        Ok(SendPastGameStats(stats::past_game_stats(past_game.id, past_game.contents)).into())
+    }
+
+    fn get_challenge_analytics(&self) -> InternEngineResponsePackage {
+        SendChallengeAnalytics(stats::challenge_analytics(
+            self.past_games.get_all().iter().map(|pg| pg.contents),
+        ))
+        .into()
+    }
+
+    /// Returns the cached player stats, computing them first if necessary
+    fn cached_player_stats(&mut self) -> &HashMap<u64, PlayerStats> {
+        self.player_stats.get_or_insert_with(|| {
+            stats::player_stats(
+                self.past_games.get_all().iter().map(|pg| pg.contents),
+                &self.zones,
+            )
+        })
+    }
+
+    fn get_player_stats(&mut self, player_id: u64) -> InternEngineResponseResult {
+        let name = self.players.get(player_id)?.contents.name.clone();
+        let mut stats = self
+            .cached_player_stats()
+            .get(&player_id)
+            .cloned()
+            .unwrap_or(PlayerStats {
+                id: player_id,
+                ..Default::default()
+            });
+        stats.name = name;
+        Ok(SendPlayerStats(stats).into())
+    }
+
+    fn get_leaderboard(&mut self) -> InternEngineResponsePackage {
+        let mut leaderboard: Vec<PlayerStats> =
+            self.cached_player_stats().values().cloned().collect();
+        // players may have been deleted since they last played
+        leaderboard.retain_mut(|stats| match self.players.get(stats.id) {
+            Ok(player) => {
+                stats.name = player.contents.name.clone();
+                true
+            }
+            Err(_) => false,
+        });
+        leaderboard.sort_by_key(|s| std::cmp::Reverse(s.points_contributed));
+        SendLeaderboard(leaderboard).into()
+    }
+
     fn handle_action(&mut self, action: EngineAction) -> InternEngineResponseResult {
         match action {
+            ConfirmCatch {
+                session_id,
+                player,
+                proposal_id,
+            } => {
+                let (mut context, session) = self.get_contexed_session(session_id)?;
+                session
+                    .contents
+                    .confirm_catch(player, proposal_id, &mut context)
+            }
+            DisputeCatch {
+                session_id,
+                player,
+                proposal_id,
+                reason,
+            } => {
+                let session = self.sessions.get_mut(session_id)?;
+                session
+                    .contents
+                    .dispute_catch(player, proposal_id, reason, session_id)
+            }
+            GetLocationQuality(session_id) => {
+                let session = self.sessions.get(session_id)?;
+                Ok(session.contents.get_location_quality())
+            }
+            GetSuspiciousLocations(session_id) => {
+                let session = self.sessions.get(session_id)?;
+                Ok(session.contents.get_suspicious_locations())
+            }
+            GetDisputedCatches(session_id) => {
+                let session = self.sessions.get(session_id)?;
+                Ok(session.contents.get_disputed_catches())
+            }
+            ResolveDisputedCatch {
+                session_id,
+                proposal_id,
+                uphold,
+            } => {
+                let (mut context, session) = self.get_contexed_session(session_id)?;
+                session
+                    .contents
+                    .resolve_disputed_catch(proposal_id, uphold, &mut context)
+            }
+            ScheduleAnnouncement {
+                session_id,
+                target,
+                text,
+                time,
+            } => {
+                let (context, session) = self.get_contexed_session(session_id)?;
+                session
+                    .contents
+                    .schedule_announcement(target, text, time, context)
+            }
+            GetAnnouncements { session_id, team } => {
+                let session = self.sessions.get(session_id)?;
+                Ok(session.contents.get_announcements(team))
+            }
+            AcknowledgeAnnouncement {
+                session_id,
+                player,
+                announcement_id,
+            } => {
+                let session = self.sessions.get_mut(session_id)?;
+                session
+                    .contents
+                    .acknowledge_announcement(player, announcement_id)
+            }
+            SendMessage {
+                session_id,
+                author,
+                scope,
+                text,
+                pictures,
+            } => {
+                let (context, session) = self.get_contexed_session(session_id)?;
+                session
+                    .contents
+                    .send_message(author, scope, text, pictures, context)
+            }
+            GetMessages { session_id, team } => {
+                let session = self.sessions.get(session_id)?;
+                Ok(session.contents.get_messages(team))
+            }
+            GetPlayerStats(player_id) => self.get_player_stats(player_id),
+            GetLeaderboard => Ok(self.get_leaderboard()),
+            GetChallengeAnalytics => Ok(self.get_challenge_analytics()),
+            GetPastGameStats(past_game_id) => self.get_past_game_stats(past_game_id),
+            ExportPastGame {
+                past_game_id,
+                format,
+            } => self.export_past_game(past_game_id, format),
             GetPastGame(past_game_id) => self.get_past_game(past_game_id),
             ListPastGamesOfPlayer(player_id) => self.list_past_games_of_player(player_id),
             ListPastGames => self.list_past_games(),
@@ -1018,14 +2152,29 @@ impl Engine {
                 zone_id,
                 sector_ids,
             } => self.set_close_sectors(zone_id, sector_ids),
-            RemoveSector(sector_id) => self.remove_sector(sector_id),
+            RenameSector { sector_id, name } => self.rename_sector(sector_id, name),
+            RemoveSector {
+                sector_id,
+                strategy,
+            } => self.remove_sector(sector_id, strategy),
             RemoveNeighbourhood(one, other) => self.remove_neighbourhood(one, other),
             GetSectors => self.get_sectors(),
+            ImportSectorBoundaries(geojson) => self.import_sector_boundaries(geojson),
+            ImportStations(stations) => Ok(self.import_stations(stations)),
+            GetStations => Ok(self.get_stations()),
+            ExportDatabase => Ok(self.export_database()),
+            ImportDatabase(json) => self.import_database(json),
+            GetSectorMap => Ok(self.get_sector_map()),
             AddNeighbourhood(one, other) => self.add_neighbourhood(one, other),
             AddSector(name) => self.add_sector(name),
+            SetPlayerReviewer(player_id, reviewer) => {
+                self.players.get_mut(player_id)?.contents.reviewer = reviewer;
+                Ok(Success.into())
+            }
             SetPlayerPhoneNumber(player_id, phone_number) => {
                 self.set_player_phone_number(player_id, phone_number)
             }
+            SetPlayerLanguage(player_id, language) => self.set_player_language(player_id, language),
             RenamePlayer {
                 player_id,
                 new_name,
@@ -1036,6 +2185,7 @@ impl Engine {
                 self.upload_player_picture(player_id, picture)
             }
             GetAllZones => self.get_all_zones(),
+            ImportZoneBoundaries(geojson) => self.import_zone_boundaries(geojson),
             AddZone {
                 zone,
                 num_conn_zones,
@@ -1051,15 +2201,65 @@ impl Engine {
                 mongus,
                 s_bahn_zone,
             )),
+            UpdateZone {
+                zone_id,
+                zone,
+                num_conn_zones,
+                num_connections,
+                train_through,
+                mongus,
+                s_bahn_zone,
+            } => self.update_zone(
+                zone_id,
+                zone,
+                num_conn_zones,
+                num_connections,
+                train_through,
+                mongus,
+                s_bahn_zone,
+            ),
+            RemoveZone { zone_id, strategy } => self.remove_zone(zone_id, strategy),
             AddMinutesTo {
                 from_zone,
                 to_zone,
                 minutes,
             } => self.add_minutes_to(from_zone, to_zone, minutes),
+            AddTimedMinutesTo {
+                from_zone,
+                to_zone,
+                from,
+                until,
+                minutes,
+            } => self.add_timed_minutes_to(from_zone, to_zone, from, until, minutes),
             GetRawChallenges => Ok(self.get_raw_challenges()),
             SetRawChallenge(challenge) => self.set_raw_challenge(challenge),
-            AddRawChallenge(challenge) => Ok(self.add_raw_challenge(challenge)),
+            AddRawChallenge(challenge) => self.add_raw_challenge(challenge),
             DeleteAllChallenges => Ok(self.delete_all_challenges()),
+            DeleteChallenge(id) => self.delete_challenge(id),
+            PatchChallenges {
+                filter,
+                patch,
+                add_sets,
+                remove_sets,
+                comment,
+                dry_run,
+            } => self.patch_challenges(*filter, *patch, add_sets, remove_sets, comment, dry_run),
+            EditChallenge {
+                challenge,
+                author,
+                comment,
+            } => self.edit_challenge(challenge, author, comment),
+            GetChallengeHistory(challenge_id) => self.get_challenge_history(challenge_id),
+            QueryChallenges {
+                filter,
+                offset,
+                limit,
+            } => Ok(self.query_challenges(filter, offset, limit)),
+            RevertChallenge {
+                challenge_id,
+                revision,
+                author,
+            } => self.revert_challenge(challenge_id, revision, author),
             GetPlayerByPassphrase(passphrase) => Ok(self.get_player_by_passphrase(passphrase)),
             AddSession { name, mode } => Ok(self.add_session(name, mode)),
             AddPlayer {
@@ -1076,8 +2276,14 @@ impl Engine {
             RemovePlayer { player } => self.remove_player(player),
             Ping(payload) => Ok(self.ping(payload)),
             GetState(session_id) => self.get_state(session_id),
+            GetPlayerState {
+                session_id,
+                player_id,
+            } => self.get_player_state(session_id, player_id),
             AddChallengeSet(name) => Ok(self.add_challenge_set(name)),
             GetChallengeSets => Ok(self.get_challenge_sets()),
+            RenameChallengeSet { set_id, name } => self.rename_challenge_set(set_id, name),
+            RemoveChallengeSet { set_id, strategy } => self.remove_challenge_set(set_id, strategy),
             Start(session_id) => {
                 let (mut context, session) = self.get_contexed_session(session_id)?;
                 Ok(session.contents.start(&mut context))
@@ -1233,6 +2439,14 @@ impl Engine {
                 let session = self.sessions.get_mut(session_id)?;
                 Ok(session.contents.remove_team(team_id))
             }
+            ExportGeoJson {
+                session_id,
+                since,
+                until,
+            } => {
+                let session = self.sessions.get(session_id)?;
+                Ok(session.contents.export_geojson(since, until))
+            }
         }
     }
 }
@@ -0,0 +1,499 @@
+//! Conversion of team trails, game events and sectors into formats that mapping tools understand.
+//!
+//! Both running sessions and past games are first turned into a `GameMap`, which is then rendered
+//! into the requested format. That way, all formats show the same things no matter where the data
+//! came from.
+
+use crate::{DBEntry, SectorEntry};
+use chrono::{Local, NaiveTime, TimeZone};
+use libtruinlag::{Colour, Event, ExportFormat, MinimalLocation};
+use serde_json::{Value, json};
+use std::fmt::Write;
+
+/// The route a team took during a game
+pub struct Trail {
+    pub team_id: usize,
+    pub name: String,
+    pub colour: Colour,
+    pub locations: Vec<MinimalLocation>,
+}
+
+/// Everything that is needed to draw a game on a map: one trail per team and all catches and
+/// completions that happened along the way.
+pub struct GameMap {
+    pub name: String,
+    pub trails: Vec<Trail>,
+    /// The events together with the time they occurred at in seconds since epoch. The `time` of
+    /// an event is only the time of day, which is ambiguous for games that go past midnight.
+    pub events: Vec<(i64, Event)>,
+}
+
+/// Returns the time an event occurred at in seconds since epoch. Events only know their time of
+/// day, so the day is taken from the location of the event, which was recorded around the same
+/// time. Of the days around it, the one closest to the location wins, which keeps events shortly
+/// after midnight on the right day.
+pub fn event_timestamp(event: &Event) -> i64 {
+    let (time, location) = match event {
+        Event::Catch { time, location, .. } | Event::Complete { time, location, .. } => {
+            (*time, location)
+        }
+    };
+    let Some(reference) = Local.timestamp_opt(location.timestamp, 0).earliest() else {
+        return location.timestamp;
+    };
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(time, 0).unwrap_or_default();
+    [-1, 0, 1]
+        .into_iter()
+        .filter_map(|days| {
+            reference
+                .date_naive()
+                .checked_add_signed(chrono::Duration::days(days))
+        })
+        .filter_map(|day| Local.from_local_datetime(&day.and_time(time)).earliest())
+        .map(|t| t.timestamp())
+        .min_by_key(|t| (t - location.timestamp).abs())
+        .unwrap_or(location.timestamp)
+}
+
+impl GameMap {
+    /// Returns the name of the team with the given id, or an empty string if there is none
+    fn team_name(&self, team_id: usize) -> &str {
+        self.trails
+            .iter()
+            .find(|t| t.team_id == team_id)
+            .map(|t| t.name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Returns the colour of the team with the given id
+    fn team_colour(&self, team_id: usize) -> Option<Colour> {
+        self.trails
+            .iter()
+            .find(|t| t.team_id == team_id)
+            .map(|t| t.colour)
+    }
+
+    /// Drops all locations and events that lie outside the given time window. Both ends of the
+    /// window are optional and inclusive.
+    pub fn within(mut self, since: Option<i64>, until: Option<i64>) -> Self {
+        let in_window =
+            |time: i64| since.is_none_or(|s| time >= s) && until.is_none_or(|u| time <= u);
+        for trail in &mut self.trails {
+            trail.locations.retain(|l| in_window(l.timestamp));
+        }
+        let events = std::mem::take(&mut self.events);
+        self.events = events
+            .into_iter()
+            .filter(|(time, _)| in_window(*time))
+            .collect();
+        self
+    }
+
+    /// Returns the location, the kind, a short name and a human-readable description of an event.
+    /// This is used for formats that don't support arbitrary properties, like GPX and KML.
+    fn describe_event<'a>(
+        &self,
+        event: &'a Event,
+    ) -> (&'a MinimalLocation, &'static str, String, String) {
+        let pictures = |ids: &[u64]| {
+            if ids.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    " Pictures: {}",
+                    ids.iter()
+                        .map(u64::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
+        };
+        match event {
+            Event::Catch {
+                catcher_id,
+                caught_id,
+                bounty,
+                picture_ids,
+                location,
+                not_completed: _,
+                time: _,
+            } => (
+                location,
+                "catch",
+                format!(
+                    "{} caught {}",
+                    self.team_name(*catcher_id),
+                    self.team_name(*caught_id)
+                ),
+                format!(
+                    "{} caught {} and got a bounty of {} points.{}",
+                    self.team_name(*catcher_id),
+                    self.team_name(*caught_id),
+                    bounty,
+                    pictures(picture_ids)
+                ),
+            ),
+            Event::Complete {
+                challenge,
+                completer_id,
+                picture_ids,
+                location,
+                not_completed: _,
+                time: _,
+            } => (
+                location,
+                "completion",
+                format!("{}: {}", self.team_name(*completer_id), challenge.title),
+                format!(
+                    "{} completed \"{}\" for {} points: {}{}",
+                    self.team_name(*completer_id),
+                    challenge.title,
+                    challenge.points,
+                    challenge.description,
+                    pictures(picture_ids)
+                ),
+            ),
+        }
+    }
+
+    /// Renders the map in the given format
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::GeoJson => self.to_geojson(),
+            ExportFormat::Gpx => self.to_gpx(),
+            ExportFormat::Kml => self.to_kml(),
+        }
+    }
+
+    /// Renders the map as GPX 1.1.
+    ///
+    /// Every team becomes a track and every catch and completion becomes a waypoint. Team colours
+    /// are added as OsmAnd extensions, which other apps simply ignore.
+    pub fn to_gpx(&self) -> String {
+        let mut gpx = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <gpx version=\"1.1\" creator=\"truinlag\" \
+            xmlns=\"http://www.topografix.com/GPX/1/1\" \
+            xmlns:osmand=\"https://osmand.net\">\n",
+        );
+        // writing to a string cannot fail, so the results are ignored
+        let _ = writeln!(
+            gpx,
+            "  <metadata><name>{}</name></metadata>",
+            escape_xml(&self.name)
+        );
+        // GPX wants all waypoints before the tracks
+        for (time, event) in &self.events {
+            let (location, kind, name, description) = self.describe_event(event);
+            let _ = writeln!(
+                gpx,
+                "  <wpt lat=\"{}\" lon=\"{}\">\n    <time>{}</time>\n    <name>{}</name>\n    \
+                <desc>{}</desc>\n    <type>{}</type>\n  </wpt>",
+                location.latitude,
+                location.longitude,
+                rfc3339(*time),
+                escape_xml(&name),
+                escape_xml(&description),
+                kind
+            );
+        }
+        for trail in &self.trails {
+            let _ = writeln!(
+                gpx,
+                "  <trk>\n    <name>{}</name>\n    <extensions><osmand:color>{}</osmand:color></extensions>\n    <trkseg>",
+                escape_xml(&trail.name),
+                trail.colour.to_hex()
+            );
+            for location in &trail.locations {
+                let _ = writeln!(
+                    gpx,
+                    "      <trkpt lat=\"{}\" lon=\"{}\"><time>{}</time></trkpt>",
+                    location.latitude,
+                    location.longitude,
+                    rfc3339(location.timestamp)
+                );
+            }
+            gpx.push_str("    </trkseg>\n  </trk>\n");
+        }
+        gpx.push_str("</gpx>\n");
+        gpx
+    }
+
+    /// Renders the map as KML.
+    ///
+    /// Every team becomes a `gx:Track` placemark, so that Google Earth can replay the game with its
+    /// time slider, and every catch and completion becomes a point placemark.
+    pub fn to_kml(&self) -> String {
+        let mut kml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <kml xmlns=\"http://www.opengis.net/kml/2.2\" \
+            xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n<Document>\n",
+        );
+        // writing to a string cannot fail, so the results are ignored
+        let _ = writeln!(kml, "  <name>{}</name>", escape_xml(&self.name));
+        for trail in &self.trails {
+            // KML colours are aabbggrr for some reason
+            let colour = format!(
+                "ff{:02x}{:02x}{:02x}",
+                trail.colour.b, trail.colour.g, trail.colour.r
+            );
+            let _ = writeln!(
+                kml,
+                "  <Style id=\"team-{}\">\n    <LineStyle><color>{}</color><width>4</width></LineStyle>\n    \
+                <IconStyle><color>{}</color></IconStyle>\n  </Style>",
+                trail.team_id, colour, colour
+            );
+        }
+        kml.push_str("  <Folder>\n    <name>Trails</name>\n");
+        for trail in self.trails.iter().filter(|t| !t.locations.is_empty()) {
+            let _ = writeln!(
+                kml,
+                "    <Placemark>\n      <name>{}</name>\n      <styleUrl>#team-{}</styleUrl>\n      <gx:Track>",
+                escape_xml(&trail.name),
+                trail.team_id
+            );
+            for location in &trail.locations {
+                let _ = writeln!(kml, "        <when>{}</when>", rfc3339(location.timestamp));
+            }
+            for location in &trail.locations {
+                let _ = writeln!(
+                    kml,
+                    "        <gx:coord>{} {} 0</gx:coord>",
+                    location.longitude, location.latitude
+                );
+            }
+            kml.push_str("      </gx:Track>\n    </Placemark>\n");
+        }
+        kml.push_str("  </Folder>\n  <Folder>\n    <name>Events</name>\n");
+        for (time, event) in &self.events {
+            let (location, _, name, description) = self.describe_event(event);
+            let team_id = match event {
+                Event::Catch { catcher_id, .. } => catcher_id,
+                Event::Complete { completer_id, .. } => completer_id,
+            };
+            let _ = writeln!(
+                kml,
+                "    <Placemark>\n      <name>{}</name>\n      <description>{}</description>\n      \
+                <TimeStamp><when>{}</when></TimeStamp>\n      <styleUrl>#team-{}</styleUrl>\n      \
+                <Point><coordinates>{},{}</coordinates></Point>\n    </Placemark>",
+                escape_xml(&name),
+                escape_xml(&description),
+                rfc3339(*time),
+                team_id,
+                location.longitude,
+                location.latitude
+            );
+        }
+        kml.push_str("  </Folder>\n</Document>\n</kml>\n");
+        kml
+    }
+
+    /// Renders the map as a GeoJSON `FeatureCollection`.
+    ///
+    /// Every team becomes a `LineString` feature coloured in the team colour and every catch and
+    /// completion becomes a `Point` feature with the relevant metadata as properties. Colours are
+    /// also set as `stroke` and `marker-color`, so that tools supporting the simplestyle spec
+    /// (like geojson.io) show them right away. Trails with fewer than two locations are left out,
+    /// since a `LineString` needs at least two positions.
+    pub fn to_geojson(&self) -> String {
+        let mut features: Vec<Value> = self
+            .trails
+            .iter()
+            .filter(|t| t.locations.len() >= 2)
+            .map(|trail| {
+                json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "LineString",
+                        "coordinates": trail
+                            .locations
+                            .iter()
+                            .map(position)
+                            .collect::<Vec<_>>(),
+                    },
+                    "properties": {
+                        "kind": "trail",
+                        "team_id": trail.team_id,
+                        "team": trail.name,
+                        "colour": trail.colour.to_hex(),
+                        "stroke": trail.colour.to_hex(),
+                        "stroke-width": 3,
+                        "timestamps": trail
+                            .locations
+                            .iter()
+                            .map(|l| l.timestamp)
+                            .collect::<Vec<_>>(),
+                    },
+                })
+            })
+            .collect();
+        features.extend(self.events.iter().map(|(time, event)| {
+            let (location, properties) = match event {
+                Event::Catch {
+                    catcher_id,
+                    caught_id,
+                    bounty,
+                    picture_ids,
+                    location,
+                    not_completed,
+                    time: _,
+                } => (
+                    location,
+                    json!({
+                        "kind": "catch",
+                        "time": rfc3339(*time),
+                        "catcher_id": catcher_id,
+                        "catcher": self.team_name(*catcher_id),
+                        "caught_id": caught_id,
+                        "caught": self.team_name(*caught_id),
+                        "bounty": bounty,
+                        "picture_ids": picture_ids,
+                        "not_completed": not_completed
+                            .iter()
+                            .map(|c| c.title.as_str())
+                            .collect::<Vec<_>>(),
+                        "marker-color": self.team_colour(*catcher_id).map(|c| c.to_hex()),
+                        "marker-symbol": "police",
+                    }),
+                ),
+                Event::Complete {
+                    challenge,
+                    not_completed,
+                    completer_id,
+                    picture_ids,
+                    location,
+                    time: _,
+                } => (
+                    location,
+                    json!({
+                        "kind": "completion",
+                        "time": rfc3339(*time),
+                        "team_id": completer_id,
+                        "team": self.team_name(*completer_id),
+                        "challenge_id": challenge.id,
+                        "title": challenge.title,
+                        "description": challenge.description,
+                        "points": challenge.points,
+                        "picture_ids": picture_ids,
+                        "not_completed": not_completed
+                            .iter()
+                            .map(|c| c.title.as_str())
+                            .collect::<Vec<_>>(),
+                        "marker-color": self.team_colour(*completer_id).map(|c| c.to_hex()),
+                        "marker-symbol": "star",
+                    }),
+                ),
+            };
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": position(location),
+                },
+                "properties": properties,
+            })
+        }));
+        json!({
+            "type": "FeatureCollection",
+            "name": self.name,
+            "features": features,
+        })
+        .to_string()
+    }
+}
+
+/// Renders the sectors as a GeoJSON `FeatureCollection` for display.
+///
+/// Every sector becomes a feature with its outline as geometry (or `null` if it has none) and its
+/// name, id and neighbours as properties. Every neighbourhood becomes a `LineString` between the
+/// centres of the two sectors, so that the graph can be checked at a glance. Neighbourhoods of
+/// sectors without an outline are left out.
+pub fn sector_map_geojson(sectors: &[DBEntry<'_, SectorEntry>]) -> String {
+    use geo::Centroid;
+    let centre = |id: u64| {
+        sectors
+            .iter()
+            .find(|s| s.id == id)
+            .and_then(|s| s.contents.boundary.as_ref())
+            .and_then(|b| b.centroid())
+            .map(|c| [c.x(), c.y()])
+    };
+    let mut features: Vec<Value> = sectors
+        .iter()
+        .map(|sector| {
+            json!({
+                "type": "Feature",
+                "geometry": sector.contents.boundary.as_ref().map(|boundary| json!({
+                    "type": "MultiPolygon",
+                    "coordinates": boundary
+                        .iter()
+                        .map(|polygon| {
+                            std::iter::once(polygon.exterior())
+                                .chain(polygon.interiors())
+                                .map(|ring| ring.coords().map(|c| [c.x, c.y]).collect::<Vec<_>>())
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>(),
+                })),
+                "properties": {
+                    "kind": "sector",
+                    "name": sector.contents.name.to_string(),
+                    "id": sector.id,
+                    "neighbours": sector.contents.neighbours,
+                },
+            })
+        })
+        .collect();
+    // neighbourhoods are stored on both sectors, but should only be drawn once
+    features.extend(sectors.iter().flat_map(|sector| {
+        sector
+            .contents
+            .neighbours
+            .iter()
+            .filter(|n| **n > sector.id)
+            .filter_map(|n| Some((centre(sector.id)?, centre(*n)?, *n)))
+            .map(|(from, to, neighbour)| {
+                json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "LineString",
+                        "coordinates": [from, to],
+                    },
+                    "properties": {
+                        "kind": "neighbourhood",
+                        "sectors": [sector.id, neighbour],
+                    },
+                })
+            })
+            .collect::<Vec<_>>()
+    }));
+    json!({
+        "type": "FeatureCollection",
+        "name": "sectors",
+        "features": features,
+    })
+    .to_string()
+}
+
+/// Escapes the characters that have a special meaning in XML
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// GeoJSON positions are longitude first, unlike pretty much everything else.
+fn position(location: &MinimalLocation) -> [f32; 2] {
+    [location.longitude, location.latitude]
+}
+
+/// Formats a timestamp in seconds since epoch as an RFC 3339 string in local time
+fn rfc3339(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .earliest()
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
@@ -0,0 +1,423 @@
+//! Everything that happens to the locations that players send: plausibility checks, so that fake
+//! or wildly jumping positions don't end up as a team's location, fusing the locations of a
+//! team's players and finding the zone and sector a team is in.
+
+use crate::{Config, DBMirror, SectorEntry, ZoneEntry};
+use libtruinlag::{
+    DetailedLocation, LocationSuspicion, MinimalLocation, SuspiciousLocation, commands,
+};
+use serde::{Deserialize, Serialize};
+
+/// The reported speed is only a snapshot, so it is only compared with the implied speed if the
+/// previous location isn't older than this many seconds.
+const REPORTED_SPEED_WINDOW: i64 = 30;
+
+/// The number of locations in a row that have to be flagged as too fast while agreeing with each
+/// other before they are trusted over the last accepted location. This keeps a single bad location
+/// that got accepted from locking a player out.
+const REANCHOR_LOCATIONS: usize = 10;
+
+/// The minimum number of seconds between the arrival of two locations of a re-anchoring streak.
+/// The arrival time is used, since the timestamps come from the phone and can be made up, so a
+/// streak can't be sent all at once and has to last at least a few minutes.
+const REANCHOR_SPACING: i64 = 20;
+
+/// The number of suspicious locations a team keeps, older ones are dropped
+pub const MAX_SUSPICIOUS_LOCATIONS: usize = 200;
+
+/// The representation of a location that was deemed implausible in the db. Suspicious locations
+/// are stored on the team of the player that sent them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuspiciousLocationEntry {
+    pub player: u64,
+    pub location: DetailedLocation,
+    pub suspicion: LocationSuspicion,
+    pub received: chrono::DateTime<chrono::Local>,
+}
+
+impl SuspiciousLocationEntry {
+    /// Converts the engine-internal suspicious location to a truinlag suspicious location
+    pub fn to_sendable(&self, team: usize) -> SuspiciousLocation {
+        SuspiciousLocation {
+            team,
+            player: self.player,
+            location: self.location.clone(),
+            suspicion: self.suspicion,
+            received: self.received.timestamp(),
+        }
+    }
+}
+
+/// Checks whether a location sent by a player is plausible, given the last location the same
+/// player sent. Returns why the location is suspicious, if it is.
+pub fn check_plausibility(
+    previous: Option<&MinimalLocation>,
+    location: &DetailedLocation,
+    config: &Config,
+) -> Option<LocationSuspicion> {
+    let seconds_ahead = location.timestamp - chrono::Local::now().timestamp();
+    if seconds_ahead > config.max_clock_skew.num_seconds() {
+        return Some(LocationSuspicion::FutureTimestamp(seconds_ahead));
+    }
+    let previous = previous?;
+    let elapsed = location.timestamp - previous.timestamp;
+    if elapsed < 0 {
+        return Some(LocationSuspicion::OutOfOrder);
+    }
+    // the accuracy is subtracted, so that imprecise fixes of a player that doesn't move aren't
+    // counted as movement
+    let distance =
+        (previous.distance_to(&location.clone().into()) - location.accuracy as f64).max(0.0);
+    let implied_speed = distance / elapsed.max(1) as f64;
+    // phones report a negative speed if they don't know it
+    let reported_speed = location.speed as f64;
+    if implied_speed > config.max_transit_speed
+        || (elapsed <= REPORTED_SPEED_WINDOW
+            && reported_speed >= 0.0
+            && implied_speed > reported_speed + config.reported_speed_tolerance)
+    {
+        return Some(LocationSuspicion::TooFast {
+            implied_speed: implied_speed as f32,
+            reported_speed: location.speed,
+        });
+    }
+    None
+}
+
+/// Decides whether the last accepted location of a player should be replaced by the latest
+/// flagged one. That is the case if the last `REANCHOR_LOCATIONS` locations the player sent since
+/// `anchor` were all flagged as too fast, arrived at least `REANCHOR_SPACING` apart and are
+/// plausible among themselves, which means that the anchor is probably the bad one. The streak
+/// is long and slow on purpose, so that a few faked locations can't move a player somewhere else.
+pub fn should_reanchor(
+    suspicious: &[SuspiciousLocationEntry],
+    player: u64,
+    anchor: Option<&MinimalLocation>,
+    config: &Config,
+) -> bool {
+    let flagged: Vec<&SuspiciousLocationEntry> = suspicious
+        .iter()
+        .filter(|s| s.player == player)
+        .filter(|s| anchor.is_none_or(|a| s.location.timestamp > a.timestamp))
+        .collect();
+    if flagged.len() < REANCHOR_LOCATIONS {
+        return false;
+    }
+    let latest = &flagged[flagged.len() - REANCHOR_LOCATIONS..];
+    latest
+        .iter()
+        .all(|s| matches!(s.suspicion, LocationSuspicion::TooFast { .. }))
+        && latest.windows(2).all(|pair| {
+            (pair[1].received - pair[0].received).num_seconds() >= REANCHOR_SPACING
+                && check_plausibility(
+                    Some(&pair[0].location.clone().into()),
+                    &pair[1].location,
+                    config,
+                )
+                .is_none()
+        })
+}
+
+/// What the engine knows about the locations that a single player of a team sent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerFixes {
+    /// the latest plausible location the player sent
+    pub latest: Option<DetailedLocation>,
+    /// the sum of the accuracies of all plausible locations, for the average accuracy
+    pub accuracy_sum: u64,
+    /// whether the player is currently far away from the rest of their team
+    pub split: bool,
+    /// how often the player got separated from the rest of their team
+    pub splits: u32,
+}
+
+/// The result of fusing the latest locations of a team's players
+pub struct FusedLocation {
+    pub location: DetailedLocation,
+    /// the player whose location weighs the most
+    pub main_player: u64,
+    /// The players that are far away from the rest of the team, along with their distance to the
+    /// fused location. Their locations aren't part of the fused location.
+    pub outliers: Vec<(u64, f64)>,
+}
+
+/// How much a location counts when fusing. Accurate locations count more, and the weight halves
+/// every `location_fusion_half_life`.
+fn weight(location: &DetailedLocation, newest: i64, config: &Config) -> f64 {
+    let age = (newest - location.timestamp).max(0) as f64;
+    let half_life = config.location_fusion_half_life.num_seconds().max(1) as f64;
+    let accuracy = (location.accuracy as f64).max(1.0);
+    0.5_f64.powf(age / half_life) / (accuracy * accuracy)
+}
+
+/// The weighted average of some locations. Averaging latitudes and longitudes directly is fine at
+/// the distances a team is spread over.
+fn weighted_centre<'a>(
+    fixes: impl Iterator<Item = &'a (u64, &'a DetailedLocation, f64)>,
+) -> Option<MinimalLocation> {
+    let (mut latitude, mut longitude, mut total, mut timestamp) = (0.0, 0.0, 0.0, i64::MIN);
+    for (_, location, weight) in fixes {
+        latitude += location.latitude as f64 * weight;
+        longitude += location.longitude as f64 * weight;
+        total += weight;
+        timestamp = timestamp.max(location.timestamp);
+    }
+    if total <= 0.0 {
+        return None;
+    }
+    Some(MinimalLocation {
+        latitude: (latitude / total) as f32,
+        longitude: (longitude / total) as f32,
+        timestamp,
+    })
+}
+
+/// Combines the latest locations of a team's players into a single location for the team.
+///
+/// Only locations that are at most `max_location_age` older than the newest one are used. Players
+/// that are further than `team_split_distance` away from the rest of the team are left out, so
+/// that a single separated player doesn't drag the team's location somewhere in between. The
+/// rest of the team is found around the medoid, i.e. the location with the smallest total
+/// distance to all others, which is always with the bulk of the team, no matter how far away the
+/// separated players are or how much their locations weigh. Returns `None` if there are no
+/// locations at all.
+pub fn fuse<'a>(
+    fixes: impl Iterator<Item = (u64, &'a DetailedLocation)>,
+    config: &Config,
+) -> Option<FusedLocation> {
+    let fixes: Vec<(u64, &DetailedLocation)> = fixes.collect();
+    let newest = fixes.iter().map(|(_, l)| l.timestamp).max()?;
+    let recent: Vec<(u64, &DetailedLocation, f64)> = fixes
+        .into_iter()
+        .filter(|(_, l)| newest - l.timestamp <= config.max_location_age.num_seconds())
+        .map(|(player, l)| (player, l, weight(l, newest, config)))
+        .collect();
+    let points: Vec<MinimalLocation> = recent.iter().map(|(_, l, _)| (*l).clone().into()).collect();
+    let total_distance =
+        |point: &MinimalLocation| points.iter().map(|p| p.distance_to(point)).sum::<f64>();
+    // if the total distances are equal, e.g. with two players, the better location wins
+    let medoid = (0..recent.len()).min_by(|&a, &b| {
+        total_distance(&points[a])
+            .total_cmp(&total_distance(&points[b]))
+            .then(recent[b].2.total_cmp(&recent[a].2))
+    })?;
+    let (inliers, outliers): (Vec<_>, Vec<_>) = recent
+        .iter()
+        .zip(&points)
+        .partition(|(_, point)| points[medoid].distance_to(point) <= config.team_split_distance);
+    let inliers: Vec<&(u64, &DetailedLocation, f64)> =
+        inliers.into_iter().map(|(fix, _)| fix).collect();
+    let centre = weighted_centre(inliers.iter().copied())?;
+    let total_weight: f64 = inliers.iter().map(|(.., w)| w).sum();
+    let (main_player, main_location, _) = inliers
+        .iter()
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .copied()
+        .copied()?;
+    Some(FusedLocation {
+        location: DetailedLocation {
+            latitude: centre.latitude,
+            longitude: centre.longitude,
+            accuracy: (inliers
+                .iter()
+                .map(|(_, l, w)| l.accuracy as f64 * w)
+                .sum::<f64>()
+                / total_weight) as u16,
+            heading: main_location.heading,
+            speed: main_location.speed,
+            timestamp: centre.timestamp,
+        },
+        main_player,
+        outliers: outliers
+            .into_iter()
+            .map(|((player, ..), point)| (*player, centre.distance_to(point)))
+            .collect(),
+    })
+}
+
+/// Parses a GeoJSON `FeatureCollection` of boundaries, e.g. of zones or sectors. Every feature
+/// needs a `Polygon` or `MultiPolygon` geometry and the given property, which says what the
+/// boundary belongs to. The property is returned as a string, even if it is a number.
+fn parse_boundaries(
+    geojson: &str,
+    property: &str,
+) -> Result<Vec<(String, geo::MultiPolygon<f64>)>, commands::Error> {
+    let bad = |text: String| commands::Error::BadData(format!("boundaries: {}", text));
+    let value: serde_json::Value = serde_json::from_str(geojson).map_err(|e| bad(e.to_string()))?;
+    let features = value["features"]
+        .as_array()
+        .ok_or(bad("no features found".into()))?;
+    features
+        .iter()
+        .map(|feature| {
+            let key = match &feature["properties"][property] {
+                serde_json::Value::String(key) => key.clone(),
+                serde_json::Value::Number(key) => key.to_string(),
+                _ => return Err(bad(format!("a feature has no {} property", property))),
+            };
+            let geometry = &feature["geometry"];
+            let polygons = match geometry["type"].as_str() {
+                Some("Polygon") => Some(vec![parse_polygon(&geometry["coordinates"])]),
+                Some("MultiPolygon") => geometry["coordinates"]
+                    .as_array()
+                    .map(|polygons| polygons.iter().map(parse_polygon).collect()),
+                _ => None,
+            };
+            polygons
+                .and_then(|polygons| polygons.into_iter().collect::<Option<Vec<_>>>())
+                .map(|polygons| (key.clone(), geo::MultiPolygon::new(polygons)))
+                .ok_or(bad(format!(
+                    "{} {} has no valid polygon geometry",
+                    property, key
+                )))
+        })
+        .collect()
+}
+
+/// Parses a GeoJSON `FeatureCollection` of zone boundaries. Every feature needs a `zone` property
+/// with the zone number (not its id).
+pub fn parse_zone_boundaries(
+    geojson: &str,
+) -> Result<Vec<(u64, geo::MultiPolygon<f64>)>, commands::Error> {
+    parse_boundaries(geojson, "zone")?
+        .into_iter()
+        .map(|(zone, boundary)| match zone.parse() {
+            Ok(zone) => Ok((zone, boundary)),
+            Err(_) => Err(commands::Error::BadData(format!(
+                "boundaries: {} is not a zone number",
+                zone
+            ))),
+        })
+        .collect()
+}
+
+/// Parses a GeoJSON `FeatureCollection` of sector boundaries. Every feature needs a `sector`
+/// property with the sector's letter.
+pub fn parse_sector_boundaries(
+    geojson: &str,
+) -> Result<Vec<(char, geo::MultiPolygon<f64>)>, commands::Error> {
+    parse_boundaries(geojson, "sector")?
+        .into_iter()
+        .map(|(sector, boundary)| {
+            let mut chars = sector.chars();
+            match (chars.next(), chars.next()) {
+                (Some(name), None) => Ok((name, boundary)),
+                _ => Err(commands::Error::BadData(format!(
+                    "boundaries: {} is not a sector letter",
+                    sector
+                ))),
+            }
+        })
+        .collect()
+}
+
+/// Parses the coordinates of a GeoJSON polygon. The first ring is the exterior, all others are
+/// holes.
+fn parse_polygon(coordinates: &serde_json::Value) -> Option<geo::Polygon<f64>> {
+    let mut rings = coordinates
+        .as_array()?
+        .iter()
+        .map(|ring| {
+            ring.as_array()?
+                .iter()
+                .map(|position| {
+                    // GeoJSON positions are longitude first
+                    Some(geo::Coord {
+                        x: position[0].as_f64()?,
+                        y: position[1].as_f64()?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(geo::LineString::new)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if rings.is_empty() {
+        return None;
+    }
+    let exterior = rings.remove(0);
+    Some(geo::Polygon::new(exterior, rings))
+}
+
+/// Finds the id of the zone that contains a location, using the zone boundaries. The current zone
+/// is checked first, since teams usually stay in a zone for a while. Returns `None` if no zone
+/// contains the location, e.g. because the team left the game area or no boundaries were
+/// imported.
+pub fn zone_containing(
+    location: &DetailedLocation,
+    current_zone_id: u64,
+    zones: &DBMirror<ZoneEntry>,
+) -> Option<u64> {
+    use geo::Contains;
+    let point = geo::Point::new(location.longitude as f64, location.latitude as f64);
+    let contains = |zone: &ZoneEntry| {
+        zone.boundary
+            .as_ref()
+            .is_some_and(|boundary| boundary.contains(&point))
+    };
+    if zones
+        .get(current_zone_id)
+        .is_ok_and(|zone| contains(zone.contents))
+    {
+        return Some(current_zone_id);
+    }
+    zones.find(contains).map(|zone| zone.id)
+}
+
+/// Finds the id of the sector that contains a location, like `zone_containing`
+pub fn sector_containing(
+    location: &DetailedLocation,
+    current_sector_id: Option<u64>,
+    sectors: &DBMirror<SectorEntry>,
+) -> Option<u64> {
+    use geo::Contains;
+    let point = geo::Point::new(location.longitude as f64, location.latitude as f64);
+    let contains = |sector: &SectorEntry| {
+        sector
+            .boundary
+            .as_ref()
+            .is_some_and(|boundary| boundary.contains(&point))
+    };
+    if let Some(id) = current_sector_id
+        && sectors
+            .get(id)
+            .is_ok_and(|sector| contains(sector.contents))
+    {
+        return Some(id);
+    }
+    sectors.find(contains).map(|sector| sector.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(latitude: f32, longitude: f32, accuracy: u16) -> DetailedLocation {
+        DetailedLocation {
+            latitude,
+            longitude,
+            accuracy,
+            heading: 0.0,
+            speed: 0.0,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn fuses_around_the_rest_of_the_team() {
+        let config = Config::default();
+        // two players about 100 m apart, and a third one about 5.5 km away with the most
+        // accurate location
+        let together = [fix(47.3700, 8.5400, 30), fix(47.3709, 8.5400, 30)];
+        let separated = fix(47.4200, 8.5400, 5);
+        let fused = fuse(
+            [(1, &together[0]), (2, &together[1]), (3, &separated)].into_iter(),
+            &config,
+        )
+        .unwrap();
+        assert_ne!(fused.main_player, 3);
+        assert_eq!(fused.outliers.len(), 1);
+        assert_eq!(fused.outliers[0].0, 3);
+        let location: MinimalLocation = fused.location.into();
+        assert!(location.distance_to(&together[0].clone().into()) < 100.0);
+    }
+}
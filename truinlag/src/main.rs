@@ -1,19 +1,28 @@
+pub(crate) mod archive;
+pub(crate) mod catch;
 pub(crate) mod challenge;
+pub(crate) mod chat;
 pub(crate) mod engine;
 mod error;
+pub(crate) mod export;
+pub(crate) mod location;
+pub(crate) mod migration;
 pub(crate) mod runtime;
 pub(crate) mod session;
+pub(crate) mod stats;
 pub(crate) mod team;
 
 use bonsaidb::{
     core::schema::{Collection, Schema, SerializedCollection},
     local::Database,
 };
-use challenge::{ChallengeEntry, ChallengeSetEntry, InOpenChallenge};
+use challenge::{ChallengeEntry, ChallengeRevisionEntry, ChallengeSetEntry, InOpenChallenge};
 use chrono::Timelike;
 use error::Result;
+use export::{GameMap, Trail, event_timestamp};
 use libtruinlag::{commands::EngineAction, *};
 use log::error;
+use migration::MetaEntry;
 use partially::Partial;
 use runtime::{InternEngineCommand, RuntimeRequest, manager};
 use serde::{Deserialize, Serialize};
@@ -39,7 +48,8 @@ pub struct TimerHook {
     pub id: u64,
 }
 
-// This implementation exists for db backwards compatibility.
+// This implementation exists for db backwards compatibility, games saved before timers were added
+// need it to be read, e.g. by the first migration.
 impl Default for TimerHook {
     fn default() -> Self {
         Self {
@@ -133,6 +143,8 @@ pub struct EngineContext<'a> {
     picture_db: &'a mut DBMirror<PictureEntry>,
     timer_tracker: &'a mut TimerTracker,
     sector_db: &'a DBMirror<SectorEntry>,
+    /// the engine's cache of the player stats, which has to be cleared when a past game is added
+    player_stats: &'a mut Option<HashMap<u64, PlayerStats>>,
 }
 
 /// A grouping of data contained within the relevant session and the engine. Its primary purpose is
@@ -345,28 +357,6 @@ where
             })
     }
 
-    /// Searches for an entry in the collection that satisfies a predicate. (mutably)
-    fn find_mut(&mut self, mut predicate: impl FnMut(&T) -> bool) -> Option<MutDBEntry<'_, T>> {
-        match self
-            .entries
-            .iter_mut()
-            .enumerate()
-            .find(|(_, entry)| match entry {
-                None => false,
-                Some((item, _)) => predicate(item), // TODO: return false if deleted
-            }) {
-            Some((index, entry)) => {
-                let contents = entry.as_mut().unwrap();
-                contents.1 = DBStatus::Edited;
-                Some(MutDBEntry {
-                    id: index as u64,
-                    contents: &mut contents.0,
-                })
-            }
-            None => None,
-        }
-    }
-
     /// Gets all entries from the collection in a `Vec`.
     fn get_all(&self) -> Vec<DBEntry<'_, T>> {
         self.entries
@@ -428,6 +418,32 @@ where
         }
     }
 
+    /// Clones all entries of the collection together with their ids.
+    fn entries(&self) -> Vec<(u64, T)> {
+        self.get_all()
+            .into_iter()
+            .map(|entry| (entry.id, entry.contents.clone()))
+            .collect()
+    }
+
+    /// Replaces the whole collection with the given entries, keeping their ids. Entries that are
+    /// not replaced are deleted.
+    fn replace_all(&mut self, entries: Vec<(u64, T)>) {
+        self.delete_all();
+        for (id, thing) in entries {
+            self.insert(id, thing);
+        }
+    }
+
+    /// Adds an entry with the given id, replacing the entry that had the id before, if any.
+    fn insert(&mut self, id: u64, thing: T) {
+        let index = id as usize;
+        if self.entries.len() <= index {
+            self.entries.resize(index + 1, None);
+        }
+        self.entries[index] = Some((thing, DBStatus::Edited));
+    }
+
     /// Extracts all changes in the collection for saving them to disk.
     fn extract_changes(&mut self) -> Vec<ClonedDBEntry<T>> {
         let mut changes = Vec::new();
@@ -646,6 +662,56 @@ pub struct Config {
     /// away.
     /// *Recommended Value:* **15 minutes**
     pub grace_period_duration: chrono::TimeDelta,
+    /// If this is turned on, a catch only goes through once the caught team confirms it. The
+    /// caught team may also dispute the catch, in which case the organisers have to decide.
+    /// *Recommended Value:* **false, unless catches have been contentious**
+    pub catch_confirmation: bool,
+    /// With catch confirmation turned on, the caught team has this much time to confirm or dispute
+    /// a catch before it goes through anyways.
+    /// *Recommended Value:* **2 minutes**
+    pub catch_confirmation_timeout: chrono::TimeDelta,
+    /// How close the catchers have to be to the caught team for a catch to go through, in metres.
+    /// *Recommended Value:* **300**
+    pub catch_radius: f64,
+    /// Locations are never exact, so the catch radius is extended by the accuracies reported by
+    /// both teams, but by at most this many metres.
+    /// *Recommended Value:* **100**
+    pub catch_accuracy_margin: f64,
+    /// A location that is older than this is considered stale when checking a catch.
+    /// *Recommended Value:* **2 minutes**
+    pub max_location_age: chrono::TimeDelta,
+    /// What happens to a catch if the location of one of the teams is missing or stale.
+    /// *Recommended Value:* **Allow, or RequireConfirmation if catches have been contentious**
+    pub location_policy: LocationPolicy,
+    /// Whether completions are checked against the team's location, see `VerificationMode`.
+    /// *Recommended Value:* **Warn, once places have coordinates or zones have outlines**
+    pub completion_verification: VerificationMode,
+    /// How close a team has to be to the place of a challenge to complete it, in metres. The
+    /// team's location accuracy is added, up to `catch_accuracy_margin`.
+    /// *Recommended Value:* **300**
+    pub completion_radius: f64,
+    /// Locations that imply that a player moved faster than this (in m/s) are deemed implausible
+    /// and ignored. This should be a bit above the fastest train in the game area.
+    /// *Recommended Value:* **70 (about 250 km/h)**
+    pub max_transit_speed: f64,
+    /// Locations that imply that a player moved faster than the speed reported by their phone
+    /// plus this much (in m/s) are deemed implausible and ignored.
+    /// *Recommended Value:* **15**
+    pub reported_speed_tolerance: f64,
+    /// Locations with a timestamp further in the future than this are deemed implausible and
+    /// ignored. Phone clocks are never exactly right, so some leeway is needed.
+    /// *Recommended Value:* **30 seconds**
+    pub max_clock_skew: chrono::TimeDelta,
+    /// The locations of a team's players are fused into a single location for the team. The
+    /// weight of a location halves with every this much time that it is older than the newest one.
+    /// Locations older than `max_location_age` aren't used at all.
+    /// *Recommended Value:* **30 seconds**
+    pub location_fusion_half_life: chrono::TimeDelta,
+    /// A player that is further than this away from the rest of their team (in metres) is
+    /// considered separated from the team. Their location isn't used for the team's location and
+    /// the organisers are warned.
+    /// *Recommended Value:* **500**
+    pub team_split_distance: f64,
 
     // Bounty system
     /// When a team becomes gatherer, they should start off with a certain bounty. Or not, we have
@@ -748,6 +814,19 @@ impl Default for Config {
             num_challenges: 3,
             start_zone: 110,
             grace_period_duration: chrono::TimeDelta::minutes(15),
+            catch_confirmation: false,
+            catch_confirmation_timeout: chrono::TimeDelta::minutes(2),
+            catch_radius: 300.0,
+            catch_accuracy_margin: 100.0,
+            max_location_age: chrono::TimeDelta::minutes(2),
+            location_policy: LocationPolicy::Allow,
+            completion_verification: VerificationMode::Off,
+            completion_radius: 300.0,
+            max_transit_speed: 70.0,
+            reported_speed_tolerance: 15.0,
+            max_clock_skew: chrono::TimeDelta::seconds(30),
+            location_fusion_half_life: chrono::TimeDelta::seconds(30),
+            team_split_distance: 500.0,
             bounty_base_points: 0,
             bounty_start_points: 500,
             bounty_percentage: 0.3,
@@ -857,6 +936,12 @@ impl From<Config> for GameConfig {
             start_time: value.start_time,
             end_time: value.end_time,
             challenge_sets: value.challenge_sets,
+            catch_confirmation: value.catch_confirmation,
+            catch_radius: value.catch_radius,
+            catch_accuracy_margin: value.catch_accuracy_margin,
+            max_location_age: value.max_location_age.num_seconds().max(0) as u64,
+            location_policy: value.location_policy,
+            completion_verification: value.completion_verification,
         }
     }
 }
@@ -869,6 +954,14 @@ impl From<PartialGameConfig> for PartialConfig {
             start_time: value.start_time,
             end_time: value.end_time,
             challenge_sets: value.challenge_sets,
+            catch_confirmation: value.catch_confirmation,
+            catch_radius: value.catch_radius,
+            catch_accuracy_margin: value.catch_accuracy_margin,
+            max_location_age: value
+                .max_location_age
+                .map(|seconds| chrono::TimeDelta::seconds(seconds as i64)),
+            location_policy: value.location_policy,
+            completion_verification: value.completion_verification,
             ..Default::default()
         }
     }
@@ -876,7 +969,7 @@ impl From<PartialGameConfig> for PartialConfig {
 
 /// Some bonsaidb thing to make the db work
 #[derive(Schema)]
-#[schema(name="engine", collections=[Session, PlayerEntry, ChallengeEntry, ZoneEntry, SectorEntry, PastGameEntry, PictureEntry, ChallengeSetEntry])]
+#[schema(name="engine", collections=[Session, PlayerEntry, ChallengeEntry, ZoneEntry, SectorEntry, PastGameEntry, PictureEntry, ChallengeSetEntry, StationEntry, MetaEntry, ChallengeRevisionEntry])]
 struct EngineSchema {}
 
 #[derive(Debug, Collection, Serialize, Deserialize, Clone)]
@@ -931,6 +1024,12 @@ pub struct PlayerEntry {
     session: Option<u64>,
     #[serde(default)]
     last_location: Option<MinimalLocation>,
+    /// Reviewers may approve challenges, see `challenge::review`
+    #[serde(default)]
+    reviewer: bool,
+    /// The language of the translated challenge texts this player gets
+    #[serde(default)]
+    language: Option<String>,
 }
 
 impl PlayerEntry {
@@ -942,6 +1041,8 @@ impl PlayerEntry {
             id,
             picture_id: self.picture,
             phone_number: self.phone_number.clone(),
+            reviewer: self.reviewer,
+            language: self.language.clone(),
         }
     }
 }
@@ -966,10 +1067,17 @@ pub struct ZoneEntry {
     /// Travel duration to every other zone. Key is the target zone id and value is the time in
     /// minutes required to travel there.
     pub minutes_to: HashMap<u64, u64>,
+    /// Travel durations for parts of the day, which replace `minutes_to` while they apply.
+    #[serde(default)]
+    pub travel_bands: Vec<TravelBand>,
     /// The ids of the sectors that are 'close'. During ZKaff generation, if the team has no sector
     /// but a zone, only challenges in close sectors will be generated.
     #[serde(default)]
     pub sectors: Vec<u64>,
+    /// The outline of the zone, with longitude as x and latitude as y. Used to find out which
+    /// zone a team is in from its location.
+    #[serde(default)]
+    pub boundary: Option<geo::MultiPolygon<f64>>,
 }
 
 impl ZoneEntry {
@@ -987,6 +1095,7 @@ impl ZoneEntry {
             mongus: self.mongus,
             s_bahn_zone: self.s_bahn_zone,
             minutes_to: self.minutes_to.clone(),
+            travel_bands: self.travel_bands.clone(),
             close_sectors: {
                 let mut sectors = Vec::new();
                 for s in &self.sectors {
@@ -1029,8 +1138,22 @@ impl ZoneEntry {
         }
     }
 
+    /// The travel durations to the other zones at a time of day, i.e. the ones of the travel band
+    /// that contains the time or `minutes_to` if there is none.
+    pub fn minutes_to_at(&self, time: chrono::NaiveTime) -> &HashMap<u64, u64> {
+        self.travel_bands
+            .iter()
+            .find(|b| b.contains(time))
+            .map_or(&self.minutes_to, |band| &band.minutes_to)
+    }
+
+    /// The travel durations to the other zones right now, see `minutes_to_at`.
+    pub fn current_minutes_to(&self) -> &HashMap<u64, u64> {
+        self.minutes_to_at(chrono::Local::now().time())
+    }
+
     pub fn zones_with_distance(&self, range: Range<u64>) -> Vec<u64> {
-        self.minutes_to
+        self.current_minutes_to()
             .iter()
             .filter_map(|(to, t)| if range.contains(t) { Some(*to) } else { None })
             .collect()
@@ -1042,6 +1165,9 @@ impl ZoneEntry {
 pub struct SectorEntry {
     pub name: char,
     pub neighbours: Vec<u64>,
+    /// The outline of the sector, like `ZoneEntry::boundary`
+    #[serde(default)]
+    pub boundary: Option<geo::MultiPolygon<f64>>,
 }
 
 impl SectorEntry {
@@ -1054,6 +1180,35 @@ impl SectorEntry {
     }
 }
 
+/// The representation of a public transport station in the db
+#[derive(Debug, Clone, Serialize, Deserialize, Collection)]
+#[collection(name = "station")]
+pub struct StationEntry {
+    pub name: String,
+    pub latitude: f32,
+    pub longitude: f32,
+    /// the id of the zone the station is in
+    pub zone: Option<u64>,
+    pub aliases: Vec<String>,
+}
+
+impl StationEntry {
+    /// Converts the engine-internal `StationEntry` type into a sendable truinlag `Station` type
+    fn to_sendable(&self, id: u64, zone_db: &DBMirror<ZoneEntry>) -> Station {
+        Station {
+            name: self.name.clone(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+            zone: self
+                .zone
+                .and_then(|z| zone_db.get(z).ok())
+                .map(|z| z.contents.zone),
+            aliases: self.aliases.clone(),
+            id: Some(id),
+        }
+    }
+}
+
 /// The representation of a running game in the db
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InGame {
@@ -1126,66 +1281,93 @@ impl PastGameEntry {
                 .map(|t| t.to_sendable(player_db))
                 .collect::<Result<_, _>>()?,
             id,
-            events: {
-                let mut unsorted: Vec<Event> = self
-                    .teams
+            events: self.events(),
+        })
+    }
+
+    /// Compiles all events from all teams into a list of events ordered by time
+    fn events(&self) -> Vec<Event> {
+        let mut unsorted: Vec<Event> = self
+            .teams
+            .iter()
+            .enumerate()
+            .flat_map(|(i, t)| {
+                t.periods
                     .iter()
-                    .enumerate()
-                    .flat_map(|(i, t)| {
-                        t.periods
-                            .iter()
-                            .filter_map(|p| match p.context.clone() {
-                                PeriodContext::CompletedChallenge {
-                                    title,
-                                    description,
-                                    zone: _,
-                                    points,
-                                    id,
-                                    not_completed,
-                                } => Some(Event::Complete {
-                                    challenge: Challenge {
-                                        title,
-                                        description,
-                                        points,
-                                        id,
-                                    },
-                                    not_completed,
-                                    completer_id: i,
-                                    time: p.end_time.num_seconds_from_midnight(),
-                                    picture_ids: p.pictures.clone(),
-                                    location: MinimalLocation {
-                                        latitude: p.end_location.0,
-                                        longitude: p.end_location.1,
-                                        timestamp: p.end_time.timestamp(),
-                                    },
-                                }),
-                                PeriodContext::Caught {
-                                    catcher_team,
-                                    bounty,
-                                    not_completed,
-                                    catcher_period_id: _,
-                                } => Some(Event::Catch {
-                                    catcher_id: catcher_team,
-                                    caught_id: i,
-                                    bounty,
-                                    time: p.end_time.num_seconds_from_midnight(),
-                                    picture_ids: p.pictures.clone(),
-                                    location: MinimalLocation {
-                                        latitude: p.end_location.0,
-                                        longitude: p.end_location.1,
-                                        timestamp: p.end_time.timestamp(),
-                                    },
-                                    not_completed,
-                                }),
-                                _ => None,
-                            })
-                            .collect::<Vec<Event>>()
+                    .filter_map(|p| match p.context.clone() {
+                        PeriodContext::CompletedChallenge {
+                            title,
+                            description,
+                            zone: _,
+                            points,
+                            id,
+                            not_completed,
+                            verification: _,
+                        } => Some(Event::Complete {
+                            challenge: Challenge {
+                                title,
+                                description,
+                                points,
+                                id,
+                            },
+                            not_completed,
+                            completer_id: i,
+                            time: p.end_time.num_seconds_from_midnight(),
+                            picture_ids: p.pictures.clone(),
+                            location: MinimalLocation {
+                                latitude: p.end_location.0,
+                                longitude: p.end_location.1,
+                                timestamp: p.end_time.timestamp(),
+                            },
+                        }),
+                        PeriodContext::Caught {
+                            catcher_team,
+                            bounty,
+                            not_completed,
+                            catcher_period_id: _,
+                        } => Some(Event::Catch {
+                            catcher_id: catcher_team,
+                            caught_id: i,
+                            bounty,
+                            time: p.end_time.num_seconds_from_midnight(),
+                            picture_ids: p.pictures.clone(),
+                            location: MinimalLocation {
+                                latitude: p.end_location.0,
+                                longitude: p.end_location.1,
+                                timestamp: p.end_time.timestamp(),
+                            },
+                            not_completed,
+                        }),
+                        _ => None,
                     })
-                    .collect();
-                unsorted.sort();
-                unsorted
-            },
-        })
+                    .collect::<Vec<Event>>()
+            })
+            .collect();
+        unsorted.sort();
+        unsorted
+    }
+
+    /// Compiles the team trails and all events into a `GameMap` for exporting
+    fn to_map(&self) -> GameMap {
+        GameMap {
+            name: self.name.clone(),
+            trails: self
+                .teams
+                .iter()
+                .enumerate()
+                .map(|(team_id, team)| Trail {
+                    team_id,
+                    name: team.name.clone(),
+                    colour: team.colour,
+                    locations: team.loaction_history.clone(),
+                })
+                .collect(),
+            events: self
+                .events()
+                .into_iter()
+                .map(|e| (event_timestamp(&e), e))
+                .collect(),
+        }
     }
 }
 
@@ -1292,6 +1474,7 @@ impl PastTeam {
                         zone: _,
                         points,
                         id,
+                        verification,
                     } => Some(CompletedChallenge {
                         title,
                         description,
@@ -1300,6 +1483,7 @@ impl PastTeam {
                         time: p.end_time.time().num_seconds_from_midnight(),
                         not_completed,
                         picture_ids: p.pictures.clone(),
+                        verification,
                     }),
                     _ => None,
                 })
@@ -1314,6 +1498,7 @@ impl PastTeam {
             }),
             grace_period_end: self.grace_period_end,
             period_id: self.periods.len(),
+            zone: None,
         })
     }
 }
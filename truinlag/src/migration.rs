@@ -0,0 +1,343 @@
+//! Versioned migrations of the documents in the database.
+//!
+//! The database stores the version of its schema in the single `MetaEntry`. When the engine
+//! starts, all migrations with a higher version are run in order. Each migration is applied in the
+//! same transaction that raises the version, so a failed migration leaves nothing behind.
+//! Databases from before the migrations existed have version 0.
+//!
+//! Migrations change the documents as `serde_json::Value`s, so that they don't have to be
+//! rewritten when the types of the collections change. To rename a field, give it a
+//! `#[serde(alias = "old_name")]` and add a migration that resaves the collection, after which
+//! the alias can be dropped once older databases no longer need to be supported.
+
+use crate::{
+    PastGameEntry, PictureEntry, PlayerEntry, SectorEntry, StationEntry, ZoneEntry,
+    challenge::{ChallengeEntry, ChallengeSetEntry},
+    session::Session,
+};
+use bonsaidb::{
+    core::{
+        schema::{Collection, SerializedCollection},
+        transaction::Transaction,
+    },
+    local::Database,
+};
+use log::info;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// Information about the database itself. There is only ever one entry, with id 0.
+#[derive(Debug, Clone, Serialize, Deserialize, Collection)]
+#[collection(name = "meta")]
+pub struct MetaEntry {
+    pub schema_version: u32,
+}
+
+/// A change to the documents of the database that brings it to `version`. The changes are added
+/// to the transaction instead of being applied right away.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(&Database, &mut Transaction) -> Result<(), bonsaidb::core::Error>,
+}
+
+/// All migrations, ordered by version. New migrations are appended at the end.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "write all documents in their current layout, filling in defaulted fields",
+        run: resave_everything,
+    },
+    Migration {
+        version: 2,
+        description: "move french challenge translations from \"de_fr\" to \"fr_ch\"",
+        run: fix_french_translation_key,
+    },
+];
+
+/// The schema version that this version of truinlag writes
+pub fn current_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Applies `change` to every document of a collection and adds the documents for which it
+/// returns `true` to the transaction. Returns the number of changed documents.
+///
+/// The documents are stored with pot, which can't be read into a `serde_json::Value` directly
+/// because it loses unit enum variants and integer map keys along the way. They are therefore
+/// converted through the collection's type, which fills in defaulted fields and follows aliases.
+fn transform<T>(
+    db: &Database,
+    transaction: &mut Transaction,
+    mut change: impl FnMut(&mut serde_json::Value) -> bool,
+) -> Result<usize, bonsaidb::core::Error>
+where
+    T: SerializedCollection<Contents = T, PrimaryKey = u64>,
+    T: Serialize + DeserializeOwned + Clone + 'static,
+{
+    let mut changed = 0;
+    for document in T::all(db).query()? {
+        let mut value = serde_json::to_value(&document.contents)
+            .map_err(|err| bonsaidb::core::Error::other("migration", err))?;
+        if change(&mut value) {
+            serde_json::from_value::<T>(value)
+                .map_err(|err| bonsaidb::core::Error::other("migration", err))?
+                .overwrite_in_transaction(&document.header.id, transaction)?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Loads every document of a collection and saves it in its current layout.
+fn resave<T>(db: &Database, transaction: &mut Transaction) -> Result<usize, bonsaidb::core::Error>
+where
+    T: SerializedCollection<Contents = T, PrimaryKey = u64> + Clone + 'static,
+{
+    let documents = T::all(db).query()?;
+    for document in &documents {
+        document
+            .contents
+            .overwrite_in_transaction(&document.header.id, transaction)?;
+    }
+    Ok(documents.len())
+}
+
+fn resave_everything(
+    db: &Database,
+    transaction: &mut Transaction,
+) -> Result<(), bonsaidb::core::Error> {
+    resave::<Session>(db, transaction)?;
+    resave::<PlayerEntry>(db, transaction)?;
+    resave::<ChallengeEntry>(db, transaction)?;
+    resave::<ChallengeSetEntry>(db, transaction)?;
+    resave::<ZoneEntry>(db, transaction)?;
+    resave::<SectorEntry>(db, transaction)?;
+    resave::<StationEntry>(db, transaction)?;
+    resave::<PastGameEntry>(db, transaction)?;
+    resave::<PictureEntry>(db, transaction)?;
+    Ok(())
+}
+
+/// The challenge import used to store french titles under "de_fr" instead of "fr_ch".
+fn fix_french_translation_key(
+    db: &Database,
+    transaction: &mut Transaction,
+) -> Result<(), bonsaidb::core::Error> {
+    let changed = transform::<ChallengeEntry>(db, transaction, |challenge| {
+        let Some(titles) = challenge
+            .get_mut("translated_titles")
+            .and_then(|titles| titles.as_object_mut())
+        else {
+            return false;
+        };
+        match titles.remove("de_fr") {
+            Some(title) => {
+                titles.entry("fr_ch").or_insert(title);
+                true
+            }
+            None => false,
+        }
+    })?;
+    info!("Engine: fixed the french titles of {} challenges", changed);
+    Ok(())
+}
+
+/// Brings the database to the current schema version by running all missing migrations.
+///
+/// # Panics
+///
+/// Panics if the database has a newer schema version than this version of truinlag knows, since
+/// running on it could destroy data.
+pub fn migrate(db: &Database) -> Result<(), bonsaidb::core::Error> {
+    let version = MetaEntry::get(&0_u64, db)?.map_or(0, |meta| meta.contents.schema_version);
+    if version > current_version() {
+        panic!(
+            "the database has schema version {}, but this truinlag only knows versions up to {}",
+            version,
+            current_version()
+        );
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        info!(
+            "Engine: migrating database to version {}: {}",
+            migration.version, migration.description
+        );
+        let mut transaction = Transaction::new();
+        (migration.run)(db, &mut transaction)?;
+        MetaEntry {
+            schema_version: migration.version,
+        }
+        .overwrite_in_transaction(&0_u64, &mut transaction)?;
+        transaction.apply(db)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EngineSchema;
+    use bonsaidb::core::{
+        document::DocumentId, schema::Collection, transmog::Format, transmog_pot::Pot,
+    };
+    use bonsaidb::local::config::{Builder, StorageConfiguration};
+    use libtruinlag::{ChallengeStatus, ChallengeType};
+    use std::collections::HashMap;
+
+    /// A zone as it was stored before travel bands, close sectors and boundaries existed
+    #[derive(Serialize)]
+    struct OldZoneEntry {
+        zone: u64,
+        num_conn_zones: u64,
+        num_connections: u64,
+        train_through: bool,
+        mongus: bool,
+        s_bahn_zone: bool,
+        minutes_to: HashMap<u64, u64>,
+    }
+
+    /// A challenge as it was stored before place coordinates and sectors existed, with its french
+    /// title under "de_fr"
+    #[derive(Serialize)]
+    struct OldChallengeEntry {
+        kind: ChallengeType,
+        sets: Vec<u64>,
+        status: ChallengeStatus,
+        title: Option<String>,
+        description: Option<String>,
+        random_place: Option<()>,
+        place: Option<String>,
+        comment: String,
+        kaffskala: Option<u8>,
+        grade: Option<u8>,
+        zone: Vec<u64>,
+        bias_sat: f32,
+        bias_sun: f32,
+        walking_time: u8,
+        stationary_time: u8,
+        additional_points: i16,
+        repetitions: std::ops::Range<u16>,
+        points_per_rep: i16,
+        station_distance: u16,
+        time_to_hb: u8,
+        departures: u8,
+        dead_end: bool,
+        no_disembark: bool,
+        fixed: bool,
+        in_perimeter_override: Option<bool>,
+        translated_titles: HashMap<String, String>,
+        translated_descriptions: HashMap<String, String>,
+        action: Option<()>,
+        last_edit: chrono::DateTime<chrono::Local>,
+    }
+
+    /// Stores `contents` with the given id without going through the collection's type
+    fn insert_raw<C: Collection>(db: &Database, id: u64, contents: &impl Serialize) {
+        let bytes = Format::serialize(&Pot::default(), contents).unwrap();
+        Transaction::insert(
+            C::collection_name(),
+            Some(DocumentId::new(&id).unwrap()),
+            bytes,
+        )
+        .apply(db)
+        .unwrap();
+    }
+
+    /// A database with version 0 and documents in the layout from before the migrations existed
+    fn old_database() -> Database {
+        let db =
+            Database::open::<EngineSchema>(StorageConfiguration::default().memory_only()).unwrap();
+        insert_raw::<ZoneEntry>(
+            &db,
+            1,
+            &OldZoneEntry {
+                zone: 110,
+                num_conn_zones: 3,
+                num_connections: 12,
+                train_through: true,
+                mongus: false,
+                s_bahn_zone: false,
+                minutes_to: HashMap::from([(1, 0), (2, 15)]),
+            },
+        );
+        insert_raw::<ChallengeEntry>(
+            &db,
+            1,
+            &OldChallengeEntry {
+                kind: ChallengeType::Kaff,
+                sets: vec![1],
+                status: ChallengeStatus::Approved,
+                title: Some("Chilbi".into()),
+                description: None,
+                random_place: None,
+                place: Some("Bauma".into()),
+                comment: String::new(),
+                kaffskala: Some(3),
+                grade: None,
+                zone: vec![1],
+                bias_sat: 1.0,
+                bias_sun: 1.0,
+                walking_time: 5,
+                stationary_time: 10,
+                additional_points: 0,
+                repetitions: 0..1,
+                points_per_rep: 0,
+                station_distance: 300,
+                time_to_hb: 40,
+                departures: 2,
+                dead_end: false,
+                no_disembark: false,
+                fixed: false,
+                in_perimeter_override: None,
+                translated_titles: HashMap::from([("de_fr".into(), "Kermesse".into())]),
+                translated_descriptions: HashMap::new(),
+                action: None,
+                last_edit: chrono::Local::now(),
+            },
+        );
+        db
+    }
+
+    fn revision<C>(db: &Database) -> u32
+    where
+        C: SerializedCollection<Contents = C, PrimaryKey = u64>,
+    {
+        C::get(&1, db).unwrap().unwrap().header.revision.id
+    }
+
+    fn schema_version(db: &Database) -> Option<u32> {
+        MetaEntry::get(&0_u64, db)
+            .unwrap()
+            .map(|meta| meta.contents.schema_version)
+    }
+
+    #[test]
+    fn migrates_old_documents() {
+        let db = old_database();
+        assert_eq!(schema_version(&db), None);
+        let zone_revision = revision::<ZoneEntry>(&db);
+        migrate(&db).unwrap();
+        assert_eq!(schema_version(&db), Some(current_version()));
+        let zone = ZoneEntry::get(&1, &db).unwrap().unwrap();
+        assert!(zone.header.revision.id > zone_revision);
+        assert_eq!(zone.contents.minutes_to.get(&2), Some(&15));
+        assert!(zone.contents.travel_bands.is_empty());
+        let challenge = ChallengeEntry::get(&1, &db).unwrap().unwrap().contents;
+        assert_eq!(
+            challenge.translated_titles,
+            HashMap::from([("fr_ch".to_string(), "Kermesse".to_string())])
+        );
+    }
+
+    #[test]
+    fn rerunning_changes_nothing() {
+        let db = old_database();
+        migrate(&db).unwrap();
+        let zone_revision = revision::<ZoneEntry>(&db);
+        let challenge_revision = revision::<ChallengeEntry>(&db);
+        migrate(&db).unwrap();
+        assert_eq!(schema_version(&db), Some(current_version()));
+        assert_eq!(revision::<ZoneEntry>(&db), zone_revision);
+        assert_eq!(revision::<ChallengeEntry>(&db), challenge_revision);
+    }
+}
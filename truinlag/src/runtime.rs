@@ -36,6 +36,7 @@ pub enum EngineSignal {
 }
 
 pub enum RuntimeRequest {
+    #[allow(dead_code)]
     CreateTimer {
         duration: Duration,
         payload: InternEngineCommand,
@@ -49,6 +50,9 @@ pub enum RuntimeRequest {
     // Similar to DelayedLoopback but not associated with a client.
     RawLoopback(JoinHandle<InternEngineCommand>),
     CancelTimer(u64),
+    /// A broadcast in addition to the one of the response, for when there is more than one thing
+    /// to tell the clients
+    Broadcast(Box<BroadcastAction>),
 }
 
 impl RuntimeRequest {
@@ -68,6 +72,7 @@ impl RuntimeRequest {
             } => *id,
             RuntimeRequest::RawLoopback(_) => 0,
             RuntimeRequest::CancelTimer(id) => *id,
+            RuntimeRequest::Broadcast(_) => 0,
         }
     }
 }
@@ -102,6 +107,15 @@ pub enum InternEngineCommand {
         player_id: u64,
         pfp: Result<PictureEntry, Error>,
     },
+    DeliverAnnouncement {
+        session_id: u64,
+        announcement_id: usize,
+    },
+    CatchConfirmationTimedOut {
+        session_id: u64,
+        proposal_id: usize,
+    },
+    ExportedDatabase(Result<String, commands::Error>),
 }
 
 #[derive(Clone, Debug)]
@@ -322,6 +336,7 @@ pub async fn manager() -> Result<()> {
 async fn handle_runtime_requests(
     requests: Option<Vec<RuntimeRequest>>,
     mpsc_sender: &mpsc::Sender<EngineSignal>,
+    broadcast_handle: &broadcast::Sender<IOSignal>,
 ) -> Vec<(Option<u64>, JoinHandle<()>)> {
     let mut handles = Vec::new();
     if let Some(requests) = requests {
@@ -383,12 +398,28 @@ async fn handle_runtime_requests(
                         Some(i) => i != &id,
                     });
                 }
+                RuntimeRequest::Broadcast(action) => {
+                    send_broadcast(*action, broadcast_handle).await
+                }
             }
         }
     }
     handles
 }
 
+async fn send_broadcast(action: BroadcastAction, broadcast_handle: &broadcast::Sender<IOSignal>) {
+    let message = IOSignal::Command(ClientCommand::Broadcast(Box::new(action)));
+    if broadcast_handle.is_full() {
+        warn!(
+            "Engine: broadcast full, {} receivers",
+            broadcast_handle.receiver_count()
+        )
+    }
+    if let Err(err) = broadcast_handle.broadcast_direct(message).await {
+        error!("{}: {}", SEND_ERROR, err);
+    };
+}
+
 async fn handle_intern_response(
     response: InternEngineResponsePackage,
     broadcast_handle: &broadcast::Sender<IOSignal>,
@@ -396,25 +427,17 @@ async fn handle_intern_response(
     mpsc_sender: mpsc::Sender<EngineSignal>,
     id: u64,
 ) -> Vec<(Option<u64>, JoinHandle<()>)> {
-    let mut handles = handle_runtime_requests(response.runtime_requests, &mpsc_sender).await;
+    let mut handles =
+        handle_runtime_requests(response.runtime_requests, &mpsc_sender, broadcast_handle).await;
     match response.response {
         InternEngineResponse::DirectResponse(response) => {
             if let Some(action) = response.broadcast_action {
-                let message = IOSignal::Command(ClientCommand::Broadcast(action));
-                if broadcast_handle.is_full() {
-                    warn!(
-                        "Engine: broadcast full, {} receivers",
-                        broadcast_handle.receiver_count()
-                    )
-                }
-                if let Err(err) = broadcast_handle.broadcast_direct(message).await {
-                    error!("{}: {}", SEND_ERROR, err);
-                };
+                send_broadcast(action, broadcast_handle).await;
             }
-            channel.send(IOSignal::Command(ClientCommand::Response(ResponsePackage {
+            channel.send(IOSignal::Command(ClientCommand::Response(Box::new(ResponsePackage {
                     action: response.response_action,
                     id
-                }))).unwrap_or_else(|_err| warn!("Engine: Couldn't send response to IO task, assuming client disconnect and continuing"));
+                })))).unwrap_or_else(|_err| warn!("Engine: Couldn't send response to IO task, assuming client disconnect and continuing"));
         }
         InternEngineResponse::DelayedLoopback(handle) => {
             let task = tokio::spawn(async move {
@@ -451,7 +474,12 @@ async fn engine(
     mpsc_sender: mpsc::Sender<EngineSignal>,
 ) -> Result<()> {
     let mut engine = tokio::task::block_in_place(|| engine::Engine::init(Path::new("truintabase")));
-    let mut handles = handle_runtime_requests(engine.setup().runtime_requests, &mpsc_sender).await;
+    let mut handles = handle_runtime_requests(
+        engine.setup().runtime_requests,
+        &mpsc_sender,
+        &broadcast_handle,
+    )
+    .await;
     loop {
         handles.retain(|(_, h)| !h.is_finished());
         match mpsc_handle
@@ -513,13 +541,30 @@ async fn engine(
                 break;
             }
             EngineSignal::RawLoopbackCommand(command) => {
+                // nobody is waiting for a response, but delivered announcements and catches that
+                // went through without confirmation still need to reach the clients
+                let forward = matches!(
+                    command,
+                    InternEngineCommand::DeliverAnnouncement { .. }
+                        | InternEngineCommand::CatchConfirmationTimedOut { .. }
+                );
+                let response = engine.vroom(command);
                 handles.append(
                     &mut handle_runtime_requests(
-                        engine.vroom(command).runtime_requests,
+                        response.runtime_requests,
                         &mpsc_sender,
+                        &broadcast_handle,
                     )
                     .await,
                 );
+                if forward
+                    && let InternEngineResponse::DirectResponse(EngineResponse {
+                        broadcast_action: Some(action),
+                        ..
+                    }) = response.response
+                {
+                    send_broadcast(action, &broadcast_handle).await;
+                }
             }
         };
     }
@@ -554,13 +599,14 @@ async fn io(
     use bytes::Bytes;
     use futures::SinkExt;
     use futures::prelude::*;
-    use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+    use libtruinlag::codec::FrameCodec;
+    use tokio_util::codec::{FramedRead, FramedWrite};
 
     async fn engine_parser(
         mut rx: mpsc::Receiver<IOSignal>,
         stream: net::unix::OwnedWriteHalf,
     ) -> Result<()> {
-        let mut transport = FramedWrite::new(stream, LengthDelimitedCodec::new());
+        let mut transport = FramedWrite::new(stream, FrameCodec::client_commands());
 
         loop {
             match rx.recv().await.ok_or(error::Error::IDontCareAnymore)? {
@@ -605,7 +651,7 @@ async fn io(
         recv_tx: mpsc::Sender<oneshot::Receiver<IOSignal>>,
         stream: net::unix::OwnedReadHalf,
     ) -> Result<()> {
-        let mut transport = FramedRead::new(stream, LengthDelimitedCodec::new());
+        let mut transport = FramedRead::new(stream, FrameCodec::commands());
 
         while let Some(message) = transport.next().await {
             match message {
@@ -2,7 +2,11 @@ use std::num::NonZeroU32;
 
 use crate::{
     Config, EngineContext, InGame, PartialConfig, PastGameEntry, PictureEntry, SessionContext,
+    catch::CatchProposalEntry,
     challenge::InOpenChallenge,
+    chat::{AnnouncementEntry, MAX_ANNOUNCEMENTS, MAX_MESSAGES, MessageEntry},
+    export::{GameMap, Trail, event_timestamp},
+    location::{self, SuspiciousLocationEntry},
     runtime::{
         InternEngineCommand, InternEngineResponse, InternEngineResponsePackage,
         InternEngineResponseResult, RuntimeRequest,
@@ -11,7 +15,6 @@ use crate::{
 };
 use bonsaidb::core::schema::Collection;
 use chrono::Timelike;
-use geo::Distance;
 use libtruinlag::{
     commands::{BroadcastAction::*, EngineAction::*, Error::*, ResponseAction::*, *},
     *,
@@ -35,6 +38,20 @@ pub struct Session {
     pub discord_game_channel: Option<u64>,
     pub discord_admin_channel: Option<u64>,
     pub game: Option<InGame>,
+    #[serde(default)]
+    pub messages: Vec<MessageEntry>,
+    /// The number of messages that were dropped to stay below `MAX_MESSAGES`. Message ids keep
+    /// counting from the first message ever sent.
+    #[serde(default)]
+    pub dropped_messages: usize,
+    #[serde(default)]
+    pub announcements: Vec<AnnouncementEntry>,
+    /// The number of announcements that were dropped to stay below `MAX_ANNOUNCEMENTS`, like
+    /// `dropped_messages`
+    #[serde(default)]
+    pub dropped_announcements: usize,
+    #[serde(default)]
+    pub catch_proposals: Vec<CatchProposalEntry>,
 }
 
 impl Session {
@@ -60,25 +77,37 @@ impl Session {
             discord_game_channel: None,
             discord_admin_channel: None,
             game: None,
+            messages: Vec::new(),
+            dropped_messages: 0,
+            announcements: Vec::new(),
+            dropped_announcements: 0,
+            catch_proposals: Vec::new(),
         }
     }
 
     /// Gets called by the engine on startup and returns runtime requests for all timers that need
     /// to be restarted.
     pub fn setup(&self) -> Vec<RuntimeRequest> {
-        match &self.game {
-            None => Vec::new(),
-            Some(game) => {
-                let mut requests = Vec::new();
-                requests.push(game.timer.create_request());
-                for team in &self.teams {
-                    if let Some(hook) = &team.grace_period_end {
-                        requests.push(hook.create_request());
-                    }
+        // announcements can be scheduled whether or not a game is running
+        let mut requests: Vec<RuntimeRequest> = self
+            .announcements
+            .iter()
+            .filter_map(|a| a.delivery.as_ref().map(|hook| hook.create_request()))
+            .collect();
+        if let Some(game) = &self.game {
+            requests.push(game.timer.create_request());
+            for team in &self.teams {
+                if let Some(hook) = &team.grace_period_end {
+                    requests.push(hook.create_request());
+                }
+            }
+            for proposal in &self.catch_proposals {
+                if let Some(hook) = &proposal.timeout {
+                    requests.push(hook.create_request());
                 }
-                requests
             }
         }
+        requests
     }
 
     /// Converts the engine-internal session to a truinlag session
@@ -151,10 +180,13 @@ impl Session {
                     team.challenges.push(InOpenChallenge {
                         title: challenge.title,
                         description: challenge.description,
+                        translated_titles: Default::default(),
+                        translated_descriptions: Default::default(),
                         points: challenge.points,
                         action: None,
                         zone: None,
                         sector: None,
+                        place_coordinates: None,
                         id: 0,
                     });
                     Success.into()
@@ -232,38 +264,112 @@ impl Session {
             // app to stop sending locations at those times. Yeah, that'll be a TODO
             return Ok(Success.into());
         }
+        let Some((team_id, team)) = self
+            .teams
+            .iter_mut()
+            .enumerate()
+            .find(|(_, t)| t.players.contains(&player_id))
+        else {
+            return Ok(Error(NotFound(format!(
+                "team with the player with id {}",
+                player_id
+            )))
+            .into());
+        };
         let player = context.engine_context.player_db.get_mut(player_id)?;
         if let Some(last_loc) = &player.contents.last_location
             && last_loc == &location.clone().into()
         {
             return Ok(Success.into());
         }
+        if let Some(suspicion) = location::check_plausibility(
+            player.contents.last_location.as_ref(),
+            &location,
+            &context.config,
+        ) {
+            team.suspicious_locations.push(SuspiciousLocationEntry {
+                player: player_id,
+                location: location.clone(),
+                suspicion,
+                received: chrono::Local::now(),
+            });
+            if team.suspicious_locations.len() > location::MAX_SUSPICIOUS_LOCATIONS {
+                team.suspicious_locations.remove(0);
+            }
+            if location::should_reanchor(
+                &team.suspicious_locations,
+                player_id,
+                player.contents.last_location.as_ref(),
+                &context.config,
+            ) {
+                warn!(
+                    "engine: session: player {} keeps sending consistent locations far from \
+                    the last accepted one, accepting them",
+                    player_id
+                );
+            } else {
+                // like above, the app shouldn't show an error for this, the location is just
+                // ignored
+                warn!(
+                    "engine: session: ignoring suspicious location of player {}: {:?}",
+                    player_id, suspicion
+                );
+                return Ok(Success.into());
+            }
+        }
         player.contents.last_location = Some(location.clone().into());
-        Ok(
-            match self
-                .teams
-                .iter_mut()
-                .enumerate()
-                .find(|(_, t)| t.players.contains(&player_id))
+        let (location, new_splits) = team.add_location(location, player_id, &context.config);
+        // the team's location only changes if the player's location was used for it
+        if let Some(location) = &location {
+            if let Some(zone_id) = location::zone_containing(
+                location,
+                team.current_zone_id,
+                context.engine_context.zone_db,
+            ) && zone_id != team.current_zone_id
             {
-                None => Error(NotFound(format!(
-                    "team with the player with id {}",
-                    player_id
-                )))
-                .into(),
-                Some((team_id, team)) => match team.add_location(location, player_id) {
-                    Some(location) => EngineResponse {
-                        response_action: Success,
-                        broadcast_action: Some(Location {
-                            team: team_id,
-                            location,
-                        }),
-                    }
-                    .into(),
-                    None => Success.into(),
-                },
-            },
-        )
+                // the sector was only known from a challenge in the old zone
+                team.current_zone_id = zone_id;
+                team.current_sector_id = None;
+            }
+            let sectors = context.engine_context.sector_db;
+            match location::sector_containing(location, team.current_sector_id, sectors) {
+                Some(sector_id) => team.current_sector_id = Some(sector_id),
+                // sectors without an outline can only be left by changing zones
+                None if team.current_sector_id.is_some_and(|id| {
+                    sectors.get(id).is_ok_and(|s| s.contents.boundary.is_some())
+                }) =>
+                {
+                    team.current_sector_id = None
+                }
+                None => (),
+            }
+        }
+        let splits = new_splits
+            .into_iter()
+            .map(|(player, distance)| {
+                warn!(
+                    "engine: session: player {} got separated from team {} by {:.0} m",
+                    player, team.name, distance
+                );
+                RuntimeRequest::Broadcast(Box::new(TeamSplit {
+                    session: context.session_id,
+                    team: team_id,
+                    player,
+                    distance: distance as u32,
+                }))
+            })
+            .collect();
+        Ok(InternEngineResponsePackage {
+            response: EngineResponse {
+                response_action: Success,
+                broadcast_action: location.map(|location| Location {
+                    team: team_id,
+                    location,
+                }),
+            }
+            .into(),
+            runtime_requests: Some(splits),
+        })
     }
 
     /// Corresponds to an `EngineAction` and assigns a player to a team
@@ -316,7 +422,10 @@ impl Session {
         }
     }
 
-    /// Corresponds to an `EngineAction` and processes one team catching another
+    /// Corresponds to an `EngineAction` and processes one team catching another.
+    ///
+    /// If catch confirmation is turned on, the catch is only proposed and goes through once the
+    /// caught team confirms it, the organisers uphold a dispute or the confirmation times out.
     pub fn catch(
         &mut self,
         catcher_id: usize,
@@ -324,111 +433,438 @@ impl Session {
         period_id: usize,
         context: &mut SessionContext,
     ) -> InternEngineResponsePackage {
-        match self.game {
-            Some(_) => {
-                let catcher_period_id;
-                if caught_id == catcher_id {
-                    return Error(BadData("a team cannot catch itself".into())).into();
-                }
-                let broadcast;
-                let mut runtime_requests = Vec::new();
-                match self.teams.get(catcher_id) {
-                    Some(catcher_team) => {
-                        if period_id != catcher_team.period_id() {
-                            return Error(BadData(format!(
-                                "period_id {} is invalid, should be {}",
-                                period_id,
-                                catcher_team.period_id()
-                            )))
-                            .into();
-                        }
-                        match catcher_team.role {
-                            TeamRole::Catcher => match self.teams.get(caught_id) {
-                                Some(caught_team) => {
-                                    catcher_period_id = catcher_team.period_id();
-                                    match caught_team.role {
-                                        TeamRole::Runner => {
-                                            if caught_team.location().is_some()
-                                                && catcher_team.location().is_some()
-                                            {
-                                                let caught_location =
-                                                    caught_team.location_as_point().unwrap();
-                                                let catcher_location =
-                                                    catcher_team.location_as_point().unwrap();
-                                                if geo::Geodesic
-                                                    .distance(caught_location, catcher_location)
-                                                    > 300.0
-                                                {
-                                                    return Error(TeamsTooFar).into();
-                                                }
-                                            } else {
-                                                warn!(
-                                                    "engine: session: no location for team {} or {}",
-                                                    catcher_team.name, caught_team.name
-                                                )
-                                            }
-                                            broadcast = Caught {
-                                                catcher: catcher_team
-                                                    .to_sendable(catcher_id, context),
-                                                caught: caught_team.to_sendable(caught_id, context),
-                                            };
-                                        }
-                                        TeamRole::Catcher => {
-                                            return Error(TeamIsCatcher(caught_id)).into();
-                                        }
-                                    }
-                                }
-                                None => {
-                                    return Error(NotFound(format!(
-                                        "caught team with id {}",
-                                        caught_id
-                                    )))
-                                    .into();
-                                }
-                            },
-                            TeamRole::Runner => return Error(TeamIsRunner(catcher_id)).into(),
-                        }
-                    }
-                    None => {
-                        return Error(NotFound(format!("catcher team with id {}", catcher_id)))
-                            .into();
-                    }
-                };
-                let caught_info;
-                match self.teams.get_mut(caught_id) {
-                    Some(caught_team) => {
-                        caught_info = caught_team.clone();
-                        caught_team.be_caught(catcher_id, catcher_period_id);
-                    }
-                    None => {
-                        return Error(NotFound(format!("caught team with id {}", caught_id)))
-                            .into();
-                    }
-                }
-                match self.teams.get_mut(catcher_id) {
-                    Some(catcher_team) => {
-                        runtime_requests.push(catcher_team.have_caught(
-                            caught_id,
-                            catcher_id,
-                            context,
-                            caught_info,
-                        ));
-                    }
+        if let Err(err) = self.validate_catch(catcher_id, caught_id, Some(period_id)) {
+            return Error(err).into();
+        }
+        let needs_confirmation =
+            match self.check_catch_distance(catcher_id, caught_id, &context.config) {
+                Ok(needs_confirmation) => needs_confirmation,
+                Err(err) => return Error(err).into(),
+            };
+        if context.config.catch_confirmation || needs_confirmation {
+            self.propose_catch(catcher_id, caught_id, period_id, context)
+        } else {
+            self.execute_catch(catcher_id, caught_id, context).0
+        }
+    }
+
+    /// Checks whether a team can currently catch another team, apart from their distance. The
+    /// period id of the catcher is only checked if one is given.
+    fn validate_catch(
+        &self,
+        catcher_id: usize,
+        caught_id: usize,
+        period_id: Option<usize>,
+    ) -> Result<(), commands::Error> {
+        if self.game.is_none() {
+            return Err(GameNotRunning);
+        }
+        if caught_id == catcher_id {
+            return Err(BadData("a team cannot catch itself".into()));
+        }
+        let catcher_team = self
+            .teams
+            .get(catcher_id)
+            .ok_or(NotFound(format!("catcher team with id {}", catcher_id)))?;
+        if let Some(period_id) = period_id
+            && period_id != catcher_team.period_id()
+        {
+            return Err(BadData(format!(
+                "period_id {} is invalid, should be {}",
+                period_id,
+                catcher_team.period_id()
+            )));
+        }
+        if let TeamRole::Runner = catcher_team.role {
+            return Err(TeamIsRunner(catcher_id));
+        }
+        let caught_team = self
+            .teams
+            .get(caught_id)
+            .ok_or(NotFound(format!("caught team with id {}", caught_id)))?;
+        if let TeamRole::Catcher = caught_team.role {
+            return Err(TeamIsCatcher(caught_id));
+        }
+        Ok(())
+    }
+
+    /// Checks whether two (already validated) teams are close enough to each other for a catch.
+    ///
+    /// If the location of one of the teams is missing or stale, the location policy in the config
+    /// decides what happens. `Ok(true)` means that the catch may only go through once the caught
+    /// team confirms it.
+    fn check_catch_distance(
+        &self,
+        catcher_id: usize,
+        caught_id: usize,
+        config: &Config,
+    ) -> Result<bool, commands::Error> {
+        let catcher_team = &self.teams[catcher_id];
+        let caught_team = &self.teams[caught_id];
+        let now = chrono::Local::now().timestamp();
+        let reason = match (
+            &catcher_team.current_location,
+            &caught_team.current_location,
+        ) {
+            (None, _) => format!("there is no location for team {}", catcher_team.name),
+            (_, None) => format!("there is no location for team {}", caught_team.name),
+            (Some(catcher_location), Some(caught_location)) => {
+                let stale = [
+                    (catcher_team, catcher_location),
+                    (caught_team, caught_location),
+                ]
+                .into_iter()
+                .find(|(_, location)| {
+                    now - location.timestamp > config.max_location_age.num_seconds()
+                });
+                match stale {
+                    Some((team, location)) => format!(
+                        "the location of team {} is {} seconds old",
+                        team.name,
+                        now - location.timestamp
+                    ),
                     None => {
-                        return Error(NotFound(format!("catcher team with id {}", catcher_id)))
-                            .into();
-                    }
-                }
-                InternEngineResponsePackage {
-                    response: EngineResponse {
-                        response_action: Period(catcher_period_id),
-                        broadcast_action: Some(broadcast),
+                        let distance = MinimalLocation::from(catcher_location.clone())
+                            .distance_to(&caught_location.clone().into());
+                        let margin = (catcher_location.accuracy as f64
+                            + caught_location.accuracy as f64)
+                            .min(config.catch_accuracy_margin);
+                        let allowed = config.catch_radius + margin;
+                        if distance > allowed {
+                            return Err(TeamsTooFar {
+                                distance: Some(distance as u32),
+                                allowed: allowed as u32,
+                                reason: format!(
+                                    "the teams are {:.0} m apart, but at most {:.0} m are allowed",
+                                    distance, allowed
+                                ),
+                            });
+                        }
+                        return Ok(false);
                     }
-                    .into(),
-                    runtime_requests: Some(runtime_requests),
                 }
             }
-            None => Error(GameNotRunning).into(),
+        };
+        warn!("engine: session: {}", reason);
+        match config.location_policy {
+            LocationPolicy::Allow => Ok(false),
+            LocationPolicy::RequireConfirmation => Ok(true),
+            LocationPolicy::Reject => Err(TeamsTooFar {
+                distance: None,
+                allowed: config.catch_radius as u32,
+                reason,
+            }),
+        }
+    }
+
+    /// Carries out a catch that has already been validated. The response contains the new period
+    /// of the catcher, the new period of the caught team is returned alongside it.
+    fn execute_catch(
+        &mut self,
+        catcher_id: usize,
+        caught_id: usize,
+        context: &mut SessionContext,
+    ) -> (InternEngineResponsePackage, usize) {
+        let catcher_period_id = self.teams[catcher_id].period_id();
+        let caught_period_id = self.teams[caught_id].period_id();
+        let broadcast = Caught {
+            catcher: self.teams[catcher_id].to_sendable(catcher_id, context),
+            caught: self.teams[caught_id].to_sendable(caught_id, context),
+        };
+        let caught_team = &mut self.teams[caught_id];
+        let caught_info = caught_team.clone();
+        caught_team.be_caught(catcher_id, catcher_period_id);
+        let request =
+            self.teams[catcher_id].have_caught(caught_id, catcher_id, context, caught_info);
+        let response = InternEngineResponsePackage {
+            response: EngineResponse {
+                response_action: Period(catcher_period_id),
+                broadcast_action: Some(broadcast),
+            }
+            .into(),
+            runtime_requests: Some(vec![request]),
+        };
+        (response, caught_period_id)
+    }
+
+    /// Creates a catch proposal that the caught team has to confirm
+    fn propose_catch(
+        &mut self,
+        catcher_id: usize,
+        caught_id: usize,
+        period_id: usize,
+        context: &mut SessionContext,
+    ) -> InternEngineResponsePackage {
+        if self
+            .catch_proposals
+            .iter()
+            .any(|p| p.is_open() && p.caught == caught_id)
+        {
+            return Error(AlreadyExists).into();
+        }
+        let proposal_id = self.catch_proposals.len();
+        let now = chrono::Local::now();
+        let deadline = now + context.config.catch_confirmation_timeout;
+        let (request, timeout) = context.engine_context.timer_tracker.alarm(
+            deadline,
+            InternEngineCommand::CatchConfirmationTimedOut {
+                session_id: context.session_id,
+                proposal_id,
+            },
+        );
+        let proposal = CatchProposalEntry {
+            catcher: catcher_id,
+            caught: caught_id,
+            catcher_period_id: period_id,
+            status: CatchStatus::Pending,
+            time: now,
+            deadline,
+            timeout: Some(timeout),
+            dispute_reason: None,
+        };
+        let broadcast = CatchProposed {
+            session: context.session_id,
+            proposal: proposal.to_sendable(proposal_id),
+        };
+        self.catch_proposals.push(proposal);
+        info!(
+            "team {} proposed catching team {} in session {}",
+            catcher_id, caught_id, context.session_id
+        );
+        InternEngineResponsePackage {
+            response: EngineResponse {
+                response_action: CatchPending(proposal_id),
+                broadcast_action: Some(broadcast),
+            }
+            .into(),
+            runtime_requests: Some(vec![request]),
+        }
+    }
+
+    /// Carries out a proposed catch and sets the proposal's status. If the catch is no longer
+    /// possible, the proposal is voided instead and the caller gets the reason as an error. Like
+    /// `execute_catch`, the new period of the caught team is returned alongside the response.
+    fn settle_catch_proposal(
+        &mut self,
+        proposal_id: usize,
+        status: CatchStatus,
+        context: &mut SessionContext,
+    ) -> Result<(InternEngineResponsePackage, usize), commands::Error> {
+        let proposal = &self.catch_proposals[proposal_id];
+        let (catcher_id, caught_id) = (proposal.catcher, proposal.caught);
+        let mut requests: Vec<RuntimeRequest> = proposal
+            .timeout
+            .iter()
+            .map(|t| t.cancel_request())
+            .collect();
+        // the distance was already checked when the catch was proposed. Disputes can take a
+        // while, during which the catcher may have moved on to a new period. The catch happened
+        // back in the period it was proposed in, so only the roles have to still be right.
+        let period_id = match status {
+            CatchStatus::Upheld => None,
+            _ => Some(proposal.catcher_period_id),
+        };
+        let validation = self.validate_catch(catcher_id, caught_id, period_id);
+        let proposal = &mut self.catch_proposals[proposal_id];
+        proposal.timeout = None;
+        if let Err(err) = validation {
+            proposal.status = CatchStatus::Void;
+            return Err(err);
+        }
+        proposal.status = status;
+        let (mut response, caught_period_id) = self.execute_catch(catcher_id, caught_id, context);
+        if let Some(mut catch_requests) = response.runtime_requests.take() {
+            requests.append(&mut catch_requests);
+        }
+        response.runtime_requests = Some(requests);
+        Ok((response, caught_period_id))
+    }
+
+    /// Returns the proposal with the given id if it is open and the player is in the caught team
+    fn open_proposal_of_caught_player(
+        &self,
+        proposal_id: usize,
+        player_id: u64,
+    ) -> Result<&CatchProposalEntry, commands::Error> {
+        let proposal = self
+            .catch_proposals
+            .get(proposal_id)
+            .ok_or(NotFound(format!("catch proposal with id {}", proposal_id)))?;
+        if !self
+            .teams
+            .get(proposal.caught)
+            .is_some_and(|t| t.players.contains(&player_id))
+        {
+            return Err(BadData(
+                "only the caught team can respond to a catch".into(),
+            ));
+        }
+        if proposal.status != CatchStatus::Pending {
+            return Err(BadData(format!(
+                "the catch is not pending anymore, it is {:?}",
+                proposal.status
+            )));
+        }
+        Ok(proposal)
+    }
+
+    /// Corresponds to an `EngineAction` and lets the caught team confirm a proposed catch
+    pub fn confirm_catch(
+        &mut self,
+        player_id: u64,
+        proposal_id: usize,
+        context: &mut SessionContext,
+    ) -> InternEngineResponseResult {
+        self.open_proposal_of_caught_player(proposal_id, player_id)?;
+        let (mut response, caught_period_id) =
+            self.settle_catch_proposal(proposal_id, CatchStatus::Confirmed, context)?;
+        // the response goes to the caught team, so it should contain their new period
+        if let InternEngineResponse::DirectResponse(response) = &mut response.response {
+            response.response_action = Period(caught_period_id);
+        }
+        Ok(response)
+    }
+
+    /// Corresponds to an `EngineAction` and lets the caught team dispute a proposed catch, which
+    /// puts it into the organisers' queue
+    pub fn dispute_catch(
+        &mut self,
+        player_id: u64,
+        proposal_id: usize,
+        reason: String,
+        session_id: u64,
+    ) -> InternEngineResponseResult {
+        let request = self
+            .open_proposal_of_caught_player(proposal_id, player_id)?
+            .timeout
+            .as_ref()
+            .map(|t| t.cancel_request());
+        let proposal = &mut self.catch_proposals[proposal_id];
+        proposal.status = CatchStatus::Disputed;
+        proposal.timeout = None;
+        proposal.dispute_reason = Some(reason);
+        info!(
+            "team {} disputed being caught by team {} in session {}",
+            proposal.caught, proposal.catcher, session_id
+        );
+        Ok(InternEngineResponsePackage {
+            response: EngineResponse {
+                response_action: Success,
+                broadcast_action: Some(CatchProposalUpdated {
+                    session: session_id,
+                    proposal: proposal.to_sendable(proposal_id),
+                }),
+            }
+            .into(),
+            runtime_requests: request.map(|r| vec![r]),
+        })
+    }
+
+    /// Gets called from a timer and makes a proposed catch go through if the caught team hasn't
+    /// reacted to it
+    pub fn catch_confirmation_timed_out(
+        &mut self,
+        proposal_id: usize,
+        context: &mut SessionContext,
+    ) -> InternEngineResponsePackage {
+        if !self
+            .catch_proposals
+            .get(proposal_id)
+            .is_some_and(|p| p.status == CatchStatus::Pending)
+        {
+            return Success.into(); // do nothing
+        }
+        match self.settle_catch_proposal(proposal_id, CatchStatus::TimedOut, context) {
+            Ok((response, _)) => response,
+            Err(err) => {
+                warn!(
+                    "engine: session: catch proposal {} timed out, but couldn't go through: {}",
+                    proposal_id, err
+                );
+                Success.into()
+            }
+        }
+    }
+
+    /// Corresponds to an `EngineAction` and returns all catches that have been disputed and not
+    /// yet resolved by the organisers
+    pub fn get_disputed_catches(&self) -> InternEngineResponsePackage {
+        SendCatchProposals(
+            self.catch_proposals
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.status == CatchStatus::Disputed)
+                .map(|(id, p)| p.to_sendable(id))
+                .collect(),
+        )
+        .into()
+    }
+
+    /// Corresponds to an `EngineAction` and returns all locations that were ignored because they
+    /// seemed implausible, oldest first
+    pub fn get_suspicious_locations(&self) -> InternEngineResponsePackage {
+        let mut locations: Vec<SuspiciousLocation> = self
+            .teams
+            .iter()
+            .enumerate()
+            .flat_map(|(id, t)| {
+                t.suspicious_locations
+                    .iter()
+                    .map(move |l| l.to_sendable(id))
+            })
+            .collect();
+        locations.sort_by_key(|l| l.received);
+        SendSuspiciousLocations(locations).into()
+    }
+
+    /// Corresponds to an `EngineAction` and returns how well the location tracking of each player
+    /// works
+    pub fn get_location_quality(&self) -> InternEngineResponsePackage {
+        SendLocationQuality(
+            self.teams
+                .iter()
+                .enumerate()
+                .flat_map(|(id, team)| {
+                    team.players
+                        .iter()
+                        .map(move |player| team.location_quality(id, *player))
+                })
+                .collect(),
+        )
+        .into()
+    }
+
+    /// Corresponds to an `EngineAction` and lets the organisers decide on a disputed catch
+    pub fn resolve_disputed_catch(
+        &mut self,
+        proposal_id: usize,
+        uphold: bool,
+        context: &mut SessionContext,
+    ) -> InternEngineResponseResult {
+        match self.catch_proposals.get(proposal_id) {
+            None => return Err(NotFound(format!("catch proposal with id {}", proposal_id))),
+            Some(proposal) if proposal.status != CatchStatus::Disputed => {
+                return Err(BadData(format!(
+                    "the catch is not disputed, it is {:?}",
+                    proposal.status
+                )));
+            }
+            Some(_) => (),
+        }
+        if uphold {
+            self.settle_catch_proposal(proposal_id, CatchStatus::Upheld, context)
+                .map(|(response, _)| response)
+        } else {
+            let proposal = &mut self.catch_proposals[proposal_id];
+            proposal.status = CatchStatus::Rejected;
+            Ok(EngineResponse {
+                response_action: Success,
+                broadcast_action: Some(CatchProposalUpdated {
+                    session: context.session_id,
+                    proposal: proposal.to_sendable(proposal_id),
+                }),
+            }
+            .into())
         }
     }
 
@@ -471,6 +907,7 @@ impl Session {
                                 broadcast_action: Some(BroadcastAction::Completed {
                                     completer: completer_team.to_sendable(completer, context),
                                     completed: completed.to_sendable(),
+                                    translations: completed.translations(),
                                 }),
                             }),
                             runtime_requests: request.map(|r| vec![r]),
@@ -485,13 +922,18 @@ impl Session {
     }
 
     /// Corresponds to an `EngineAction` and returns the current session state
-    pub fn get_state(&self, context: &SessionContext) -> InternEngineResponsePackage {
+    /// Sends the state of the session, with the challenges in `language` where possible
+    pub fn get_state(
+        &self,
+        context: &SessionContext,
+        language: Option<&str>,
+    ) -> InternEngineResponsePackage {
         SendState {
             teams: self
                 .teams
                 .iter()
                 .enumerate()
-                .map(|(i, t)| t.to_sendable(i, context))
+                .map(|(i, t)| t.to_localised_sendable(i, context, language))
                 .collect(),
             events: self.gather_events(),
             game: self.game.clone().map(|g| g.to_sendable()),
@@ -672,9 +1114,21 @@ impl Session {
                 .collect(),
         );
 
+        // open catch proposals can't go through anymore
+        for proposal in &mut self.catch_proposals {
+            if proposal.is_open() {
+                proposal.status = CatchStatus::Void;
+                if let Some(timer) = proposal.timeout.take() {
+                    requests.push(timer.cancel_request());
+                }
+            }
+        }
+
         // extract and save past game
         let past_game = PastGameEntry::new_now(self.game.take().unwrap(), self.teams.clone());
         context.engine_context.past_game_db.add(past_game);
+        // the player stats include the new past game now
+        *context.engine_context.player_stats = None;
 
         // reset teams
         for team in &mut self.teams {
@@ -715,6 +1169,7 @@ impl Session {
                         points,
                         id,
                         not_completed,
+                        verification: _,
                     } => Some(Event::Complete {
                         challenge: Challenge {
                             title: title.clone(),
@@ -766,6 +1221,43 @@ impl Session {
         events
     }
 
+    /// Compiles the team trails and all events into a `GameMap` for exporting
+    fn game_map(&self) -> GameMap {
+        GameMap {
+            name: self
+                .game
+                .as_ref()
+                .map(|g| g.name.clone())
+                .unwrap_or(self.name.clone()),
+            trails: self
+                .teams
+                .iter()
+                .enumerate()
+                .map(|(team_id, team)| Trail {
+                    team_id,
+                    name: team.name.clone(),
+                    colour: team.colour,
+                    locations: team.locations.clone(),
+                })
+                .collect(),
+            events: self
+                .gather_events()
+                .into_iter()
+                .map(|e| (event_timestamp(&e), e))
+                .collect(),
+        }
+    }
+
+    /// Corresponds to an `EngineAction` and exports the team trails and events as GeoJSON,
+    /// optionally limited to a time window
+    pub fn export_geojson(
+        &self,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> InternEngineResponsePackage {
+        SendGeoJson(self.game_map().within(since, until).to_geojson()).into()
+    }
+
     /// Corresponds to an `EngineAction` and returns all events in a list ordered by time
     pub fn get_events(&self) -> InternEngineResponsePackage {
         SendEvents(self.gather_events()).into()
@@ -916,6 +1408,267 @@ impl Session {
 
     pub fn remove_team(&mut self, team_id: usize) -> InternEngineResponsePackage {
         self.teams.remove(team_id);
+        // messages and announcements refer to teams by their index
+        for message in &mut self.messages {
+            message.team_removed(team_id);
+        }
+        for announcement in &mut self.announcements {
+            announcement.team_removed(team_id);
+        }
         Success.into()
     }
+
+    /// Corresponds to an `EngineAction` and sends a chat message.
+    ///
+    /// Messages without an author come from the organisers and may be addressed to anyone.
+    /// Players may only write to their own team, to everyone, to the organisers and, if their
+    /// team is catcher, to the catchers. The author's team always receives its own messages.
+    pub fn send_message(
+        &mut self,
+        author: Option<u64>,
+        scope: MessageScope,
+        text: String,
+        pictures: Vec<RawPicture>,
+        context: SessionContext,
+    ) -> InternEngineResponseResult {
+        let text = text.trim().to_string();
+        if text.is_empty() && pictures.is_empty() {
+            return Err(BadData("a message needs a text or pictures".into()));
+        }
+        let team = match author {
+            None => None,
+            Some(player_id) => Some(
+                self.teams
+                    .iter()
+                    .position(|t| t.players.contains(&player_id))
+                    .ok_or(NotFound(format!(
+                        "team with the player with id {}",
+                        player_id
+                    )))?,
+            ),
+        };
+        match scope {
+            MessageScope::Team(team_id) => {
+                if team_id >= self.teams.len() {
+                    return Err(NotFound(format!("team with id {team_id}")));
+                }
+                if team.is_some_and(|t| t != team_id) {
+                    return Err(BadData("players can only message their own team".into()));
+                }
+            }
+            MessageScope::Catchers => {
+                if let Some(team) = team
+                    && matches!(self.teams[team].role, TeamRole::Runner)
+                {
+                    return Err(TeamIsRunner(team));
+                }
+            }
+            MessageScope::All | MessageScope::Organisers => (),
+        }
+        let mut recipients = self.scope_recipients(scope);
+        if let Some(team) = team
+            && !recipients.contains(&team)
+        {
+            recipients.push(team);
+        }
+        let pictures = pictures
+            .into_iter()
+            .map(|picture| {
+                context
+                    .engine_context
+                    .picture_db
+                    .add(PictureEntry::new_challenge_picture(picture))
+            })
+            .collect();
+        let message = MessageEntry {
+            author,
+            team,
+            scope,
+            recipients: recipients.clone(),
+            text,
+            pictures,
+            time: chrono::Local::now(),
+        };
+        let id = self.dropped_messages + self.messages.len();
+        let sendable = message.to_sendable(id);
+        self.messages.push(message);
+        if self.messages.len() > MAX_MESSAGES {
+            let excess = self.messages.len() - MAX_MESSAGES;
+            self.messages.drain(..excess);
+            self.dropped_messages += excess;
+        }
+        trace!(
+            "message {} sent in session {} to teams {:?}",
+            id, context.session_id, recipients
+        );
+        Ok(EngineResponse {
+            response_action: Success,
+            broadcast_action: Some(BroadcastAction::Message {
+                session: context.session_id,
+                recipients,
+                message: sendable,
+            }),
+        }
+        .into())
+    }
+
+    /// Returns the indices of the teams that a message or announcement to the given scope goes to
+    fn scope_recipients(&self, scope: MessageScope) -> Vec<usize> {
+        match scope {
+            MessageScope::Team(team_id) => vec![team_id],
+            MessageScope::Catchers => self
+                .teams
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| matches!(t.role, TeamRole::Catcher))
+                .map(|(index, _)| index)
+                .collect(),
+            MessageScope::All => (0..self.teams.len()).collect(),
+            MessageScope::Organisers => Vec::new(),
+        }
+    }
+
+    /// Corresponds to an `EngineAction` and returns all messages that the given team can read. If
+    /// no team is given, all messages are returned.
+    pub fn get_messages(&self, team: Option<usize>) -> InternEngineResponsePackage {
+        SendMessages(
+            self.messages
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| message.is_visible_to(team))
+                .map(|(index, message)| message.to_sendable(self.dropped_messages + index))
+                .collect(),
+        )
+        .into()
+    }
+
+    /// Corresponds to an `EngineAction` and schedules an announcement. If no time is given, or if
+    /// the time has already passed, the announcement is delivered right away.
+    pub fn schedule_announcement(
+        &mut self,
+        target: MessageScope,
+        text: String,
+        time: Option<i64>,
+        context: SessionContext,
+    ) -> InternEngineResponseResult {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Err(BadData("an announcement needs a text".into()));
+        }
+        match target {
+            MessageScope::Team(team_id) if team_id >= self.teams.len() => {
+                return Err(NotFound(format!("team with id {team_id}")));
+            }
+            MessageScope::Organisers => {
+                return Err(BadData(
+                    "announcements cannot be sent to the organisers".into(),
+                ));
+            }
+            _ => (),
+        }
+        let time = match time {
+            None => chrono::Local::now(),
+            Some(time) => chrono::DateTime::from_timestamp(time, 0)
+                .ok_or(BadData(format!("{} is not a valid timestamp", time)))?
+                .with_timezone(&chrono::Local),
+        };
+        let announcement_id = self.dropped_announcements + self.announcements.len();
+        let (request, hook) = context.engine_context.timer_tracker.alarm(
+            time,
+            InternEngineCommand::DeliverAnnouncement {
+                session_id: context.session_id,
+                announcement_id,
+            },
+        );
+        self.announcements.push(AnnouncementEntry {
+            target,
+            text,
+            time,
+            delivery: Some(hook),
+            delivered: false,
+            recipients: Vec::new(),
+            acknowledged_by: Vec::new(),
+        });
+        // scheduled announcements are kept, since their alarms refer to them by id
+        while self.announcements.len() > MAX_ANNOUNCEMENTS && self.announcements[0].delivered {
+            self.announcements.remove(0);
+            self.dropped_announcements += 1;
+        }
+        info!(
+            "scheduled announcement {} in session {} for {}",
+            announcement_id, context.session_id, time
+        );
+        Ok(request.into())
+    }
+
+    /// Gets called from an alarm and delivers a scheduled announcement
+    pub fn deliver_announcement(
+        &mut self,
+        announcement_id: usize,
+        session_id: u64,
+    ) -> InternEngineResponsePackage {
+        let Some(index) = announcement_id.checked_sub(self.dropped_announcements) else {
+            return Success.into(); // do nothing
+        };
+        let recipients = match self.announcements.get(index) {
+            Some(announcement) if !announcement.delivered => {
+                self.scope_recipients(announcement.target)
+            }
+            _ => return Success.into(), // do nothing
+        };
+        let announcement = &mut self.announcements[index];
+        announcement.delivery = None;
+        announcement.delivered = true;
+        announcement.recipients = recipients.clone();
+        EngineResponse {
+            response_action: Success,
+            broadcast_action: Some(BroadcastAction::Announcement {
+                session: session_id,
+                recipients,
+                announcement: announcement.to_sendable(announcement_id),
+            }),
+        }
+        .into()
+    }
+
+    /// Corresponds to an `EngineAction` and returns all announcements that the given team can
+    /// read. If no team is given, all announcements, including scheduled ones, are returned.
+    pub fn get_announcements(&self, team: Option<usize>) -> InternEngineResponsePackage {
+        SendAnnouncements(
+            self.announcements
+                .iter()
+                .enumerate()
+                .filter(|(_, announcement)| announcement.is_visible_to(team))
+                .map(|(index, announcement)| {
+                    announcement.to_sendable(self.dropped_announcements + index)
+                })
+                .collect(),
+        )
+        .into()
+    }
+
+    /// Corresponds to an `EngineAction` and marks an announcement as read by a player
+    pub fn acknowledge_announcement(
+        &mut self,
+        player_id: u64,
+        announcement_id: usize,
+    ) -> InternEngineResponseResult {
+        let team = self
+            .teams
+            .iter()
+            .position(|t| t.players.contains(&player_id))
+            .ok_or(NotFound(format!(
+                "team with the player with id {}",
+                player_id
+            )))?;
+        let announcement = announcement_id
+            .checked_sub(self.dropped_announcements)
+            .and_then(|index| self.announcements.get_mut(index))
+            .filter(|a| a.is_visible_to(Some(team)))
+            .ok_or(NotFound(format!("announcement with id {announcement_id}")))?;
+        if !announcement.acknowledged_by.contains(&player_id) {
+            announcement.acknowledged_by.push(player_id);
+        }
+        Ok(Success.into())
+    }
 }
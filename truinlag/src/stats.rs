@@ -0,0 +1,308 @@
+//! Statistics computed from past games. Nothing in here is stored, everything is computed from the
+//! `PastGameEntry`s whenever it is requested.
+
+use crate::{DBMirror, PastGameEntry, PastTeam, ZoneEntry, team::PeriodContext};
+use libtruinlag::{
+    ChallengeAnalytics, PastGameStats, PlayerStats, SkippedChallenge, TeamRole, TeamStats,
+};
+use std::collections::HashMap;
+
+/// How many of the most skipped challenges are included in the stats
+const NUM_MOST_SKIPPED: usize = 5;
+
+/// How many favourite zones are included in player stats
+const NUM_FAVOURITE_ZONES: usize = 3;
+
+/// Counts how often each challenge was skipped, i.e. how often it was in the `not_completed` list
+/// of a completed challenge. Challenges are identified by their id and title, since manually
+/// added challenges all have the id 0.
+fn count_skipped<'a>(
+    teams: impl Iterator<Item = &'a PastTeam>,
+    counts: &mut HashMap<(u64, String), u32>,
+) {
+    for team in teams {
+        for period in &team.periods {
+            if let PeriodContext::CompletedChallenge { not_completed, .. } = &period.context {
+                for challenge in not_completed {
+                    *counts
+                        .entry((challenge.id, challenge.title.clone()))
+                        .or_default() += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Turns skip counts into a list of the most skipped challenges, most skipped first
+fn most_skipped(counts: HashMap<(u64, String), u32>) -> Vec<SkippedChallenge> {
+    let mut skipped: Vec<SkippedChallenge> = counts
+        .into_iter()
+        .map(|((id, title), times)| SkippedChallenge { title, times, id })
+        .collect();
+    skipped.sort_by(|a, b| b.times.cmp(&a.times).then(a.title.cmp(&b.title)));
+    skipped.truncate(NUM_MOST_SKIPPED);
+    skipped
+}
+
+/// Returns the points of all challenges a team completed
+fn completed_points(team: &PastTeam) -> Vec<u64> {
+    team.periods
+        .iter()
+        .filter_map(|p| match p.context {
+            PeriodContext::CompletedChallenge { points, .. } => Some(points),
+            _ => None,
+        })
+        .collect()
+}
+
+fn average(values: &[u64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<u64>() as f64 / values.len() as f64
+    }
+}
+
+/// Returns the distance a team travelled in metres, based on its location history
+fn distance_travelled(team: &PastTeam) -> f64 {
+    team.loaction_history
+        .windows(2)
+        .map(|pair| pair[0].distance_to(&pair[1]))
+        .sum()
+}
+
+/// Returns how many seconds a team spent as catcher and as runner.
+///
+/// Past teams don't store their role for each period, but it can be inferred: catcher periods
+/// are spent as catcher and all other periods (completing challenges, getting caught) as runner.
+/// Whatever happened after the last period was spent in the team's end role.
+fn role_times(team: &PastTeam, game: &PastGameEntry) -> (i64, i64) {
+    let mut as_catcher = 0;
+    let mut as_runner = 0;
+    let mut last_time = game.start_time;
+    for period in &team.periods {
+        let duration = (period.end_time - last_time).num_seconds().max(0);
+        match period.context {
+            PeriodContext::Catcher { .. } => as_catcher += duration,
+            _ => as_runner += duration,
+        }
+        last_time = period.end_time;
+    }
+    let remaining = (game.end_time - last_time).num_seconds().max(0);
+    match team.end_role {
+        TeamRole::Catcher => as_catcher += remaining,
+        TeamRole::Runner => as_runner += remaining,
+    }
+    (as_catcher, as_runner)
+}
+
+fn team_stats(team_id: usize, team: &PastTeam, game: &PastGameEntry) -> TeamStats {
+    let hours = (game.end_time - game.start_time).num_seconds() as f64 / 3600.0;
+    let points = completed_points(team);
+    let (time_as_catcher, time_as_runner) = role_times(team, game);
+    let mut skipped = HashMap::new();
+    count_skipped(std::iter::once(team), &mut skipped);
+    TeamStats {
+        name: team.name.clone(),
+        points: team.points,
+        distance_travelled: distance_travelled(team),
+        points_per_hour: if hours > 0.0 {
+            team.points as f64 / hours
+        } else {
+            0.0
+        },
+        catches: team
+            .periods
+            .iter()
+            .filter(|p| matches!(p.context, PeriodContext::Catcher { .. }))
+            .count() as u32,
+        times_caught: team
+            .periods
+            .iter()
+            .filter(|p| matches!(p.context, PeriodContext::Caught { .. }))
+            .count() as u32,
+        completed_challenges: points.len() as u32,
+        average_challenge_points: average(&points),
+        time_as_catcher,
+        time_as_runner,
+        most_skipped: most_skipped(skipped),
+        team_id,
+    }
+}
+
+/// Computes the statistics of a past game
+pub fn past_game_stats(id: u64, game: &PastGameEntry) -> PastGameStats {
+    let teams: Vec<TeamStats> = game
+        .teams
+        .iter()
+        .enumerate()
+        .map(|(team_id, team)| team_stats(team_id, team, game))
+        .collect();
+    let points: Vec<u64> = game.teams.iter().flat_map(completed_points).collect();
+    let mut skipped = HashMap::new();
+    count_skipped(game.teams.iter(), &mut skipped);
+    PastGameStats {
+        name: game.name.clone(),
+        duration: (game.end_time - game.start_time).num_seconds(),
+        total_points: teams.iter().map(|t| t.points).sum(),
+        total_distance: teams.iter().map(|t| t.distance_travelled).sum(),
+        total_catches: teams.iter().map(|t| t.catches).sum(),
+        total_completed_challenges: teams.iter().map(|t| t.completed_challenges).sum(),
+        average_challenge_points: average(&points),
+        most_skipped: most_skipped(skipped),
+        teams,
+        id,
+    }
+}
+
+/// Running totals for a single challenge while compiling challenge analytics
+#[derive(Default)]
+struct ChallengeTally {
+    title: String,
+    offered: u32,
+    chosen: u32,
+    skipped: u32,
+    total_points: u64,
+    total_completion_time: i64,
+}
+
+/// Compiles analytics for every challenge that appeared in any of the given past games. Manually
+/// added challenges (with id 0) are left out, since they don't correspond to a challenge entry.
+pub fn challenge_analytics<'a>(
+    games: impl Iterator<Item = &'a PastGameEntry>,
+) -> Vec<ChallengeAnalytics> {
+    let mut tallies: HashMap<u64, ChallengeTally> = HashMap::new();
+    for game in games {
+        for team in &game.teams {
+            // challenges are (re)generated at the end of every period, so the time a challenge was
+            // offered at is the end of the previous period
+            let mut offered_at = game.start_time;
+            for period in &team.periods {
+                match &period.context {
+                    PeriodContext::CompletedChallenge {
+                        title,
+                        points,
+                        id,
+                        not_completed,
+                        ..
+                    } => {
+                        let tally = tallies.entry(*id).or_default();
+                        tally.title = title.clone();
+                        tally.offered += 1;
+                        tally.chosen += 1;
+                        tally.total_points += points;
+                        tally.total_completion_time +=
+                            (period.end_time - offered_at).num_seconds().max(0);
+                        for challenge in not_completed {
+                            let tally = tallies.entry(challenge.id).or_default();
+                            tally.title = challenge.title.clone();
+                            tally.offered += 1;
+                            tally.skipped += 1;
+                        }
+                    }
+                    PeriodContext::Caught { not_completed, .. } => {
+                        for challenge in not_completed {
+                            let tally = tallies.entry(challenge.id).or_default();
+                            tally.title = challenge.title.clone();
+                            tally.offered += 1;
+                        }
+                    }
+                    PeriodContext::Catcher { .. } | PeriodContext::Trophy { .. } => (),
+                }
+                offered_at = period.end_time;
+            }
+            for challenge in &team.end_challenges {
+                let tally = tallies.entry(challenge.id).or_default();
+                tally.title = challenge.title.clone();
+                tally.offered += 1;
+            }
+        }
+    }
+    let mut analytics: Vec<ChallengeAnalytics> = tallies
+        .into_iter()
+        .filter(|(id, _)| *id != 0)
+        .map(|(id, tally)| ChallengeAnalytics {
+            title: tally.title,
+            offered: tally.offered,
+            chosen: tally.chosen,
+            skipped: tally.skipped,
+            average_points: (tally.chosen > 0)
+                .then(|| tally.total_points as f64 / tally.chosen as f64),
+            average_completion_time: (tally.chosen > 0)
+                .then(|| tally.total_completion_time / tally.chosen as i64),
+            id,
+        })
+        .collect();
+    analytics.sort_by_key(|a| a.id);
+    analytics
+}
+
+/// Compiles the all-time stats of every player that took part in any of the given past games,
+/// indexed by player id. Player names are left empty, since they can change and are better filled
+/// in when the stats are sent.
+pub fn player_stats<'a>(
+    games: impl Iterator<Item = &'a PastGameEntry>,
+    zone_db: &DBMirror<ZoneEntry>,
+) -> HashMap<u64, PlayerStats> {
+    let mut stats: HashMap<u64, PlayerStats> = HashMap::new();
+    // zone ids per player, converted to favourite zones at the end
+    let mut zones: HashMap<u64, HashMap<u64, u32>> = HashMap::new();
+    for game in games {
+        for team in &game.teams {
+            if team.players.is_empty() {
+                continue;
+            }
+            let catches = team
+                .periods
+                .iter()
+                .filter(|p| matches!(p.context, PeriodContext::Catcher { .. }))
+                .count() as u32;
+            let times_caught = team
+                .periods
+                .iter()
+                .filter(|p| matches!(p.context, PeriodContext::Caught { .. }))
+                .count() as u32;
+            let completed_zones: Vec<Option<u64>> = team
+                .periods
+                .iter()
+                .filter_map(|p| match p.context {
+                    PeriodContext::CompletedChallenge { zone, .. } => Some(zone),
+                    _ => None,
+                })
+                .collect();
+            // the points are split evenly, the first players get what doesn't divide
+            let share = team.points / team.players.len().max(1) as u64;
+            let remainder = team.points % team.players.len().max(1) as u64;
+            for (index, player_id) in team.players.iter().enumerate() {
+                let player = stats.entry(*player_id).or_insert_with(|| PlayerStats {
+                    id: *player_id,
+                    ..Default::default()
+                });
+                player.games_played += 1;
+                player.points_contributed += share + u64::from((index as u64) < remainder);
+                player.catches += catches;
+                player.times_caught += times_caught;
+                player.challenges_completed += completed_zones.len() as u32;
+                let player_zones = zones.entry(*player_id).or_default();
+                for zone in completed_zones.iter().flatten() {
+                    *player_zones.entry(*zone).or_default() += 1;
+                }
+            }
+        }
+    }
+    for (player_id, player_zones) in zones {
+        if let Some(player) = stats.get_mut(&player_id) {
+            let mut favourites: Vec<(u64, u32)> = player_zones
+                .into_iter()
+                .filter_map(|(zone_id, count)| {
+                    // zones may have been deleted since
+                    zone_db.get(zone_id).ok().map(|z| (z.contents.zone, count))
+                })
+                .collect();
+            favourites.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            favourites.truncate(NUM_FAVOURITE_ZONES);
+            player.favourite_zones = favourites;
+        }
+    }
+    stats
+}
@@ -1,6 +1,10 @@
 use std::{collections::HashMap, rc::Rc};
 
-use crate::{InternEngineCommand, SessionContext, TimerHook, runtime::RuntimeRequest};
+use crate::{
+    InternEngineCommand, SessionContext, TimerHook,
+    location::{self, PlayerFixes, SuspiciousLocationEntry},
+    runtime::RuntimeRequest,
+};
 
 use super::{
     Config, DBEntry,
@@ -20,6 +24,7 @@ pub struct TeamEntry {
     #[serde(default)]
     pub picture: Option<u64>,
     pub players: Vec<u64>,
+    /// how many locations each player sent and how many of them were used for the team's location
     #[serde(default)]
     pub player_location_counts: HashMap<u64, (u32, u32)>,
     pub discord_channel: Option<u64>,
@@ -30,11 +35,17 @@ pub struct TeamEntry {
     #[serde(default)]
     pub current_zone_id: u64,
     #[serde(default)]
-    pub current_sector_id: Option<u64>, // should only be Some after completing a challenge in Zürich
+    pub current_sector_id: Option<u64>, // only Some in Zürich, from a challenge or the location
     pub current_location: Option<DetailedLocation>,
     pub locations: Vec<MinimalLocation>,
     #[serde(default)]
     pub location_sending_player: Option<u64>,
+    /// locations sent by the team's players that were ignored because they seemed implausible
+    #[serde(default)]
+    pub suspicious_locations: Vec<SuspiciousLocationEntry>,
+    /// the latest locations of the team's players, which are fused into the team's location
+    #[serde(default)]
+    pub player_fixes: HashMap<u64, PlayerFixes>,
     pub challenges: Vec<InOpenChallenge>,
     pub periods: Vec<Period>,
     #[serde(default)]
@@ -81,6 +92,8 @@ pub enum PeriodContext {
         id: u64,
         #[serde(default)]
         not_completed: Vec<Challenge>,
+        #[serde(default)]
+        verification: Option<CompletionVerification>, // None if verification was turned off
     },
 }
 
@@ -130,6 +143,8 @@ impl TeamEntry {
             current_location: None,
             locations: Vec::new(),
             location_sending_player: None,
+            suspicious_locations: Vec::new(),
+            player_fixes: HashMap::new(),
             periods: Vec::new(),
             current_zone_id: config.start_zone,
             current_sector_id: None,
@@ -139,6 +154,16 @@ impl TeamEntry {
 
     /// Converts the engine-internal `TeamEntry` type into a sendable truinlag `Team` type
     pub fn to_sendable(&self, index: usize, context: &SessionContext) -> libtruinlag::Team {
+        self.to_localised_sendable(index, context, None)
+    }
+
+    /// Like `to_sendable`, but with the challenges in the given language where possible
+    pub fn to_localised_sendable(
+        &self,
+        index: usize,
+        context: &SessionContext,
+        language: Option<&str>,
+    ) -> libtruinlag::Team {
         libtruinlag::Team {
             colour: self.colour,
             role: self.role,
@@ -160,7 +185,11 @@ impl TeamEntry {
                         .to_sendable(*p)
                 })
                 .collect(),
-            challenges: self.challenges.iter().map(|c| c.to_sendable()).collect(),
+            challenges: self
+                .challenges
+                .iter()
+                .map(|c| c.to_localised_sendable(language))
+                .collect(),
             completed_challenges: self
                 .periods
                 .iter()
@@ -172,6 +201,7 @@ impl TeamEntry {
                         id,
                         zone: _,
                         not_completed,
+                        verification,
                     } => Some(CompletedChallenge {
                         title: title.clone(),
                         description: description.clone(),
@@ -180,6 +210,7 @@ impl TeamEntry {
                         picture_ids: p.pictures.clone(),
                         not_completed: not_completed.clone(),
                         id: *id,
+                        verification: verification.clone(),
                     }),
                     _ => None,
                 })
@@ -190,6 +221,12 @@ impl TeamEntry {
                 .clone()
                 .map(|hook| hook.end_time.timestamp()),
             period_id: self.period_id(),
+            zone: context
+                .engine_context
+                .zone_db
+                .get(self.current_zone_id)
+                .ok()
+                .map(|z| z.contents.zone),
         }
     }
 
@@ -197,36 +234,50 @@ impl TeamEntry {
         self.periods.len()
     }
 
+    /// Adds a plausible location sent by one of the team's players and fuses it with the latest
+    /// locations of the other players into the team's new location. The new location is only
+    /// returned if the player's location was used for it, i.e. if the player isn't separated from
+    /// the rest of the team. Also returns the players that just got separated from the rest of
+    /// the team, along with their distance to it.
     pub fn add_location(
         &mut self,
         location: DetailedLocation,
         by_player: u64,
-    ) -> Option<DetailedLocation> {
+        config: &Config,
+    ) -> (Option<DetailedLocation>, Vec<(u64, f64)>) {
+        let fixes = self.player_fixes.entry(by_player).or_default();
+        fixes.accuracy_sum += location.accuracy as u64;
+        fixes.latest = Some(location.clone());
+        let fused = location::fuse(
+            self.player_fixes
+                .iter()
+                .filter(|(player, _)| self.players.contains(player))
+                .filter_map(|(player, f)| f.latest.as_ref().map(|l| (*player, l))),
+            config,
+        )
+        .expect("the location that was just added is always there");
         let (total_count, accepted_count) = self
             .player_location_counts
             .get(&by_player)
             .cloned()
             .unwrap_or((0, 0));
-        self.player_location_counts
-            .insert(by_player, (total_count + 1, accepted_count));
-        match self.current_location.clone() {
-            None => Some(self.definitely_add_location(location, by_player)),
-            Some(old_location) => {
-                let time_since_last_location = location.timestamp - old_location.timestamp;
-                if location.accuracy < 20
-                    || (location.accuracy as f32 / old_location.accuracy as f32) < 1.0
-                    || (time_since_last_location > 15
-                        && (location.accuracy as f32 / old_location.accuracy as f32) < 1.5)
-                    || time_since_last_location > 30
-                {
-                    self.location_sending_player = Some(by_player);
-                    self.player_location_counts
-                        .insert(by_player, (total_count + 1, accepted_count + 1));
-                    return Some(self.definitely_add_location(location, by_player));
-                }
-                None
+        let accepted = !fused.outliers.iter().any(|(p, _)| *p == by_player);
+        self.player_location_counts.insert(
+            by_player,
+            (total_count + 1, accepted_count + accepted as u32),
+        );
+        let mut new_splits = Vec::new();
+        for (player, fixes) in self.player_fixes.iter_mut() {
+            let outlier = fused.outliers.iter().find(|(p, _)| p == player);
+            if outlier.is_some() && !fixes.split {
+                fixes.splits += 1;
+                new_splits.extend(outlier);
             }
+            fixes.split = outlier.is_some();
         }
+        let location =
+            accepted.then(|| self.definitely_add_location(fused.location, fused.main_player));
+        (location, new_splits)
     }
 
     fn definitely_add_location(
@@ -250,18 +301,29 @@ impl TeamEntry {
         location
     }
 
-    /// Returns the team's current location as a geo `Point`.
-    pub fn location_as_point(&self) -> Option<geo::Point<f64>> {
-        self.current_location
-            .clone()
-            .map(|l| geo::Point::from((l.latitude as f64, l.longitude as f64)))
-    }
-
-    /// Returns the team's current location
-    pub fn location(&self) -> Option<(f32, f32)> {
-        self.current_location
-            .clone()
-            .map(|l| (l.latitude, l.longitude))
+    /// Summarises how well the location tracking of one of the team's players works
+    pub fn location_quality(&self, team_id: usize, player: u64) -> PlayerLocationQuality {
+        let (received, used) = self
+            .player_location_counts
+            .get(&player)
+            .cloned()
+            .unwrap_or((0, 0));
+        let fixes = self.player_fixes.get(&player).cloned().unwrap_or_default();
+        PlayerLocationQuality {
+            team: team_id,
+            player,
+            received,
+            used,
+            suspicious: self
+                .suspicious_locations
+                .iter()
+                .filter(|l| l.player == player)
+                .count() as u32,
+            average_accuracy: (received > 0).then(|| fixes.accuracy_sum as f32 / received as f32),
+            last_location: fixes.latest.map(|l| l.timestamp),
+            separated: fixes.split,
+            times_separated: fixes.splits,
+        }
     }
 
     pub fn seen_challenges(&self) -> Vec<Challenge> {
@@ -275,6 +337,7 @@ impl TeamEntry {
                     points,
                     id,
                     not_completed,
+                    verification: _,
                 } => {
                     ret.push(Challenge {
                         title: title.clone(),
@@ -361,6 +424,8 @@ impl TeamEntry {
         self.player_location_counts = HashMap::new();
         self.current_location = None;
         self.location_sending_player = None;
+        self.suspicious_locations = Vec::new();
+        self.player_fixes = HashMap::new();
         self.current_zone_id = context
             .engine_context
             .zone_db
@@ -447,6 +512,7 @@ impl TeamEntry {
                         zone: _,
                         points: _,
                         not_completed: _,
+                        verification: _,
                         id,
                     } => Some(id),
                     _ => None,
@@ -507,19 +573,14 @@ impl TeamEntry {
                 .expect("team is in a sector that is not in db")
         });
 
-        // If the sector is None or Zone is not 110, we use Zone adjacency (i.e. the "sectors" field from zones sheet)
+        // If the sector is None, we use Zone adjacency (i.e. the "sectors" field from zones sheet)
         // If it is Some, we use Sector adjacency (i.e. the "neighbors" field from sectors sheet)
         // both of these are Strings of capital letters representing sectors (e.g. "XOD")
+        // The sector is reset whenever the team changes zones, so it can't be stale.
 
-        let current_zone = team_zone.contents.zone;
-        let adjacent_sectors: Vec<u64> = if current_zone == 110 {
-            if let Some(sector) = &team_sector {
-                sector.contents.neighbours.clone()
-            } else {
-                team_zone.contents.sectors.clone()
-            }
-        } else {
-            team_zone.contents.sectors.clone()
+        let adjacent_sectors: Vec<u64> = match &team_sector {
+            Some(sector) => sector.contents.neighbours.clone(),
+            None => team_zone.contents.sectors.clone(),
         };
 
         let sector_filter: Filter = Rc::new(move |c| -> bool {
@@ -946,6 +1007,32 @@ impl TeamEntry {
                 id
             ))),
             Some(completed) => {
+                let verification = match context.config.completion_verification {
+                    VerificationMode::Off => None,
+                    mode => {
+                        let verification =
+                            completed.verify_location(self.current_location.as_ref(), context);
+                        let reason = match &verification {
+                            CompletionVerification::Failed(reason) => Some(reason.clone()),
+                            // without a location, the team can't prove where it is
+                            CompletionVerification::NoLocation => {
+                                Some("the team's location is missing or too old".into())
+                            }
+                            CompletionVerification::Unchecked
+                            | CompletionVerification::Verified => None,
+                        };
+                        if let Some(reason) = reason {
+                            warn!(
+                                "engine: team {} completed {} unverified: {}",
+                                self.name, completed.title, reason
+                            );
+                            if let VerificationMode::Enforce = mode {
+                                return Err(commands::Error::NotAtChallenge(reason));
+                            }
+                        }
+                        Some(verification)
+                    }
+                };
                 let request = self
                     .grace_period_end
                     .take()
@@ -964,6 +1051,7 @@ impl TeamEntry {
                         .filter(|c| c.id != completed.id)
                         .map(|c| c.to_sendable())
                         .collect(),
+                    verification,
                 });
                 if let Some(zone) = completed.zone {
                     self.current_zone_id = zone;